@@ -23,6 +23,7 @@ extern crate rocket_contrib;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde_json;
 extern crate uuid;
 
 use rocket::request::Request;
@@ -65,7 +66,12 @@ fn main() {
             routes![
                 list_compositions,
                 submit_composition,
-                get_shared_composition
+                get_shared_composition,
+                export_heightmap,
+                export_animation,
+                analyze_shared_composition,
+                ir_json_schema,
+                definition_json_schema
             ],
         ).catch(catchers![not_found, internal_error])
         .manage(DbPool(create_db_pool()))
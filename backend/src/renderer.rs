@@ -4,15 +4,41 @@ use std::env::temp_dir;
 use std::fs::{remove_file, File};
 
 use image::{ImageBuffer, ImageRgb8, Pixel, Rgb, PNG};
+use libcomposition::animation::{encode_animation, AnimationConf, AnimationFormat};
 use libcomposition::color_schemes::ColorFunction;
-use libcomposition::util::build_tree_from_def;
+use libcomposition::heightmap::{write_heightmap, HeightmapFormat, ValueRemap};
+use libcomposition::limits::ResourceLimits;
+use libcomposition::render::{render_rgba, render_values};
+use libcomposition::stats::{AnalysisConf, ValueStats};
+use libcomposition::util::{build_def_from_str, build_tree_from_def};
 use libcomposition::CompositionTree;
-use noise::NoiseFn;
 use uuid::Uuid;
 
 use ameotrack::upload_image;
 
 const IMAGE_SIZE: u32 = 400;
+/// The largest width/height of heightmaps that can be exported through the API.  Exports are rendered on demand for
+/// unauthenticated requests, so this keeps each one within a small multiple of the cost of rendering a thumbnail.
+pub const MAX_HEIGHTMAP_SIZE: usize = 512;
+/// The largest width/height of animations that can be exported through the API
+pub const MAX_ANIMATION_SIZE: usize = 512;
+/// The largest number of frames that animations exported through the API can have
+pub const MAX_ANIMATION_FRAMES: usize = 240;
+/// Frame rate of animations exported through the API
+const ANIMATION_FPS: f64 = 30.0;
+/// The engine renders one tick per browser frame at ~60 FPS, so this keeps exported animations at the same speed.
+const TICKS_PER_ANIMATION_FRAME: f64 = 2.0;
+/// The number of points in time sampled when analyzing compositions over a time range
+const ANALYSIS_TIME_SAMPLES: usize = 16;
+
+/// Checks that a definition string is within the resource limits that compositions rendered by the backend are held
+/// to, returning an error describing the limit that it exceeds if it isn't.
+pub fn check_resource_limits(def: &str) -> Result<(), String> {
+    let (_, tree_def) = build_def_from_str(def).map_err(|err| err.message)?;
+    ResourceLimits::default()
+        .check_definition(&tree_def.root_node)
+        .map_err(|err| format!("Composition rejected: {}", err))
+}
 
 /// Composes a noise module definition, generates a thumbnail image, uploads it to AmeoTrack,
 /// and returns the resulting image URL.
@@ -20,12 +46,13 @@ pub fn create_thumbnail(def: &str) -> Result<String, String> {
     // create a `CompositionTree` from the provided definition string
     let (color_fn, tree): (ColorFunction, CompositionTree) = build_tree_from_def(def)?;
 
-    // use this tree node to populate a buffer with pixel data using the selected color function to
-    // populate an image buffer with pixel data
+    // render the tree using the selected color function (or relief mode) and use the resulting
+    // pixel data to populate an image buffer
+    let size = IMAGE_SIZE as usize;
+    let pixels = render_rgba(&tree, color_fn, size, size, 0.0);
     let img_buf = ImageBuffer::from_fn(IMAGE_SIZE, IMAGE_SIZE, |x, y| {
-        let val: f64 = tree.get([x as f64, y as f64, 0.0]);
-        let color = color_fn.colorize(val as f32);
-        Rgb::from_channels(color[0], color[1], color[2], 255u8)
+        let i = ((y as usize * size) + x as usize) * 4;
+        Rgb::from_channels(pixels[i], pixels[i + 1], pixels[i + 2], 255u8)
     });
 
     // create a temporary file and write the image to it
@@ -58,3 +85,86 @@ pub fn create_thumbnail(def: &str) -> Result<String, String> {
 
     res
 }
+
+/// Composes a noise module definition and exports its raw values as a square heightmap in the supplied format.
+pub fn create_heightmap(
+    def: &str,
+    format: HeightmapFormat,
+    size: usize,
+    remap: ValueRemap,
+) -> Result<Vec<u8>, String> {
+    if size == 0 || size > MAX_HEIGHTMAP_SIZE {
+        return Err(format!(
+            "Heightmap size must be between 1 and {}!",
+            MAX_HEIGHTMAP_SIZE
+        ));
+    }
+
+    let (_, tree): (ColorFunction, CompositionTree) = build_tree_from_def(def)?;
+    let values = render_values(&tree, size, size, 0.0);
+
+    let mut buf: Vec<u8> = Vec::new();
+    write_heightmap(&mut buf, format, size, size, &values, remap)?;
+    Ok(buf)
+}
+
+/// Composes a noise module definition and renders it as a square animation starting at time 0 in the supplied
+/// format, optionally looping seamlessly.
+pub fn create_animation(
+    def: &str,
+    format: AnimationFormat,
+    size: usize,
+    frame_count: usize,
+    seamless_loop: bool,
+) -> Result<Vec<u8>, String> {
+    if size == 0 || size > MAX_ANIMATION_SIZE {
+        return Err(format!(
+            "Animation size must be between 1 and {}!",
+            MAX_ANIMATION_SIZE
+        ));
+    } else if frame_count == 0 || frame_count > MAX_ANIMATION_FRAMES {
+        return Err(format!(
+            "Animations must have between 1 and {} frames!",
+            MAX_ANIMATION_FRAMES
+        ));
+    }
+
+    let (color_fn, tree): (ColorFunction, CompositionTree) = build_tree_from_def(def)?;
+    let frame_span = if seamless_loop {
+        frame_count
+    } else {
+        frame_count - 1
+    };
+    let conf = AnimationConf {
+        start_time: 0.0,
+        end_time: frame_span as f64 * TICKS_PER_ANIMATION_FRAME,
+        frame_count,
+        fps: ANIMATION_FPS,
+        seamless_loop,
+    };
+
+    encode_animation(&tree, color_fn, size, size, format, &conf)
+}
+
+/// Composes a noise module definition and computes statistics of its values over the area of a thumbnail, sampling
+/// `duration` ticks starting at time 0.  If `duration` is 0, only a single frame is sampled.
+pub fn analyze_composition(def: &str, duration: f64) -> Result<ValueStats, String> {
+    if !(duration >= 0.) {
+        return Err(String::from("The analyzed duration must not be negative!"));
+    }
+
+    let (_, tree): (ColorFunction, CompositionTree) = build_tree_from_def(def)?;
+    let conf = AnalysisConf {
+        width: IMAGE_SIZE as f64,
+        height: IMAGE_SIZE as f64,
+        end_time: duration,
+        time_samples: if duration > 0. {
+            ANALYSIS_TIME_SAMPLES
+        } else {
+            1
+        },
+        ..AnalysisConf::default()
+    };
+
+    tree.analyze(&conf)
+}
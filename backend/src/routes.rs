@@ -6,18 +6,62 @@ use diesel::expression::sql_literal::sql;
 use diesel::prelude::*;
 use diesel::types::Binary;
 use htmlescape::encode_minimal;
+use libcomposition::animation::AnimationFormat;
+use libcomposition::heightmap::{HeightmapFormat, ValueRemap};
+use libcomposition::json_schema::{definition_schema, ir_schema};
+use libcomposition::stats::ValueStats;
+use rocket::http::{ContentType, Status};
+use rocket::response::content::Content;
+use rocket::response::status;
 use rocket::State;
 use rocket_contrib::Json;
+use serde_json::Value;
 
 use db_interface::DbPool;
-use models::{NewSharedComposition, QueryResult, SharedComposition, UserSharedComposition};
-use renderer::create_thumbnail;
+use models::{
+    ErrorMessage, NewSharedComposition, QueryResult, SharedComposition, UserSharedComposition,
+};
+use renderer::{
+    analyze_composition, check_resource_limits, create_animation, create_heightmap, create_thumbnail,
+};
 use schema::shared_compositions::dsl as shared_compositions_dsl;
 use schema::shared_compositions::table as shared_compositions_table;
 use util::debug;
 
 const ITEMS_PER_PAGE: i64 = 10;
 
+/// Binary exports can't be wrapped in a `QueryResult`, so errors are returned with an error status and the same body
+/// as the error catchers instead.
+type ExportResult = Result<Content<Vec<u8>>, status::Custom<Json<ErrorMessage>>>;
+
+fn error_response(status: Status, message: String) -> status::Custom<Json<ErrorMessage>> {
+    status::Custom(
+        status,
+        Json(ErrorMessage {
+            status: status.code,
+            message,
+        }),
+    )
+}
+
+/// Retrieves the shared composition with the supplied ID for exporting it, returning a 404 error if it doesn't exist.
+fn find_export_composition(
+    composition_id: i32,
+    conn_pool: &State<DbPool>,
+) -> Result<SharedComposition, status::Custom<Json<ErrorMessage>>> {
+    let conn = &*conn_pool.inner().get_conn();
+    shared_compositions_dsl::shared_compositions
+        .find(composition_id)
+        .first(conn)
+        .map_err(|err| match err {
+            diesel::result::Error::NotFound => error_response(
+                Status::NotFound,
+                format!("No shared composition with ID {} exists!", composition_id),
+            ),
+            err => error_response(Status::InternalServerError, debug(err)),
+        })
+}
+
 enum SortMethod {
     Newest,
     Oldest,
@@ -77,6 +121,12 @@ pub fn submit_composition(
 ) -> Json<QueryResult<NewSharedComposition>> {
     let conn = &*conn_pool.inner().get_conn();
 
+    // definitions come from untrusted users, so reject those that are too expensive to render before doing anything
+    // else with them
+    if let Err(err) = check_resource_limits(&user_composition.definition_string) {
+        return Json(QueryResult::Error(err));
+    }
+
     // build the noise function, create a tumbnail image, upload that to AmeoTrack,
     // and retrieve the URL.
     let thumb_res: String = match create_thumbnail(&user_composition.definition_string) {
@@ -119,3 +169,103 @@ pub fn get_shared_composition(
         Err(err) => QueryResult::Error(err),
     })
 }
+
+/// Exports the raw values of a shared composition as a heightmap.  `format` is one of `png16`, `f32`, `f64`, `pfm`, or
+/// `exr` and `range` is either `auto`, `none`, or a fixed input range in the format `min:max`.
+#[get("/export_heightmap/<composition_id>/<format>/<size>/<range>")]
+pub fn export_heightmap(
+    composition_id: i32,
+    format: String,
+    size: usize,
+    range: String,
+    conn_pool: State<DbPool>,
+) -> ExportResult {
+    let bad_request = |err| error_response(Status::BadRequest, err);
+    let format: HeightmapFormat = format.parse().map_err(bad_request)?;
+    let remap: ValueRemap = range.parse().map_err(bad_request)?;
+
+    let composition = find_export_composition(composition_id, &conn_pool)?;
+    let buf = create_heightmap(&composition.definition_string, format, size, remap)
+        .map_err(bad_request)?;
+    let content_type = match format {
+        HeightmapFormat::Png16 => ContentType::PNG,
+        _ => ContentType::Binary,
+    };
+
+    Ok(Content(content_type, buf))
+}
+
+/// Renders a shared composition as an animation.  `format` is either `gif` or `apng` and `mode` is either `loop` for
+/// a seamlessly looping animation or `once`.
+#[get("/export_animation/<composition_id>/<format>/<size>/<frame_count>/<mode>")]
+pub fn export_animation(
+    composition_id: i32,
+    format: String,
+    size: usize,
+    frame_count: usize,
+    mode: String,
+    conn_pool: State<DbPool>,
+) -> ExportResult {
+    let bad_request = |err| error_response(Status::BadRequest, err);
+    let format: AnimationFormat = format.parse().map_err(bad_request)?;
+    let seamless_loop = match mode.as_str() {
+        "loop" => true,
+        "once" => false,
+        _ => return Err(bad_request(format!("Invalid animation mode: {}", mode))),
+    };
+    let content_type = match format {
+        AnimationFormat::Gif => ContentType::GIF,
+        AnimationFormat::Apng => ContentType::PNG,
+        AnimationFormat::Frames => {
+            return Err(bad_request(
+                "Frame sequences can't be exported through the API!".into(),
+            ))
+        }
+    };
+
+    let composition = find_export_composition(composition_id, &conn_pool)?;
+    let buf = create_animation(
+        &composition.definition_string,
+        format,
+        size,
+        frame_count,
+        seamless_loop,
+    ).map_err(bad_request)?;
+    Ok(Content(content_type, buf))
+}
+
+/// Returns statistics about the values produced by a shared composition: the minimum, maximum, mean, standard
+/// deviation, percentiles, and a histogram.  Values are sampled over `duration` ticks starting at time 0.
+#[get("/analyze_composition/<composition_id>/<duration>")]
+pub fn analyze_shared_composition(
+    composition_id: i32,
+    duration: f64,
+    conn_pool: State<DbPool>,
+) -> Json<QueryResult<ValueStats>> {
+    let conn = &*conn_pool.inner().get_conn();
+    let result: Result<ValueStats, String> = shared_compositions_dsl::shared_compositions
+        .find(composition_id)
+        .first(conn)
+        .map_err(debug)
+        .and_then(|composition: SharedComposition| {
+            analyze_composition(&composition.definition_string, duration)
+        });
+
+    Json(match result {
+        Ok(stats) => QueryResult::Success(stats),
+        Err(err) => QueryResult::Error(err),
+    })
+}
+
+/// Returns a JSON Schema describing definition strings in the IR format that compositions are shared in, which editors
+/// can use to validate them.
+#[get("/schema/ir")]
+pub fn ir_json_schema() -> Json<Value> {
+    Json(ir_schema())
+}
+
+/// Returns a JSON Schema describing composition tree definitions in the format used by `libcomposition`.
+#[get("/schema/definition")]
+pub fn definition_json_schema() -> Json<Value> {
+    Json(definition_schema())
+}
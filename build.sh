@@ -1,10 +1,24 @@
 #!/bin/sh
 
-# Compiles the Asm.JS engine backend and symlinks it into the static code directory
-cd engine && ./emscripten.sh
-cd ..
-echo "Symlinking \`./engine/target/asmjs-unknown-emscripten/release/noise-backend.js\` to \`./public/compiled.js\`"
-ln -sf $(pwd)/engine/target/asmjs-unknown-emscripten/release/noise-backend.js $(pwd)/public/compiled.js
+# Compiles the Asm.JS engine backend and symlinks it into the static code directory.  The frontend loads this engine, but
+# it's only built if the Emscripten toolchain is available.
+if hash emcc 2>/dev/null; then
+  cd engine && ./emscripten.sh
+  cd ..
+  echo "Symlinking \`./engine/target/asmjs-unknown-emscripten/release/noise-backend.js\` to \`./public/compiled.js\`"
+  ln -sf $(pwd)/engine/target/asmjs-unknown-emscripten/release/noise-backend.js $(pwd)/public/compiled.js
+else
+  echo "Emscripten not found; skipping the Asm.JS engine.  The frontend won't be able to render anything without it."
+fi
+
+# Compiles the WebAssembly engine and generates its JS bindings into the static code directory if the
+# `wasm32-unknown-unknown` target and the wasm-bindgen CLI are installed
+if rustup target list --installed 2>/dev/null | grep -q wasm32-unknown-unknown && hash wasm-bindgen 2>/dev/null; then
+  cd engine-wasm && ./build.sh
+  cd ..
+else
+  echo "The wasm32-unknown-unknown target or wasm-bindgen CLI isn't installed; skipping the WebAssembly engine"
+fi
 
 # Builds the frontend, including the compiled code
 yarn run build
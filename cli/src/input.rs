@@ -0,0 +1,108 @@
+//! Functions for reading composition definitions in either of the supported formats.
+
+use std::fs::File;
+use std::io::{self, Read};
+
+use serde_json::{self, Value};
+
+use libcomposition::color_schemes::ColorFunction;
+use libcomposition::definition::CompositionTreeDefinition;
+use libcomposition::ir::IrNode;
+use libcomposition::limits::ResourceLimits;
+use libcomposition::util::{build_def_from_ir, check_def_limits, BuildError, BuildStage};
+
+/// The formats that compositions can be read from and written to.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum InputFormat {
+    /// The `IrNode` tree produced by the frontend and stored for shared compositions
+    Ir,
+    /// A serialized `CompositionTreeDefinition`
+    Definition,
+}
+
+/// An error encountered while loading an input, including the step at which it failed.
+#[derive(Debug, Serialize)]
+pub struct InputError {
+    pub format: Option<InputFormat>,
+    pub stage: BuildStage,
+    pub message: String,
+}
+
+impl InputError {
+    fn from_build_error(format: InputFormat, err: BuildError) -> Self {
+        InputError {
+            format: Some(format),
+            stage: err.stage,
+            message: err.message,
+        }
+    }
+}
+
+/// A successfully loaded input along with the color function selected in it.
+pub struct LoadedInput {
+    pub format: InputFormat,
+    pub color_fn: Option<ColorFunction>,
+    pub definition: CompositionTreeDefinition,
+}
+
+/// Reads the full contents of the file at `path`, or stdin if the path is `-`.
+pub fn read_input(path: &str) -> Result<String, String> {
+    let mut buf = String::new();
+    if path == "-" {
+        io::stdin()
+            .read_to_string(&mut buf)
+            .map_err(|err| format!("Unable to read input from stdin: {}", err))?;
+    } else {
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut buf))
+            .map_err(|err| format!("Unable to read input file {}: {}", path, err))?;
+    }
+
+    Ok(buf)
+}
+
+/// Tree definitions have a top-level `global_conf` key, which IR nodes never have.
+fn detect_format(json: &Value) -> InputFormat {
+    if json.get("global_conf").is_some() {
+        InputFormat::Definition
+    } else {
+        InputFormat::Ir
+    }
+}
+
+fn parse_error(format: Option<InputFormat>, err: serde_json::Error) -> InputError {
+    InputError {
+        format,
+        stage: BuildStage::Parse,
+        message: format!("Error while parsing the provided definition string: {}", err),
+    }
+}
+
+/// Parses the raw input into a tree definition, detecting its format if one isn't provided.  Inputs in either format
+/// are checked against the default `ResourceLimits` just like definitions built by the backend are.
+pub fn load_input(raw: &str, format: Option<InputFormat>) -> Result<LoadedInput, InputError> {
+    let json: Value = serde_json::from_str(raw).map_err(|err| parse_error(format, err))?;
+    let format = format.unwrap_or_else(|| detect_format(&json));
+
+    let (color_fn, definition) = match format {
+        InputFormat::Ir => {
+            let ir: IrNode =
+                serde_json::from_value(json).map_err(|err| parse_error(Some(format), err))?;
+            build_def_from_ir(ir).map_err(|err| InputError::from_build_error(format, err))?
+        }
+        InputFormat::Definition => {
+            let definition: CompositionTreeDefinition =
+                serde_json::from_value(json).map_err(|err| parse_error(Some(format), err))?;
+            (definition.global_conf.color_function, definition)
+        }
+    };
+    check_def_limits(&definition, &ResourceLimits::default())
+        .map_err(|err| InputError::from_build_error(format, err))?;
+
+    Ok(LoadedInput {
+        format,
+        color_fn: Some(color_fn),
+        definition,
+    })
+}
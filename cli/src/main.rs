@@ -0,0 +1,613 @@
+//! Headless command line interface for working with noise function compositions.  Allows compositions to be rendered
+//! to images, validated, and converted between the IR format produced by the frontend and the tree definition format
+//! without needing a browser.
+
+#[macro_use]
+extern crate clap;
+extern crate libcomposition;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
+use std::fs::File;
+use std::path::Path;
+use std::io::{self, Write};
+use std::process::exit;
+use std::str::FromStr;
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+
+use libcomposition::animation::{
+    encode_animation, render_animation_values, write_frame_directory, AnimationConf,
+    AnimationFormat,
+};
+use libcomposition::color_schemes::ColorFunction;
+use libcomposition::heightmap::{write_heightmap, HeightmapFormat, ValueRemap};
+use libcomposition::mesh::{build_mesh, write_mesh, MeshConf, MeshFormat};
+use libcomposition::relief::{
+    ReliefMode, DEFAULT_RELIEF_STRENGTH, DEFAULT_SUN_ALTITUDE, DEFAULT_SUN_AZIMUTH,
+};
+use libcomposition::render::{
+    render_tileable_values, render_values, render_values_2d, shade_values, write_png, TileConf,
+    TileMode,
+};
+use libcomposition::CompositionTree;
+
+mod input;
+#[cfg(test)]
+mod tests;
+
+use input::{load_input, read_input, InputFormat, LoadedInput};
+
+/// Output of the `validate` subcommand, printed as JSON.
+#[derive(Serialize)]
+struct ValidationReport {
+    valid: bool,
+    format: Option<InputFormat>,
+    error: Option<input::InputError>,
+}
+
+fn input_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("INPUT")
+            .help("Path to the definition file to read, or `-` to read from stdin")
+            .required(true),
+        Arg::with_name("format")
+            .long("format")
+            .takes_value(true)
+            .possible_values(&["ir", "definition"])
+            .help("Format of the input file.  Detected automatically if not supplied."),
+    ]
+}
+
+fn parse_arg<T: FromStr>(matches: &ArgMatches, name: &str) -> Result<Option<T>, String> {
+    match matches.value_of(name) {
+        Some(raw_val) => raw_val
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("Invalid value supplied for `--{}`: {}", name, raw_val)),
+        None => Ok(None),
+    }
+}
+
+fn parse_format(matches: &ArgMatches) -> Option<InputFormat> {
+    match matches.value_of("format") {
+        Some("ir") => Some(InputFormat::Ir),
+        Some("definition") => Some(InputFormat::Definition),
+        _ => None,
+    }
+}
+
+fn parse_color_fn(matches: &ArgMatches) -> Result<Option<ColorFunction>, String> {
+    match matches.value_of("color-function") {
+        Some(raw_val) => ColorFunction::from_str(raw_val).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Opens the output file at the supplied path, or stdout if the path is `-` or not provided.
+fn open_output(path: Option<&str>) -> Result<Box<Write>, String> {
+    match path {
+        None | Some("-") => Ok(Box::new(io::stdout())),
+        Some(path) => File::create(path)
+            .map(|file| Box::new(file) as Box<Write>)
+            .map_err(|err| format!("Unable to create output file {}: {}", path, err)),
+    }
+}
+
+/// Settings shared by all subcommands that render a composition.
+struct RenderSettings {
+    color_fn: ColorFunction,
+    tree: CompositionTree,
+    width: usize,
+    height: usize,
+    time: f64,
+    tile_conf: Option<TileConf>,
+    /// Evaluates the tree in 2D, ignoring time
+    two_d: bool,
+}
+
+impl RenderSettings {
+    /// Renders the raw values of the tree, making them tileable if a tiling mode was selected.
+    fn render_values(&self) -> Result<Vec<f64>, String> {
+        match self.tile_conf {
+            Some(ref tile_conf) => {
+                render_tileable_values(&self.tree, self.width, self.height, self.time, tile_conf)
+            }
+            None if self.two_d => Ok(render_values_2d(&self.tree, self.width, self.height)),
+            None => Ok(render_values(
+                &self.tree,
+                self.width,
+                self.height,
+                self.time,
+            )),
+        }
+    }
+}
+
+/// Loads the input and applies any overrides for the viewport before building the tree.
+fn load_render_settings(matches: &ArgMatches) -> Result<RenderSettings, String> {
+    let raw_input = read_input(matches.value_of("INPUT").unwrap())?;
+    let LoadedInput {
+        color_fn,
+        mut definition,
+        ..
+    } = load_input(&raw_input, parse_format(matches)).map_err(|err| err.message)?;
+    let color_fn = parse_color_fn(matches)?.or(color_fn).unwrap_or(ColorFunction::TieDye);
+
+    {
+        let conf = &mut definition.global_conf;
+        conf.zoom = parse_arg(matches, "zoom")?.unwrap_or(conf.zoom);
+        conf.speed = parse_arg(matches, "speed")?.unwrap_or(conf.speed);
+        conf.x_offset = parse_arg(matches, "x-offset")?.unwrap_or(conf.x_offset);
+        conf.y_offset = parse_arg(matches, "y-offset")?.unwrap_or(conf.y_offset);
+        conf.z_offset = parse_arg(matches, "z-offset")?.unwrap_or(conf.z_offset);
+        if let Some(period) = parse_arg::<f64>(matches, "loop-period")? {
+            conf.loop_period = if period > 0. { Some(period) } else { None };
+        }
+        if matches.is_present("auto-normalize") {
+            conf.auto_normalize = true;
+        }
+    }
+
+    let tree: CompositionTree = definition.into();
+    let width: usize = parse_arg(matches, "width")?.unwrap();
+    let height: usize = parse_arg(matches, "height")?.unwrap();
+
+    let tile_conf = match matches.value_of("tile-mode") {
+        Some(raw_mode) => Some(TileConf {
+            mode: match raw_mode {
+                "torus" => TileMode::Torus,
+                "blended" => TileMode::BlendedEdges,
+                _ => TileMode::best_for(&tree),
+            },
+            period_x: parse_arg(matches, "tile-period-x")?.unwrap_or(width as f64),
+            period_y: parse_arg(matches, "tile-period-y")?.unwrap_or(height as f64),
+        }),
+        None => None,
+    };
+
+    Ok(RenderSettings {
+        color_fn,
+        tree,
+        width,
+        height,
+        time: parse_arg(matches, "time")?.unwrap(),
+        tile_conf,
+        two_d: matches.is_present("2d"),
+    })
+}
+
+/// Returns the relief mode selected by the arguments, falling back to the one set in the definition.
+fn parse_relief_mode(
+    matches: &ArgMatches,
+    default: Option<ReliefMode>,
+) -> Result<Option<ReliefMode>, String> {
+    match matches.value_of("relief") {
+        Some(key) => ReliefMode::from_settings(
+            key,
+            parse_arg(matches, "relief-strength")?.unwrap_or(DEFAULT_RELIEF_STRENGTH),
+            parse_arg(matches, "sun-azimuth")?.unwrap_or(DEFAULT_SUN_AZIMUTH),
+            parse_arg(matches, "sun-altitude")?.unwrap_or(DEFAULT_SUN_ALTITUDE),
+        ),
+        None => Ok(default),
+    }
+}
+
+fn render(matches: &ArgMatches) -> Result<(), String> {
+    let settings = load_render_settings(matches)?;
+    let relief_mode = parse_relief_mode(matches, settings.tree.global_conf.relief_mode)?;
+    let pixels = shade_values(
+        &settings.render_values()?,
+        settings.width,
+        settings.height,
+        settings.color_fn,
+        relief_mode,
+    );
+
+    let output = open_output(matches.value_of("output"))?;
+    write_png(output, settings.width, settings.height, &pixels)
+}
+
+fn heightmap(matches: &ArgMatches) -> Result<(), String> {
+    let settings = load_render_settings(matches)?;
+    let format: HeightmapFormat = parse_arg(matches, "heightmap-format")?.unwrap();
+    let remap: ValueRemap = parse_arg(matches, "range")?.unwrap();
+    let values = settings.render_values()?;
+
+    let output = open_output(matches.value_of("output"))?;
+    write_heightmap(
+        output,
+        format,
+        settings.width,
+        settings.height,
+        &values,
+        remap,
+    )
+}
+
+fn mesh(matches: &ArgMatches) -> Result<(), String> {
+    let settings = load_render_settings(matches)?;
+    if settings.tile_conf.is_some() {
+        return Err("Tiling isn't supported for mesh export; use `--skirt-depth` to hide seams between tiles.".into());
+    } else if settings.two_d {
+        return Err("2D evaluation isn't supported for mesh export.".into());
+    }
+
+    let format: MeshFormat = parse_arg(matches, "mesh-format")?.unwrap();
+    let resolution: usize = parse_arg(matches, "resolution")?.unwrap();
+    let conf = MeshConf {
+        resolution,
+        sample_size: settings.width as f64,
+        extent: parse_arg(matches, "extent")?.unwrap(),
+        height_scale: parse_arg(matches, "height-scale")?.unwrap(),
+        skirt_depth: parse_arg(matches, "skirt-depth")?,
+        vertex_colors: if matches.is_present("vertex-colors") {
+            Some(settings.color_fn)
+        } else {
+            None
+        },
+        time: settings.time,
+    };
+    let mesh = build_mesh(&settings.tree, &conf)?;
+
+    let output = open_output(matches.value_of("output"))?;
+    write_mesh(output, format, &mesh)
+}
+
+fn animate(matches: &ArgMatches) -> Result<(), String> {
+    let settings = load_render_settings(matches)?;
+    if settings.tile_conf.is_some() {
+        return Err("Tiling isn't supported for animations.".into());
+    } else if settings.two_d {
+        return Err("Animations can't be rendered in 2D since it has no concept of time.".into());
+    }
+
+    let format: AnimationFormat = parse_arg(matches, "animation-format")?.unwrap();
+    let conf = AnimationConf {
+        start_time: parse_arg(matches, "start-time")?.unwrap(),
+        end_time: parse_arg(matches, "end-time")?.unwrap(),
+        frame_count: parse_arg(matches, "frames")?.unwrap(),
+        fps: parse_arg(matches, "fps")?.unwrap(),
+        seamless_loop: matches.is_present("loop"),
+    };
+    let (width, height) = (settings.width, settings.height);
+
+    match format {
+        AnimationFormat::Frames => {
+            let dir = match matches.value_of("output") {
+                None | Some("-") => {
+                    return Err("A directory must be supplied with `-o` for frame sequences!".into())
+                }
+                Some(dir) => dir,
+            };
+            let relief_mode = parse_relief_mode(matches, settings.tree.global_conf.relief_mode)?;
+            let frames: Vec<Vec<u8>> = render_animation_values(&settings.tree, width, height, &conf)?
+                .iter()
+                .map(|values| shade_values(values, width, height, settings.color_fn, relief_mode))
+                .collect();
+            write_frame_directory(Path::new(dir), width, height, &frames).map(|_| ())
+        }
+        _ => {
+            let mut tree = settings.tree;
+            tree.global_conf.relief_mode =
+                parse_relief_mode(matches, tree.global_conf.relief_mode)?;
+            let buf = encode_animation(&tree, settings.color_fn, width, height, format, &conf)?;
+
+            let mut output = open_output(matches.value_of("output"))?;
+            output
+                .write_all(&buf)
+                .map_err(|err| format!("Unable to write output: {}", err))
+        }
+    }
+}
+
+/// Attempts to load the raw input, reporting the stage at which it failed if it isn't valid.
+fn validation_report(raw_input: &str, format: Option<InputFormat>) -> ValidationReport {
+    match load_input(raw_input, format) {
+        Ok(loaded) => ValidationReport {
+            valid: true,
+            format: Some(loaded.format),
+            error: None,
+        },
+        Err(err) => ValidationReport {
+            valid: false,
+            format: err.format,
+            error: Some(err),
+        },
+    }
+}
+
+fn validate(matches: &ArgMatches) -> Result<(), String> {
+    let raw_input = read_input(matches.value_of("INPUT").unwrap())?;
+    let report = validation_report(&raw_input, parse_format(matches));
+
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    if !report.valid {
+        exit(1);
+    }
+
+    Ok(())
+}
+
+/// Loads the raw input and serializes it in the `to` format, overriding the color function selected in the input with
+/// `color_fn` if one is supplied.
+fn convert_input(
+    raw_input: &str,
+    format: Option<InputFormat>,
+    color_fn: Option<ColorFunction>,
+    to: InputFormat,
+) -> Result<String, String> {
+    let LoadedInput {
+        color_fn: input_color_fn,
+        mut definition,
+        ..
+    } = load_input(raw_input, format).map_err(|err| err.message)?;
+    let color_fn = color_fn.or(input_color_fn).unwrap_or(ColorFunction::TieDye);
+    definition.global_conf.color_function = color_fn;
+
+    match to {
+        InputFormat::Ir => {
+            let ir = libcomposition::ir::build_ir_from_def(&definition);
+            serde_json::to_string_pretty(&ir)
+        }
+        InputFormat::Definition => serde_json::to_string_pretty(&definition),
+    }.map_err(|err| format!("Unable to serialize the converted definition: {}", err))
+}
+
+fn convert(matches: &ArgMatches) -> Result<(), String> {
+    let raw_input = read_input(matches.value_of("INPUT").unwrap())?;
+    let to = match matches.value_of("to").unwrap() {
+        "ir" => InputFormat::Ir,
+        _ => InputFormat::Definition,
+    };
+    let serialized = convert_input(
+        &raw_input,
+        parse_format(matches),
+        parse_color_fn(matches)?,
+        to,
+    )?;
+
+    let mut output = open_output(matches.value_of("output"))?;
+    writeln!(output, "{}", serialized).map_err(|err| format!("Unable to write output: {}", err))
+}
+
+/// Arguments for the size, time, and viewport of rendered images.
+fn render_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("output")
+            .short("o")
+            .long("output")
+            .takes_value(true)
+            .help("Path of the file to write, or `-` for stdout"),
+        Arg::with_name("width")
+            .long("width")
+            .takes_value(true)
+            .default_value("400"),
+        Arg::with_name("height")
+            .long("height")
+            .takes_value(true)
+            .default_value("400"),
+        Arg::with_name("time")
+            .long("time")
+            .takes_value(true)
+            .default_value("0")
+            .help("Sequence number to render, the same unit as ticks of the engine"),
+        Arg::with_name("zoom").long("zoom").takes_value(true),
+        Arg::with_name("speed").long("speed").takes_value(true),
+        Arg::with_name("x-offset").long("x-offset").takes_value(true),
+        Arg::with_name("y-offset").long("y-offset").takes_value(true),
+        Arg::with_name("z-offset").long("z-offset").takes_value(true),
+        Arg::with_name("loop-period")
+            .long("loop-period")
+            .takes_value(true)
+            .help("Makes the output repeat over time with this period; 0 disables looping"),
+        Arg::with_name("auto-normalize")
+            .long("auto-normalize")
+            .help("Measures the range of the composition's output and stretches it to [-1, 1]"),
+        Arg::with_name("2d")
+            .long("2d")
+            .conflicts_with_all(&["speed", "z-offset", "loop-period", "tile-mode"])
+            .help("Evaluates the composition using 2D noise, which is faster for static images but looks different"),
+        Arg::with_name("tile-mode")
+            .long("tile-mode")
+            .takes_value(true)
+            .possible_values(&["auto", "torus", "blended"])
+            .help("Renders a seamlessly tileable image.  `auto` uses a torus if all modules support 4D and blended edges otherwise."),
+        Arg::with_name("tile-period-x")
+            .long("tile-period-x")
+            .takes_value(true)
+            .requires("tile-mode")
+            .help("Horizontal period of the tile in pixels; defaults to the image width"),
+        Arg::with_name("tile-period-y")
+            .long("tile-period-y")
+            .takes_value(true)
+            .requires("tile-mode")
+            .help("Vertical period of the tile in pixels; defaults to the image height"),
+    ]
+}
+
+/// Arguments for visualizing the relief of the output instead of coloring it.
+fn relief_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("relief")
+            .long("relief")
+            .takes_value(true)
+            .possible_values(&["none", "normalMap", "hillshade", "slope"])
+            .help("Renders a normal map, hillshade, or slope map of the output instead of using the color function"),
+        Arg::with_name("relief-strength")
+            .long("relief-strength")
+            .takes_value(true)
+            .requires("relief")
+            .help("Multiplier applied to the differences between neighboring values"),
+        Arg::with_name("sun-azimuth")
+            .long("sun-azimuth")
+            .takes_value(true)
+            .requires("relief")
+            .help("Direction of the sun for hillshading in degrees clockwise from the top of the image"),
+        Arg::with_name("sun-altitude")
+            .long("sun-altitude")
+            .takes_value(true)
+            .requires("relief")
+            .help("Angle of the sun above the horizon for hillshading in degrees"),
+    ]
+}
+
+fn build_app<'a, 'b>() -> App<'a, 'b> {
+    let color_function_arg = Arg::with_name("color-function")
+        .long("color-function")
+        .takes_value(true)
+        .help("Color function to use, overriding the one in the definition (e.g. `tieDye`, `oceanic`)");
+
+    App::new("noise-composition")
+        .version(crate_version!())
+        .about("Renders, validates, and converts noise function compositions")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("render")
+                .about("Renders a composition to a PNG image")
+                .args(&input_args())
+                .args(&render_args())
+                .arg(color_function_arg.clone())
+                .args(&relief_args()),
+        ).subcommand(
+            SubCommand::with_name("heightmap")
+                .about("Exports the raw values of a composition as a heightmap")
+                .args(&input_args())
+                .args(&render_args())
+                .arg(
+                    Arg::with_name("heightmap-format")
+                        .long("heightmap-format")
+                        .takes_value(true)
+                        .possible_values(&["png16", "f32", "f64", "pfm", "exr"])
+                        .default_value("png16"),
+                ).arg(
+                    Arg::with_name("range")
+                        .long("range")
+                        .takes_value(true)
+                        .default_value("auto")
+                        .help("How values are remapped: `auto` to normalize from the observed min/max, `none`, or a fixed input range as `min:max`"),
+                ),
+        ).subcommand(
+            SubCommand::with_name("mesh")
+                .about("Exports a composition as a 3D terrain mesh, using `--width` as the size of the sampled area")
+                .args(&input_args())
+                .args(&render_args())
+                .arg(color_function_arg.clone())
+                .arg(
+                    Arg::with_name("mesh-format")
+                        .long("mesh-format")
+                        .takes_value(true)
+                        .possible_values(&["obj", "stl", "glb"])
+                        .default_value("glb"),
+                ).arg(
+                    Arg::with_name("resolution")
+                        .long("resolution")
+                        .takes_value(true)
+                        .default_value("256")
+                        .help("Number of vertices along each side of the mesh"),
+                ).arg(
+                    Arg::with_name("extent")
+                        .long("extent")
+                        .takes_value(true)
+                        .default_value("1")
+                        .help("Width and depth of the mesh in output units"),
+                ).arg(
+                    Arg::with_name("height-scale")
+                        .long("height-scale")
+                        .takes_value(true)
+                        .default_value("0.1")
+                        .help("Multiplier applied to the output of the composition to get the height of each vertex"),
+                ).arg(
+                    Arg::with_name("skirt-depth")
+                        .long("skirt-depth")
+                        .takes_value(true)
+                        .help("Adds a skirt of this depth around the edges of the mesh"),
+                ).arg(
+                    Arg::with_name("vertex-colors")
+                        .long("vertex-colors")
+                        .help("Colors vertices using the color function; ignored for STL"),
+                ),
+        ).subcommand(
+            SubCommand::with_name("animate")
+                .about("Renders a composition as an animation over time")
+                .args(&input_args())
+                .args(&render_args())
+                .arg(color_function_arg.clone())
+                .args(&relief_args())
+                .arg(
+                    Arg::with_name("animation-format")
+                        .long("animation-format")
+                        .takes_value(true)
+                        .possible_values(&["gif", "apng", "frames"])
+                        .default_value("gif")
+                        .help("`frames` writes numbered PNGs into the directory passed to `-o`"),
+                ).arg(
+                    Arg::with_name("start-time")
+                        .long("start-time")
+                        .takes_value(true)
+                        .default_value("0"),
+                ).arg(
+                    Arg::with_name("end-time")
+                        .long("end-time")
+                        .takes_value(true)
+                        .default_value("120"),
+                ).arg(
+                    Arg::with_name("frames")
+                        .long("frames")
+                        .takes_value(true)
+                        .default_value("60")
+                        .help("Number of frames to render between the start and end times"),
+                ).arg(
+                    Arg::with_name("fps")
+                        .long("fps")
+                        .takes_value(true)
+                        .default_value("30"),
+                ).arg(
+                    Arg::with_name("loop")
+                        .long("loop")
+                        .help("Makes the animation loop seamlessly with a period of the end time minus the start time"),
+                ),
+        ).subcommand(
+            SubCommand::with_name("validate")
+                .about("Checks that a composition can be built, printing a JSON report")
+                .args(&input_args()),
+        ).subcommand(
+            SubCommand::with_name("convert")
+                .about("Converts a composition between the IR and definition formats")
+                .args(&input_args())
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .takes_value(true)
+                        .required(true)
+                        .possible_values(&["ir", "definition"]),
+                ).arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .takes_value(true)
+                        .help("Path of the file to write, or `-` for stdout"),
+                ).arg(color_function_arg),
+        )
+}
+
+fn main() {
+    let matches = build_app().get_matches();
+
+    let res = match matches.subcommand() {
+        ("render", Some(sub_matches)) => render(sub_matches),
+        ("heightmap", Some(sub_matches)) => heightmap(sub_matches),
+        ("mesh", Some(sub_matches)) => mesh(sub_matches),
+        ("animate", Some(sub_matches)) => animate(sub_matches),
+        ("validate", Some(sub_matches)) => validate(sub_matches),
+        ("convert", Some(sub_matches)) => convert(sub_matches),
+        _ => unreachable!(),
+    };
+
+    if let Err(err) = res {
+        eprintln!("{}", err);
+        exit(1);
+    }
+}
@@ -0,0 +1,169 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use serde_json::{self, Value};
+
+use libcomposition::conf::NoiseModuleConf;
+use libcomposition::definition::{CompositionTreeNodeDefinition, NoiseModuleType};
+use libcomposition::initial_tree::create_initial_tree_definition;
+use libcomposition::ir::build_ir_from_def;
+use libcomposition::util::BuildStage;
+
+use super::*;
+
+/// Writes the supplied contents to a file in the temporary directory, returning its path.
+fn write_temp_file(name: &str, contents: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("noise-composition-cli-{}", name));
+    File::create(&path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .unwrap();
+    path
+}
+
+fn initial_definition_json() -> String {
+    serde_json::to_string(&create_initial_tree_definition()).unwrap()
+}
+
+/// Parses the JSON and sorts the lists of settings and module configurations in it, since their order isn't preserved
+/// by conversions.
+fn canonicalize(json: &str) -> Value {
+    fn sort_lists(value: &mut Value) {
+        match *value {
+            Value::Object(ref mut map) => for (key, child) in map.iter_mut() {
+                sort_lists(child);
+                if key == "settings" || key == "module_conf" {
+                    if let Value::Array(ref mut items) = *child {
+                        items.sort_by_key(|item| item.to_string());
+                    }
+                }
+            },
+            Value::Array(ref mut items) => for item in items.iter_mut() {
+                sort_lists(item);
+            },
+            _ => (),
+        }
+    }
+
+    let mut value = serde_json::from_str(json).unwrap();
+    sort_lists(&mut value);
+    value
+}
+
+#[test]
+fn convert_round_trip() {
+    let definition = initial_definition_json();
+
+    let ir = convert_input(
+        &definition,
+        Some(InputFormat::Definition),
+        Some(ColorFunction::Oceanic),
+        InputFormat::Ir,
+    ).unwrap();
+    let loaded = load_input(&ir, None).unwrap_or_else(|err| panic!("{}", err.message));
+    assert_eq!(loaded.format, InputFormat::Ir);
+    assert_eq!(loaded.color_fn, Some(ColorFunction::Oceanic));
+
+    // the color function selected in the IR is carried over to the definition
+    let mut expected = create_initial_tree_definition();
+    expected.global_conf.color_function = ColorFunction::Oceanic;
+    let converted = convert_input(&ir, None, None, InputFormat::Definition).unwrap();
+    assert_eq!(
+        canonicalize(&converted),
+        canonicalize(&serde_json::to_string(&expected).unwrap())
+    );
+    assert_eq!(
+        canonicalize(&convert_input(&converted, None, None, InputFormat::Ir).unwrap()),
+        canonicalize(&ir)
+    );
+}
+
+#[test]
+fn validate_reports_build_stage() {
+    let valid_path = write_temp_file("valid.json", &initial_definition_json());
+    let report = validation_report(&read_input(valid_path.to_str().unwrap()).unwrap(), None);
+    assert!(report.valid);
+    assert_eq!(report.format, Some(InputFormat::Definition));
+
+    let truncated_path = write_temp_file("truncated.json", "{\"type\": \"root\", \"settings\": [");
+    let report = validation_report(&read_input(truncated_path.to_str().unwrap()).unwrap(), None);
+    assert!(!report.valid);
+    assert_eq!(report.error.unwrap().stage, BuildStage::Parse);
+
+    let no_global_conf = r#"{ "type": "root", "settings": [], "children": [] }"#;
+    let no_global_conf_path = write_temp_file("no-global-conf.json", no_global_conf);
+    let report = validation_report(
+        &read_input(no_global_conf_path.to_str().unwrap()).unwrap(),
+        None,
+    );
+    assert!(!report.valid);
+    assert_eq!(report.format, Some(InputFormat::Ir));
+    assert_eq!(report.error.unwrap().stage, BuildStage::GlobalConf);
+
+    // definitions in either format that exceed the resource limits are rejected
+    let mut too_many_octaves = create_initial_tree_definition();
+    too_many_octaves.root_node = CompositionTreeNodeDefinition::Leaf {
+        module_type: NoiseModuleType::Fbm,
+        module_conf: vec![NoiseModuleConf::MultiFractal {
+            octaves: 64,
+            frequency: 1.,
+            lacunarity: 2.,
+            persistence: 0.5,
+        }],
+        transformations: Vec::new(),
+    };
+    let inputs = [
+        (
+            InputFormat::Definition,
+            serde_json::to_string(&too_many_octaves).unwrap(),
+        ),
+        (
+            InputFormat::Ir,
+            serde_json::to_string(&build_ir_from_def(&too_many_octaves)).unwrap(),
+        ),
+    ];
+    for &(format, ref input) in inputs.iter() {
+        let report = validation_report(input, None);
+        assert!(!report.valid);
+        assert_eq!(report.format, Some(format));
+        assert_eq!(report.error.unwrap().stage, BuildStage::Limits);
+    }
+
+    for path in &[valid_path, truncated_path, no_global_conf_path] {
+        fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn render_smoke() {
+    let input_path = write_temp_file("render-input.json", &initial_definition_json());
+    let output_path = env::temp_dir().join("noise-composition-cli-render-output.png");
+
+    let matches = build_app()
+        .get_matches_from_safe(vec![
+            "noise-composition",
+            "render",
+            input_path.to_str().unwrap(),
+            "--width",
+            "24",
+            "--height",
+            "16",
+            "-o",
+            output_path.to_str().unwrap(),
+        ]).unwrap();
+    render(matches.subcommand_matches("render").unwrap()).unwrap();
+
+    let mut png = Vec::new();
+    File::open(&output_path)
+        .and_then(|mut file| file.read_to_end(&mut png))
+        .unwrap();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    // the dimensions are the first fields of the `IHDR` chunk
+    assert_eq!(&png[12..16], b"IHDR");
+    assert_eq!(&png[16..20], &[0, 0, 0, 24]);
+    assert_eq!(&png[20..24], &[0, 0, 0, 16]);
+
+    fs::remove_file(input_path).unwrap();
+    fs::remove_file(output_path).unwrap();
+}
@@ -0,0 +1,5 @@
+#!/bin/sh
+
+# Compiles the engine to WebAssembly and generates its JS bindings, which expose a `wasm_bindgen` global
+cargo build --target=wasm32-unknown-unknown --release
+wasm-bindgen target/wasm32-unknown-unknown/release/noise_engine.wasm --no-modules --out-dir ../public/wasm
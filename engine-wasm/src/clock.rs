@@ -0,0 +1,26 @@
+//! Measures the time taken to render frames for the frame budget.  In the browser the high resolution timer of the
+//! page is used; natively, the system clock is used so that the engine can be tested with `cargo test`.
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = performance, js_name = now)]
+    fn performance_now() -> f64;
+}
+
+/// Returns the current time in milliseconds.  Only differences between the returned values are meaningful.
+#[cfg(target_arch = "wasm32")]
+pub fn now() -> f64 {
+    performance_now()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now() -> f64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    elapsed.as_secs() as f64 * 1000. + elapsed.subsec_nanos() as f64 / 1_000_000.
+}
@@ -0,0 +1,224 @@
+//! The state of a single engine instance along with everything that can be done with it.  Nothing in here depends on
+//! wasm-bindgen or the browser, so it can be used and tested natively.
+
+use libcomposition::budget::{FrameBudget, FrameStats, DEFAULT_TARGET_FRAME_TIME};
+use libcomposition::definition::CompositionTreeDefinition;
+use libcomposition::frame::FrameBuffer;
+use libcomposition::history::{EditLog, TreeEdit};
+use libcomposition::initial_tree::create_initial_tree_definition;
+use libcomposition::probe::PixelProbe;
+use libcomposition::render::render_node_preview;
+use libcomposition::stats::{AnalysisConf, ValueStats};
+use libcomposition::{CompositionTree, MasterConf};
+
+use clock::now;
+
+/// Checks that a buffer of RGBA pixel data has room for exactly `width` x `height` pixels.
+fn check_buffer_size(buf: &[u8], width: usize, height: usize) -> Result<(), String> {
+    let expected = width * height * 4;
+    if buf.len() != expected {
+        return Err(format!(
+            "The supplied buffer holds {} bytes, but {} are needed for a {}x{} image!",
+            buf.len(),
+            expected,
+            width,
+            height
+        ));
+    }
+
+    Ok(())
+}
+
+/// Owns a composition tree along with its edit log and renders it into RGBA pixel data.  The engine doesn't animate
+/// itself; the caller advances its clock by calling `tick()`, usually from a `requestAnimationFrame` callback.
+pub struct Engine {
+    tree: CompositionTree,
+    /// Records the edits made to the composition tree so that they can be undone and redone
+    edit_log: EditLog,
+    /// Engine-owned buffer that frames are rendered into by `render()`; its size is the size of the rendered images
+    frame: FrameBuffer,
+    /// Measures the time taken to render frames and picks the resolution that they're rendered at
+    budget: FrameBudget,
+    /// The point in time of the next rendered frame, in ticks
+    seq: usize,
+}
+
+impl Engine {
+    /// Creates a new engine rendering the default composition tree at the supplied size.
+    pub fn new(width: usize, height: usize) -> Self {
+        Engine::from_definition(create_initial_tree_definition(), width, height)
+    }
+
+    /// Creates a new engine rendering a tree built from the supplied definition at the supplied size.
+    pub fn from_definition(def: CompositionTreeDefinition, width: usize, height: usize) -> Self {
+        let mut engine = Engine {
+            edit_log: EditLog::new(def.root_node.clone()),
+            tree: def.into(),
+            frame: FrameBuffer::new(width, height),
+            budget: FrameBudget::new(Some(DEFAULT_TARGET_FRAME_TIME)),
+            seq: 0,
+        };
+        engine.tree.global_conf.canvas_size = width;
+        engine
+    }
+
+    pub fn tree(&self) -> &CompositionTree {
+        &self.tree
+    }
+
+    pub fn edit_log(&self) -> &EditLog {
+        &self.edit_log
+    }
+
+    pub fn width(&self) -> usize {
+        self.frame.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.frame.height()
+    }
+
+    /// The buffer containing the most recently rendered frame
+    pub fn frame(&self) -> &FrameBuffer {
+        &self.frame
+    }
+
+    /// The point in time at which the next frame will be rendered
+    pub fn seq(&self) -> usize {
+        self.seq
+    }
+
+    /// Timing statistics about the rendered frames along with the resolution that they're rendered at
+    pub fn frame_stats(&self) -> &FrameStats {
+        self.budget.stats()
+    }
+
+    /// Sets the time budget for rendering a single frame in milliseconds.  If `None` is supplied, frames are always
+    /// refined to full resolution no matter how long they take to render.
+    pub fn set_target_frame_time(&mut self, target_frame_time: Option<f64>) {
+        self.budget.set_target_frame_time(target_frame_time);
+        self.frame.set_min_scale(self.budget.min_scale());
+    }
+
+    /// Sets the size of the rendered images, clearing the frame buffer.  Buffers passed to `render_into()` must match
+    /// it.
+    pub fn set_size(&mut self, width: usize, height: usize) {
+        self.frame.resize(width, height);
+        self.tree.global_conf.canvas_size = width;
+    }
+
+    /// Advances the engine's clock by a single tick.
+    pub fn tick(&mut self) {
+        self.seq += 1;
+    }
+
+    /// Replaces the tree's global configuration, keeping the current canvas size.
+    pub fn set_global_conf(&mut self, mut conf: MasterConf) {
+        conf.canvas_size = self.width();
+        self.tree.global_conf = conf;
+        self.tree.update_normalization();
+        self.frame.restart();
+    }
+
+    /// Applies the edit to the composition tree, recording it in the edit log so that it can be undone.  The tree is
+    /// left unchanged if there's an error.
+    pub fn apply_edit(&mut self, edit: TreeEdit) -> Result<(), String> {
+        self.edit_log.apply(&mut self.tree, edit)?;
+        self.frame.restart();
+        Ok(())
+    }
+
+    /// Reverses the most recent edit, returning the edit that was applied in order to do so.
+    pub fn undo(&mut self) -> Result<Option<TreeEdit>, String> {
+        let edit = self.edit_log.undo(&mut self.tree)?;
+        if edit.is_some() {
+            self.frame.restart();
+        }
+        Ok(edit)
+    }
+
+    /// Re-applies the most recently undone edit, returning the edit that was applied.
+    pub fn redo(&mut self) -> Result<Option<TreeEdit>, String> {
+        let edit = self.edit_log.redo(&mut self.tree)?;
+        if edit.is_some() {
+            self.frame.restart();
+        }
+        Ok(edit)
+    }
+
+    /// Replaces the composition tree with one built from the supplied definition, starting a new edit log for it.
+    pub fn replace_tree(&mut self, def: CompositionTreeDefinition) {
+        self.edit_log = EditLog::new(def.root_node.clone());
+        self.tree = def.into();
+        self.tree.global_conf.canvas_size = self.width();
+        self.frame.restart();
+    }
+
+    /// Renders the subtree at the supplied coordinates in place of the entire tree, or the entire tree again if `None`
+    /// is supplied.
+    pub fn solo(&mut self, coords: Option<&[i32]>) -> Result<(), String> {
+        self.tree.solo(coords)?;
+        self.frame.restart();
+        Ok(())
+    }
+
+    /// Renders the current frame into the engine's frame buffer, recording which of its rows changed.  Frames are
+    /// rendered progressively, so after the tree or viewport changes it takes several calls to reach full resolution.
+    /// If frames take longer to render than the frame time budget allows, they are only refined down to the finest
+    /// resolution that fits into it.
+    pub fn render(&mut self) {
+        let start = now();
+        self.frame.render(&self.tree, self.seq as f64);
+        let elapsed = now() - start;
+
+        // lower the resolution that frames are refined to if they take too long to render, or raise it if there's time
+        let min_scale = self.budget.record(
+            elapsed,
+            self.frame.rendered_scale(),
+            self.width(),
+            self.height(),
+        );
+        self.frame.set_min_scale(min_scale);
+    }
+
+    /// Copies the most recently rendered frame out of the frame buffer into `buf` as RGBA pixel data, which must hold
+    /// exactly `width * height * 4` bytes.
+    pub fn render_into(&self, buf: &mut [u8]) -> Result<(), String> {
+        check_buffer_size(buf, self.width(), self.height())?;
+
+        buf.copy_from_slice(self.frame.pixels());
+        Ok(())
+    }
+
+    /// Renders the subtree at the supplied coordinates into `buf` as `size` x `size` RGBA pixel data.  The preview
+    /// covers the same area and point in time as the full image.
+    pub fn render_node_preview_into(
+        &self,
+        coords: &[i32],
+        size: usize,
+        buf: &mut [u8],
+    ) -> Result<(), String> {
+        check_buffer_size(buf, size, size)?;
+
+        let pixels = render_node_preview(
+            &self.tree,
+            coords,
+            self.tree.global_conf.color_function,
+            self.width(),
+            size,
+            self.seq as f64,
+        )?;
+        buf.copy_from_slice(&pixels);
+        Ok(())
+    }
+
+    /// Traces the evaluation of the composition tree at the supplied pixel of the current frame.
+    pub fn probe(&self, x: f64, y: f64) -> PixelProbe {
+        self.tree.probe(x, y, self.seq as f64)
+    }
+
+    /// Samples the composition tree and returns statistics about its values.
+    pub fn analyze(&self, conf: &AnalysisConf) -> Result<ValueStats, String> {
+        self.tree.analyze(conf)
+    }
+}
@@ -0,0 +1,364 @@
+//! Noise engine targeting `wasm32-unknown-unknown`.  Unlike the Emscripten engine, it has no event loop or canvas of its
+//! own: the JS side drives it by calling `tick()` and draws the RGBA pixel data that it renders into a buffer supplied
+//! by the caller.  All exported functions are typed through wasm-bindgen, and all of the logic lives in `Engine`, which
+//! doesn't depend on the browser at all.
+
+extern crate libcomposition;
+extern crate noise;
+extern crate serde;
+extern crate serde_json;
+extern crate wasm_bindgen;
+
+use std::convert::TryInto;
+
+use libcomposition::composition::CompositionScheme;
+use libcomposition::definition::{CompositionTreeNodeDefinition, InputTransformationDefinition};
+use libcomposition::history::TreeEdit;
+use libcomposition::ir::IrNode;
+use libcomposition::stats::AnalysisConf;
+use libcomposition::util::build_def_from_str;
+use libcomposition::MasterConf;
+use wasm_bindgen::prelude::*;
+
+pub mod clock;
+pub mod engine;
+use engine::Engine;
+
+#[cfg(test)]
+mod tests;
+
+fn build_node(def: &str) -> Result<CompositionTreeNodeDefinition, String> {
+    let ir: IrNode = serde_json::from_str(def).map_err(|err| {
+        format!(
+            "Error while attempting to parse node definition JSON into `IrNode`: {:?}",
+            err
+        )
+    })?;
+    ir.try_into()
+}
+
+fn build_transformation(def: &str) -> Result<InputTransformationDefinition, String> {
+    let ir: IrNode = serde_json::from_str(def)
+        .map_err(|err| format!("Unable to convert string into `IrNode`: {:?}", err))?;
+    ir.try_into().map_err(|err| {
+        format!(
+            "Unable to convert `IrNode` into `InputTransformation`: {:?}",
+            err
+        )
+    })
+}
+
+fn build_global_conf(def: &str) -> Result<MasterConf, String> {
+    let ir: IrNode = serde_json::from_str(def)
+        .map_err(|err| format!("Unable to convert string into `IrNode`: {:?}", err))?;
+    ir.try_into()
+}
+
+fn to_json<T: ::serde::Serialize>(val: &T) -> Result<String, String> {
+    serde_json::to_string(val).map_err(|err| format!("Error while serializing result: {:?}", err))
+}
+
+/// Errors are thrown on the JS side as strings.
+fn js_err(err: String) -> JsValue {
+    JsValue::from_str(&err)
+}
+
+/// Returns the module's memory so that the JS side can read the engine's frame buffer directly out of it.
+#[wasm_bindgen]
+pub fn memory() -> JsValue {
+    wasm_bindgen::memory()
+}
+
+/// Handle to a single engine instance owned by the JS side.  It is freed by calling `free()` on it.
+#[wasm_bindgen]
+pub struct NoiseEngine {
+    engine: Engine,
+}
+
+#[wasm_bindgen]
+impl NoiseEngine {
+    /// Creates a new engine rendering the default composition tree into `width` x `height` images.
+    #[wasm_bindgen(constructor)]
+    pub fn new(width: usize, height: usize) -> NoiseEngine {
+        NoiseEngine {
+            engine: Engine::new(width, height),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.engine.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.engine.height()
+    }
+
+    pub fn seq(&self) -> usize {
+        self.engine.seq()
+    }
+
+    pub fn set_size(&mut self, width: usize, height: usize) {
+        self.engine.set_size(width, height);
+    }
+
+    /// Advances the engine's clock by a single tick.
+    pub fn tick(&mut self) {
+        self.engine.tick();
+    }
+
+    /// Renders the current frame into the engine's own frame buffer.  Afterwards, `changed_rows()` returns the rows
+    /// that have to be redrawn.  After the tree or viewport changes, the frame is first rendered at a coarse resolution
+    /// which is refined by each of the following calls.
+    pub fn render_frame(&mut self) {
+        self.engine.render();
+    }
+
+    /// Returns the size of the blocks that the most recent frame was rendered in; 1 once it is at full resolution.
+    pub fn render_scale(&self) -> usize {
+        self.engine.frame().rendered_scale()
+    }
+
+    /// Returns `true` if the frame buffer hasn't reached full resolution yet, meaning that it should be rendered again
+    /// even if the engine is paused.
+    pub fn is_refining(&self) -> bool {
+        self.engine.frame().is_refining()
+    }
+
+    /// Sets the time budget for rendering a single frame in milliseconds.  Frames that take longer to render are
+    /// rendered at a lower resolution.  A budget of 0 or less disables it, always refining frames to full resolution.
+    pub fn set_target_frame_time(&mut self, target_frame_time: f64) {
+        self.engine
+            .set_target_frame_time(if target_frame_time > 0. {
+                Some(target_frame_time)
+            } else {
+                None
+            });
+    }
+
+    /// Returns a JSON-encoded `FrameStats` containing the time taken to render recent frames and the effective
+    /// resolution that they were rendered at.
+    pub fn frame_stats(&self) -> Result<String, JsValue> {
+        to_json(self.engine.frame_stats()).map_err(js_err)
+    }
+
+    /// Returns a pointer to the RGBA pixel data of the frame buffer within the module's memory.  It is invalidated when
+    /// the engine is resized or freed.
+    pub fn pixels_ptr(&self) -> *const u8 {
+        self.engine.frame().pixels().as_ptr()
+    }
+
+    /// Returns the length of the frame buffer's pixel data in bytes.
+    pub fn pixels_len(&self) -> usize {
+        self.engine.frame().pixels().len()
+    }
+
+    /// Returns the ranges of rows that changed when the most recent frame was rendered, flattened into `[start, end)`
+    /// pairs.  Only those rows have to be drawn with `putImageData()`.
+    pub fn changed_rows(&self) -> Vec<u32> {
+        self.engine.frame().changed_rows().to_vec()
+    }
+
+    /// Renders the current frame like `render_frame()` and copies it into `buf` as RGBA pixel data, which must hold
+    /// exactly `width * height * 4` bytes.  It can be a view over the data of an `ImageData` to be drawn with
+    /// `putImageData()`.
+    pub fn render(&mut self, buf: &mut [u8]) -> Result<(), JsValue> {
+        self.engine.render();
+        self.engine.render_into(buf).map_err(js_err)
+    }
+
+    /// Renders the subtree at the supplied coordinates into `buf` as `size` x `size` RGBA pixel data.
+    pub fn render_node_preview(
+        &self,
+        coords: &[i32],
+        size: usize,
+        buf: &mut [u8],
+    ) -> Result<(), JsValue> {
+        self.engine
+            .render_node_preview_into(coords, size, buf)
+            .map_err(js_err)
+    }
+
+    /// Replaces the entire composition tree with one built from the supplied IR definition.
+    pub fn initialize_from_scratch(&mut self, def: &str) -> Result<(), JsValue> {
+        let (_, def) = build_def_from_str(def).map_err(|err| {
+            js_err(format!(
+                "Error while bulding supplied definition into `CompositionTree`: {}",
+                err
+            ))
+        })?;
+        self.engine.replace_tree(def);
+        Ok(())
+    }
+
+    /// Sets a new global configuration for the composition tree given the IR format.
+    pub fn set_global_conf(&mut self, conf: &str) -> Result<(), JsValue> {
+        let conf = build_global_conf(conf).map_err(js_err)?;
+        self.engine.set_global_conf(conf);
+        Ok(())
+    }
+
+    /// Adds the node defined by the supplied IR as a child of the composed node at `parent`.
+    pub fn add_node(
+        &mut self,
+        parent: &[i32],
+        index: usize,
+        node_def: &str,
+    ) -> Result<(), JsValue> {
+        let node = build_node(node_def).map_err(js_err)?;
+        self.apply_edit(TreeEdit::AddNode {
+            parent: parent.to_vec(),
+            index,
+            node,
+        })
+    }
+
+    pub fn delete_node(&mut self, parent: &[i32], index: usize) -> Result<(), JsValue> {
+        self.apply_edit(TreeEdit::DeleteNode {
+            parent: parent.to_vec(),
+            index,
+        })
+    }
+
+    pub fn replace_node(
+        &mut self,
+        parent: &[i32],
+        index: usize,
+        node_def: &str,
+    ) -> Result<(), JsValue> {
+        let node = build_node(node_def).map_err(js_err)?;
+        self.apply_edit(TreeEdit::ReplaceNode {
+            parent: parent.to_vec(),
+            index,
+            node,
+        })
+    }
+
+    /// Replaces the composition scheme of the composed node at `node` with the supplied JSON-encoded one.
+    pub fn set_composition_scheme(&mut self, node: &[i32], scheme: &str) -> Result<(), JsValue> {
+        let scheme = serde_json::from_str::<CompositionScheme>(scheme).map_err(|err| {
+            js_err(format!(
+                "Error while attempting to deserialize `CompositionScheme` definition: {:?}",
+                err
+            ))
+        })?;
+        self.apply_edit(TreeEdit::SetCompositionScheme {
+            node: node.to_vec(),
+            scheme,
+        })
+    }
+
+    /// Appends the input transformation defined by the supplied IR to those of the node at `node`.
+    pub fn add_input_transformation(&mut self, node: &[i32], def: &str) -> Result<(), JsValue> {
+        let transformation = build_transformation(def).map_err(js_err)?;
+        let index = self
+            .engine
+            .edit_log()
+            .definition()
+            .traverse(node)
+            .map_err(|err| js_err(format!("Error while traversing composition tree: {}", err)))?
+            .transformations()
+            .len();
+
+        self.apply_edit(TreeEdit::AddInputTransformation {
+            node: node.to_vec(),
+            index,
+            transformation,
+        })
+    }
+
+    pub fn delete_input_transformation(
+        &mut self,
+        node: &[i32],
+        index: usize,
+    ) -> Result<(), JsValue> {
+        self.apply_edit(TreeEdit::DeleteInputTransformation {
+            node: node.to_vec(),
+            index,
+        })
+    }
+
+    pub fn replace_input_transformation(
+        &mut self,
+        node: &[i32],
+        index: usize,
+        def: &str,
+    ) -> Result<(), JsValue> {
+        let transformation = build_transformation(def).map_err(js_err)?;
+        self.apply_edit(TreeEdit::ReplaceInputTransformation {
+            node: node.to_vec(),
+            index,
+            transformation,
+        })
+    }
+
+    /// Applies a JSON-encoded list of `TreeEdit`s as a single unit; if any of them fails, none of them are applied.
+    pub fn apply_edits(&mut self, edits: &str) -> Result<(), JsValue> {
+        let edits = serde_json::from_str::<Vec<TreeEdit>>(edits).map_err(|err| {
+            js_err(format!(
+                "Error while attempting to deserialize list of `TreeEdit`s: {:?}",
+                err
+            ))
+        })?;
+        self.apply_edit(TreeEdit::Batch { edits })
+    }
+
+    fn apply_edit(&mut self, edit: TreeEdit) -> Result<(), JsValue> {
+        self.engine.apply_edit(edit).map_err(js_err)
+    }
+
+    /// Reverses the most recent edit, returning the edit that was applied in order to do so as a JSON-encoded
+    /// `TreeEdit` so that the UI can make the same change to its copy of the tree.  Returns `undefined` if there was
+    /// nothing to undo.
+    pub fn undo(&mut self) -> Result<Option<String>, JsValue> {
+        match self.engine.undo().map_err(js_err)? {
+            Some(edit) => to_json(&edit).map(Some).map_err(js_err),
+            None => Ok(None),
+        }
+    }
+
+    /// Re-applies the most recently undone edit, returning it as a JSON-encoded `TreeEdit` or `undefined` if there was
+    /// nothing to redo.
+    pub fn redo(&mut self) -> Result<Option<String>, JsValue> {
+        match self.engine.redo().map_err(js_err)? {
+            Some(edit) => to_json(&edit).map(Some).map_err(js_err),
+            None => Ok(None),
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.engine.edit_log().can_undo()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.engine.edit_log().can_redo()
+    }
+
+    /// Renders the subtree at the supplied coordinates in place of the entire tree until `clear_solo_node()` is called.
+    pub fn set_solo_node(&mut self, coords: &[i32]) -> Result<(), JsValue> {
+        self.engine.solo(Some(coords)).map_err(js_err)
+    }
+
+    pub fn clear_solo_node(&mut self) {
+        let _ = self.engine.solo(None);
+    }
+
+    /// Samples the composition tree using the supplied JSON-encoded `AnalysisConf` and returns statistics about its
+    /// values as a JSON-encoded `ValueStats`.
+    pub fn analyze_composition(&self, conf: &str) -> Result<String, JsValue> {
+        let conf: AnalysisConf = serde_json::from_str(conf).map_err(|err| {
+            js_err(format!(
+                "Error while attempting to deserialize `AnalysisConf`: {:?}",
+                err
+            ))
+        })?;
+        self.engine
+            .analyze(&conf)
+            .and_then(|stats| to_json(&stats))
+            .map_err(js_err)
+    }
+
+    /// Traces the evaluation of the composition tree at the supplied pixel, returning a JSON-encoded `PixelProbe`.
+    pub fn probe_pixel(&self, x: f64, y: f64) -> Result<String, JsValue> {
+        to_json(&self.engine.probe(x, y)).map_err(js_err)
+    }
+}
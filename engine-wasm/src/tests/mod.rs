@@ -0,0 +1,213 @@
+//! Tests for the engine that run natively.
+
+use libcomposition::color_schemes::ColorFunction;
+use libcomposition::conf::NoiseModuleConf;
+use libcomposition::definition::{CompositionTreeNodeDefinition, NoiseModuleType};
+use libcomposition::frame::FrameBuffer;
+use libcomposition::history::TreeEdit;
+use libcomposition::render::render_rgba;
+
+use super::build_node;
+use engine::Engine;
+
+const SIZE: usize = 12;
+
+fn constant_node(constant: f64) -> CompositionTreeNodeDefinition {
+    CompositionTreeNodeDefinition::Leaf {
+        module_type: NoiseModuleType::Constant,
+        module_conf: vec![NoiseModuleConf::Constant { constant }],
+        transformations: Vec::new(),
+    }
+}
+
+/// Keeps rendering the current frame until the progressive render reaches full resolution.
+fn render_full_resolution(engine: &mut Engine) {
+    while engine.frame().is_refining() {
+        engine.render();
+    }
+}
+
+#[test]
+fn renders_into_supplied_buffer() {
+    let mut engine = Engine::new(SIZE, SIZE / 2);
+    engine.set_target_frame_time(None);
+    engine.tick();
+    render_full_resolution(&mut engine);
+
+    let mut buf = vec![0u8; SIZE * (SIZE / 2) * 4];
+    engine.render_into(&mut buf).unwrap();
+    let expected = render_rgba(engine.tree(), ColorFunction::default(), SIZE, SIZE / 2, 1.);
+    assert_eq!(buf, expected);
+
+    // buffers that don't match the size of the image are rejected without being written to
+    let mut wrong_size = vec![0u8; SIZE * SIZE * 4];
+    assert!(engine.render_into(&mut wrong_size).is_err());
+    assert!(wrong_size.iter().all(|&byte| byte == 0));
+
+    engine.set_size(SIZE, SIZE);
+    engine.render_into(&mut wrong_size).unwrap();
+}
+
+#[test]
+fn edits_change_rendered_output() {
+    let mut engine = Engine::new(SIZE, SIZE);
+    engine.set_target_frame_time(None);
+    render_full_resolution(&mut engine);
+    let mut original = vec![0u8; SIZE * SIZE * 4];
+    engine.render_into(&mut original).unwrap();
+
+    engine
+        .apply_edit(TreeEdit::ReplaceNode {
+            parent: Vec::new(),
+            index: 0,
+            node: constant_node(0.5),
+        })
+        .unwrap();
+    engine
+        .apply_edit(TreeEdit::DeleteNode {
+            parent: Vec::new(),
+            index: 1,
+        })
+        .unwrap();
+    render_full_resolution(&mut engine);
+    let mut edited = vec![0u8; SIZE * SIZE * 4];
+    engine.render_into(&mut edited).unwrap();
+    assert_ne!(edited, original);
+
+    // a failed edit leaves the tree untouched
+    assert!(engine
+        .apply_edit(TreeEdit::DeleteNode {
+            parent: vec![7],
+            index: 0,
+        })
+        .is_err());
+
+    assert!(engine.undo().unwrap().is_some());
+    assert!(engine.undo().unwrap().is_some());
+    assert!(engine.undo().unwrap().is_none());
+    render_full_resolution(&mut engine);
+    let mut undone = vec![0u8; SIZE * SIZE * 4];
+    engine.render_into(&mut undone).unwrap();
+    assert_eq!(undone, original);
+
+    assert!(engine.redo().unwrap().is_some());
+    assert!(engine.edit_log().can_redo());
+}
+
+#[test]
+fn frame_buffer_reports_changed_rows() {
+    let mut engine = Engine::new(SIZE, SIZE);
+    engine.set_target_frame_time(None);
+    engine.render();
+    assert_eq!(engine.frame().changed_rows(), &[0, SIZE as u32]);
+    render_full_resolution(&mut engine);
+    let expected = render_rgba(engine.tree(), ColorFunction::default(), SIZE, SIZE, 0.);
+    assert_eq!(engine.frame().pixels(), &expected[..]);
+
+    // rendering the same frame again doesn't change anything
+    engine.render();
+    assert!(engine.frame().changed_rows().is_empty());
+
+    // resizing clears the buffer, so the whole image has to be redrawn
+    engine.set_size(SIZE, SIZE / 2);
+    engine.render();
+    assert_eq!(engine.frame().changed_rows(), &[0, SIZE as u32 / 2]);
+    assert_eq!(engine.frame().pixels().len(), SIZE * (SIZE / 2) * 4);
+
+    // adjacent changed rows are merged into a single range
+    let mut frame = FrameBuffer::new(2, 8);
+    let row = [255u8; 8];
+    for &y in &[2, 3, 6] {
+        frame.update_row(y, &row);
+    }
+    frame.update_row(5, &[0u8; 8]);
+    assert_eq!(frame.changed_rows(), &[2, 4, 6, 7]);
+    assert_eq!(&frame.pixels()[6 * 8..7 * 8], &row);
+}
+
+#[test]
+fn progressive_rendering() {
+    let mut engine = Engine::new(SIZE, SIZE);
+    engine.set_target_frame_time(None);
+    let row_len = SIZE * 4;
+
+    // the first frame is made up of 8x8 blocks, each colored using the value at its top left corner
+    engine.render();
+    assert_eq!(engine.frame().rendered_scale(), 8);
+    let pixels = engine.frame().pixels().to_vec();
+    assert_eq!(pixels[..row_len], pixels[7 * row_len..8 * row_len]);
+    assert_eq!(pixels[0..4], pixels[7 * 4..8 * 4]);
+    assert_eq!(
+        pixels[8 * row_len..8 * row_len + 4],
+        pixels[11 * row_len + 12..11 * row_len + 16]
+    );
+    let full = render_rgba(engine.tree(), ColorFunction::default(), SIZE, SIZE, 0.);
+    assert_eq!(pixels[0..4], full[0..4]);
+    assert_eq!(pixels[8 * 4..9 * 4], full[8 * 4..9 * 4]);
+
+    // each following frame doubles the resolution
+    let mut scales = Vec::new();
+    while engine.frame().is_refining() {
+        engine.render();
+        scales.push(engine.frame().rendered_scale());
+    }
+    assert_eq!(scales, vec![4, 2, 1]);
+    assert_eq!(engine.frame().pixels(), &full[..]);
+
+    // advancing time keeps the full resolution
+    engine.tick();
+    engine.render();
+    assert_eq!(engine.frame().rendered_scale(), 1);
+
+    // but changing the tree starts over
+    engine
+        .apply_edit(TreeEdit::ReplaceNode {
+            parent: Vec::new(),
+            index: 0,
+            node: constant_node(0.5),
+        })
+        .unwrap();
+    engine.render();
+    assert_eq!(engine.frame().rendered_scale(), 8);
+    render_full_resolution(&mut engine);
+
+    // a failed edit leaves the tree unchanged, so it doesn't restart the render
+    assert!(engine
+        .apply_edit(TreeEdit::DeleteNode {
+            parent: vec![7],
+            index: 0,
+        })
+        .is_err());
+    engine.render();
+    assert_eq!(engine.frame().rendered_scale(), 1);
+
+    // changing the viewport starts over as well
+    engine.set_size(SIZE / 2, SIZE);
+    engine.render();
+    assert_eq!(engine.frame().rendered_scale(), 8);
+    render_full_resolution(&mut engine);
+    let full = render_rgba(engine.tree(), ColorFunction::default(), SIZE / 2, SIZE, 1.);
+    assert_eq!(engine.frame().pixels(), &full[..]);
+}
+
+#[test]
+fn frame_budget_limits_refinement() {
+    // an engine that can't fit any frame into its budget stops refining at the coarsest resolution
+    let mut engine = Engine::new(SIZE * 2, SIZE * 2);
+    engine.set_target_frame_time(Some(1e-12));
+    engine.render();
+    engine.render();
+    assert_eq!(engine.frame().rendered_scale(), 8);
+    assert!(!engine.frame().is_refining());
+    assert_eq!(engine.frame_stats().effective_width, 3);
+
+    engine.set_target_frame_time(None);
+    render_full_resolution(&mut engine);
+    assert_eq!(engine.frame_stats().render_scale, 1);
+}
+
+#[test]
+fn invalid_node_definitions_are_reported() {
+    assert!(build_node("not json").is_err());
+    assert!(build_node(r#"{"_type": "noiseModule", "settings": [], "children": []}"#).is_err());
+}
@@ -5,5 +5,5 @@ cargo rustc --target=asmjs-unknown-emscripten --release --verbose -- -Z print-li
     -s TOTAL_MEMORY=67108864 \
     -s NO_EXIT_RUNTIME=1 \
     -s ASSERTIONS=0 \
-    -s EXPORTED_FUNCTIONS=[\"_init\",\"_set_global_conf\",\"_set_canvas_size\",\"_pause_engine\",\"_resume_engine\",\"_add_node\",\"_delete_node\",\"_replace_node\",\"_add_input_transformation\",\"_delete_input_transformation\",\"_replace_input_transformation\",\"_initialize_from_scratch\",\"_cleanup_runtime\"] \
+    -s EXPORTED_FUNCTIONS=[\"_init\",\"_set_global_conf\",\"_set_canvas_size\",\"_pause_engine\",\"_resume_engine\",\"_add_node\",\"_delete_node\",\"_replace_node\",\"_add_input_transformation\",\"_delete_input_transformation\",\"_replace_input_transformation\",\"_initialize_from_scratch\",\"_cleanup_runtime\",\"_analyze_composition\",\"_free_string\",\"_render_node_preview_into\",\"_set_solo_node\",\"_clear_solo_node\",\"_probe_pixel\",\"_undo_edit\",\"_redo_edit\",\"_can_undo\",\"_can_redo\",\"_apply_edits\",\"_create_instance\",\"_frame_stats\",\"_set_target_frame_time\"] \
     -s EXTRA_EXPORTED_RUNTIME_METHODS=[\"ccall\",\"cwrap\"]"
@@ -1,8 +1,8 @@
 //! Defines functions that are exported to the JavaScript frontend, allowing access to the engine during runtime from the JS side.
 
 use std::convert::TryInto;
-use std::ffi::CStr;
-use std::mem;
+use std::ffi::{CStr, CString};
+use std::ptr;
 use std::slice;
 
 use serde_json;
@@ -10,99 +10,88 @@ use serde_json;
 use super::*;
 use libcomposition::composition::CompositionScheme;
 use libcomposition::definition::{CompositionTreeNodeDefinition, InputTransformationDefinition};
-use libcomposition::initial_tree::create_initial_tree;
+use libcomposition::history::TreeEdit;
 use libcomposition::ir::IrNode;
-use libcomposition::transformations::InputTransformation;
-use libcomposition::util::build_tree_from_def;
-use libcomposition::{
-    CompositionTree, CompositionTreeNode, CompositionTreeNodeType, ACTIVE_COLOR_FUNCTION,
-};
+use libcomposition::render::render_node_preview;
+use libcomposition::stats::AnalysisConf;
+use libcomposition::util::build_def_from_str;
+#[cfg(target_os = "emscripten")]
+use runtime::EmscriptenLoop;
+use runtime::{EngineHandle, Scheduler};
+
+/// Owns all running engine instances and drives them using a single browser event loop
+static mut SCHEDULER: Option<Scheduler> = None;
+
+#[cfg(target_os = "emscripten")]
+unsafe fn scheduler() -> &'static mut Scheduler {
+    if SCHEDULER.is_none() {
+        SCHEDULER = Some(Scheduler::new(Box::new(EmscriptenLoop)));
+    }
 
-extern "C" {
-    fn emscripten_pause_main_loop();
-    fn emscripten_resume_main_loop();
+    SCHEDULER.as_mut().unwrap()
 }
 
-static mut ENGINE_RUNNING: bool = false;
+/// Creates a new engine instance that renders the default initial composition tree to the canvas registered on the JS
+/// side with the supplied ID and starts animating it along with all other instances.  Returns a pointer to the
+/// instance which is used for all other calls; it stays valid until it is passed to `cleanup_runtime()`.
+#[cfg(target_os = "emscripten")]
+#[no_mangle]
+pub unsafe extern "C" fn create_instance(canvas_id: i32, canvas_size: usize) -> *mut EngineHandle {
+    scheduler().add(
+        canvas_size,
+        vec![
+            // draws the changed rows of each rendered frame to the canvas
+            Box::new(CanvasRenderer::new(canvas_id)),
+        ],
+    )
+}
 
-/// Initializes the minutiae engine and the internal noise generator engine with the default initial composition tree.
+/// Initializes a new engine instance rendering to the main canvas and registers a pointer to it on the JS side.
+#[cfg(target_os = "emscripten")]
 #[no_mangle]
 pub unsafe extern "C" fn init(canvas_size: usize) {
-    // Create the initial composition tree
-    let master_tree: CompositionTree = create_initial_tree();
-    // Put the composition tree into a box so I feel more confident that it never moves
-    let boxed_composition_tree = Box::new(master_tree);
-
-    // initialize emscripten universe and start the minutiae simulation
-    let mut conf = UniverseConf::default();
-    conf.size = canvas_size;
-    let universe = Universe::new(
-        conf,
-        &mut WorldGenerator,
-        |_, _| None,
-        |_, _, _, _, _, _, _| {},
-    );
-
-    // get the pointer of the box by taking it apart and putting it back together again
-    let boxed_tree_pointer = Box::into_raw(boxed_composition_tree);
-    let boxed_composition_tree = Box::from_raw(boxed_tree_pointer);
-
-    // send a pointer to the engine state to the JS side to be used for dynamic configuration
-    // debug("Calling `setTreePointer`...");
-    setTreePointer(boxed_tree_pointer as *const c_void);
-
-    // create the middleware that manages the image buffer and populates it each tick
-    let noise_stepper = Box::new(NoiseStepper {
-        conf: (|| {
-            let mut conf = MasterConf::default();
-            conf.canvas_size = canvas_size;
-            conf
-        })(),
-        composition_tree: boxed_composition_tree,
-    });
-    let boxed_engine_ptr = Box::into_raw(noise_stepper);
-    let boxed_noise_stepper = Box::from_raw(boxed_engine_ptr);
-
-    // debug("Calling `setEnginePointer`...");
-    setEnginePointer(boxed_engine_ptr as *const c_void);
-
-    // Initialize the simulation, registering with the emscripten Browser event loop
-    EmscriptenDriver.init(
-        universe,
-        OurEngine,
-        &mut [
-            // middleware that calculates noise values for each of the universe's cells using the current sequence number
-            boxed_noise_stepper,
-            // middleware that renders the current universe to the canvas each tick using the supplied color calculator function
-            Box::new(CanvasRenderer::new(canvas_size, calc_color, canvas_render)),
-        ],
-    );
+    let handle = create_instance(0, canvas_size);
+
+    // send a pointer to the engine to the JS side to be used for dynamic configuration
+    setEnginePointer(handle as *const c_void);
+}
+
+/// Applies the edit to the composition tree, recording it in the edit log so that it can be undone.  Returns 0 if
+/// successful and 1 if there was an error, in which case the tree is left unchanged.
+fn apply_edit(engine: &mut EngineHandle, edit: TreeEdit) -> i32 {
+    match engine.apply_edit(edit) {
+        Ok(_) => 0,
+        Err(err) => {
+            error(&err);
+            1
+        }
+    }
 }
 
 /// Deletes a node of the composition tree at the supplied depth and index.  Returns 0 if successful, 1 if there was an error.
 #[no_mangle]
 pub unsafe extern "C" fn delete_node(
-    tree_pointer: *mut CompositionTree,
+    engine_pointer: *mut EngineHandle,
     depth: i32,
     coords: *const i32,
     index: i32,
 ) -> i32 {
-    let tree = &mut *(tree_pointer);
+    let engine = &mut *engine_pointer;
     let coords_slice = slice::from_raw_parts(coords, depth as usize);
 
-    match tree.delete_node(depth as usize, coords_slice, index as usize) {
-        Ok(_) => 0,
-        Err(err) => {
-            error(&err);
-            1
-        }
-    }
+    apply_edit(
+        engine,
+        TreeEdit::DeleteNode {
+            parent: coords_slice.to_vec(),
+            index: index as usize,
+        },
+    )
 }
 
 /// Sets a new global configuration for the composition tree given the IR format.
 #[no_mangle]
 pub unsafe extern "C" fn set_global_conf(
-    tree_pointer: *mut CompositionTree,
+    engine_pointer: *mut EngineHandle,
     conf_str: *const c_char,
 ) -> i32 {
     // Convert the c-str into a &str
@@ -131,8 +120,9 @@ pub unsafe extern "C" fn set_global_conf(
         }
     };
 
-    let tree: &mut CompositionTree = &mut *tree_pointer;
+    let tree = (&mut *engine_pointer).tree_mut();
     tree.global_conf = conf;
+    tree.update_normalization();
 
     0i32
 }
@@ -155,13 +145,13 @@ fn build_node(def: &str) -> Result<CompositionTreeNodeDefinition, String> {
 /// it will have to be updated manually.
 #[no_mangle]
 pub unsafe extern "C" fn add_node(
-    tree_pointer: *mut CompositionTree,
+    engine_pointer: *mut EngineHandle,
     depth: i32,
     coords: *const i32,
     index: i32,
     node_definition: *const c_char,
 ) -> i32 {
-    let tree = &mut *(tree_pointer);
+    let engine = &mut *engine_pointer;
 
     // Convert the c-str into a &str
     let json_str: &str = match CStr::from_ptr(node_definition).to_str() {
@@ -172,38 +162,38 @@ pub unsafe extern "C" fn add_node(
         }
     };
 
-    let node: CompositionTreeNode = match build_node(json_str) {
+    let node: CompositionTreeNodeDefinition = match build_node(json_str) {
         Ok(node_def) => node_def,
         Err(err_str) => {
             error(&format!("{}", err_str));
             return 1;
         }
-    }
-    .into();
+    };
 
     // attempt to add the created node as a child of the node at the supplied coordinates in the tree
     let coords_slice = slice::from_raw_parts(coords, depth as usize);
-    match tree.add_node(depth as usize, coords_slice, node, index as usize) {
-        Ok(_) => 0,
-        Err(err) => {
-            error(&err);
-            1
-        }
-    }
+    apply_edit(
+        engine,
+        TreeEdit::AddNode {
+            parent: coords_slice.to_vec(),
+            index: index as usize,
+            node,
+        },
+    )
 }
 
-/// Convenience function.  Same as `delete_node()` and `add_node()` combined.  Helper function that replaces the node
-/// at the given coordinates with a new node.  If the target is a `ComposedNode`, the entire subtree that it defines
-/// will be destroyed and re-built.  Returns early if the removal fails.
+/// Convenience function.  Same as `delete_node()` and `add_node()` combined, but recorded as a single edit.  Helper
+/// function that replaces the node at the given coordinates with a new node.  If the target is a `ComposedNode`, the
+/// entire subtree that it defines will be destroyed and re-built.
 #[no_mangle]
 pub unsafe extern "C" fn replace_node(
-    tree_pointer: *mut CompositionTree,
+    engine_pointer: *mut EngineHandle,
     depth: i32,
     coords: *const i32,
     index: i32,
     node_definition: *const c_char,
 ) -> i32 {
-    let tree = &mut *(tree_pointer);
+    let engine = &mut *engine_pointer;
 
     // Convert the c-str into a &str
     let json_str: &str = match CStr::from_ptr(node_definition).to_str() {
@@ -215,260 +205,167 @@ pub unsafe extern "C" fn replace_node(
     };
 
     // first try to create the node, avoiding removing the old one in case of failure.
-    let node: CompositionTreeNode = match build_node(json_str) {
+    let node: CompositionTreeNodeDefinition = match build_node(json_str) {
         Ok(node_def) => node_def,
         Err(err_str) => {
             error(&format!("{}", err_str));
             return 1;
         }
-    }
-    .into();
-
-    if let 1 = delete_node(tree_pointer, depth, coords, index) {
-        return 1;
-    }
+    };
 
-    // attempt to add the created node as a child of the node at the supplied coordinates in the tree
     let coords_slice = slice::from_raw_parts(coords, depth as usize);
-    match tree.add_node(depth as usize, coords_slice, node, index as usize) {
-        Ok(_) => 0,
-        Err(err) => {
-            error(&err);
-            1
-        }
-    }
+    apply_edit(
+        engine,
+        TreeEdit::ReplaceNode {
+            parent: coords_slice.to_vec(),
+            index: index as usize,
+            node,
+        },
+    )
 }
 
-fn get_transformation_parent<'a>(
-    tree: &'a mut CompositionTree,
-    coords_slice: &[i32],
-    tree_depth: i32,
-    node_index: i32,
-) -> Result<&'a mut CompositionTreeNode, String> {
-    let grandparent_node: &mut CompositionTreeNode =
-        tree.root_node.traverse_mut(coords_slice).map_err(|err| {
-            format!(
-                "Unable to traverse the tree at the supplied coordinates: {}",
-                err
-            )
-        })?;
-
-    if node_index == -1 {
-        return Ok(grandparent_node);
+/// Returns the coordinates of the node whose input transformations are being modified.  `node_index` is the index of
+/// that node among the children of the node at `coords`, or -1 if it is the node at `coords` itself.
+fn transformation_target(coords: &[i32], node_index: i32) -> Vec<i32> {
+    let mut target = coords.to_vec();
+    if node_index != -1 {
+        target.push(node_index);
     }
 
-    let parent_node: &mut CompositionTreeNode = match grandparent_node.function {
-        CompositionTreeNodeType::Leaf(_) => {
-            return Err(format!(
-                "Attempted to traverse tree to child of leaf node! Supplied coords: {:?} ; Supplied index: {}",
-                coords_slice,
-                tree_depth
-            ));
-        }
-        CompositionTreeNodeType::Combined(ref mut composed_module) => {
-            let child_count = composed_module.children.len();
-
-            match composed_module.children.get_mut(node_index as usize) {
-                Some(child) => child,
-                None => {
-                    return Err(format!(
-                        "Tried to get child of composed module at index {} but it only has {} children!",
-                        node_index,
-                        child_count
-                    ));
-                }
-            }
-        }
-    };
+    target
+}
 
-    Ok(parent_node)
+fn build_transformation(def: &str) -> Result<InputTransformationDefinition, String> {
+    // Try to build the `IrNode`
+    let ir: IrNode = serde_json::from_str::<IrNode>(def)
+        .map_err(|err| format!("Unable to convert string into `IrNode`: {:?}", err))?;
+
+    // try to convert the `IrNode` into an `InputTransformation`
+    ir.try_into().map_err(|err| {
+        format!(
+            "Unable to convert `IrNode` into `InputTransformation`: {:?}",
+            err
+        )
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn add_input_transformation(
-    tree_pointer: *mut CompositionTree,
+    engine_pointer: *mut EngineHandle,
     tree_depth: i32,
     coords: *const i32,
     node_index: i32,
     transformation_definition: *const c_char,
 ) -> i32 {
-    let tree: &mut CompositionTree = &mut *tree_pointer;
+    let engine = &mut *engine_pointer;
     // Convert the c-str into a &str
     let json_str: &str = match CStr::from_ptr(transformation_definition).to_str() {
         Ok(s) => s,
         Err(_) => {
-            error("Invalid UTF8 string provided to `replace_node()`");
+            error("Invalid UTF8 string provided to `add_input_transformation()`");
             return 1;
         }
     };
 
-    // Try to build the `IrNode`
-    let ir: IrNode = match serde_json::from_str::<IrNode>(json_str) {
-        Ok(ir) => ir,
+    let transformation = match build_transformation(json_str) {
+        Ok(transformation) => transformation,
         Err(err) => {
-            error(&format!(
-                "Unable to convert string into `IrNode`: {:?}",
-                err
-            ));
-            return 1;
-        }
-    };
-
-    // try to convert the `IrNode` into an `InputTransformation`
-    let transformation_def: InputTransformationDefinition = match ir.try_into() {
-        Ok(t) => t,
-        Err(err) => {
-            error(&format!(
-                "Unable to convert `IrNode` into `InputTransformation`: {:?}",
-                err
-            ));
+            error(&err);
             return 1;
         }
     };
 
-    // convert the definition into a proper `InputTransformation`
-    let transformation: InputTransformation = transformation_def.into();
-
-    // traverse the tree to find the node that we're targeting
     let coords_slice = slice::from_raw_parts(coords, tree_depth as usize);
-    let parent_node = match get_transformation_parent(tree, coords_slice, tree_depth, node_index) {
-        Ok(parent_node) => parent_node,
+    let target = transformation_target(coords_slice, node_index);
+
+    // the new transformation is appended to the end of the node's list of input transformations
+    let index = match engine.edit_log.definition().traverse(&target) {
+        Ok(node) => node.transformations().len(),
         Err(err) => {
             error(&format!("Error while traversing composition tree: {}", err));
             return 1;
         }
     };
 
-    // insert the created input transformation into the list of input transformations for
-    // the node at the supplied coordinates
-    parent_node.transformations.push(transformation);
-
-    0
+    apply_edit(
+        engine,
+        TreeEdit::AddInputTransformation {
+            node: target,
+            index,
+            transformation,
+        },
+    )
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn delete_input_transformation(
-    tree_pointer: *mut CompositionTree,
+    engine_pointer: *mut EngineHandle,
     tree_depth: i32,
     coords: *const i32,
     node_index: i32,
     transformation_index: i32,
 ) -> i32 {
-    let tree: &mut CompositionTree = &mut *tree_pointer;
-
-    // traverse the tree to find the node that we're targeting
+    let engine = &mut *engine_pointer;
     let coords_slice = slice::from_raw_parts(coords, tree_depth as usize);
-    let parent_node = match get_transformation_parent(tree, coords_slice, tree_depth, node_index) {
-        Ok(parent_node) => parent_node,
-        Err(err) => {
-            error(&format!("Error while traversing composition tree: {}", err));
-            return 1;
-        }
-    };
 
-    // make sure that there are as many transformations in the list as we expect there to be
-    let transformation_count = parent_node.transformations.len() as i32;
-    if transformation_count <= transformation_index {
-        error(&format!(
-            "Attempted to remove input transformation at index {} but there are only {} transformations!",
-            transformation_index,
-            transformation_count
-        ));
-        return 1;
-    }
-
-    // actually delete the transformation from the list
-    parent_node
-        .transformations
-        .remove(transformation_index as usize);
-
-    0
+    apply_edit(
+        engine,
+        TreeEdit::DeleteInputTransformation {
+            node: transformation_target(coords_slice, node_index),
+            index: transformation_index as usize,
+        },
+    )
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn replace_input_transformation(
-    tree_pointer: *mut CompositionTree,
+    engine_pointer: *mut EngineHandle,
     tree_depth: i32,
     coords: *const i32,
     node_index: i32,
     transformation_index: i32,
     transformation_definition: *const c_char,
 ) -> i32 {
-    let tree: &mut CompositionTree = &mut *tree_pointer;
+    let engine = &mut *engine_pointer;
 
     // Convert the c-str into a &str
     let json_str: &str = match CStr::from_ptr(transformation_definition).to_str() {
         Ok(s) => s,
         Err(_) => {
-            error("Invalid UTF8 string provided to `replace_node()`");
-            return 1;
-        }
-    };
-
-    // Try to build the `IrNode`
-    let ir: IrNode = match serde_json::from_str::<IrNode>(json_str) {
-        Ok(ir) => ir,
-        Err(err) => {
-            error(&format!(
-                "Unable to convert string into `IrNode`: {:?}",
-                err
-            ));
+            error("Invalid UTF8 string provided to `replace_input_transformation()`");
             return 1;
         }
     };
 
-    // try to convert the `IrNode` into an `InputTransformation`
-    let transformation_def: InputTransformationDefinition = match ir.try_into() {
-        Ok(t) => t,
+    let transformation = match build_transformation(json_str) {
+        Ok(transformation) => transformation,
         Err(err) => {
-            error(&format!(
-                "Unable to convert `IrNode` into `InputTransformation`: {:?}",
-                err
-            ));
+            error(&err);
             return 1;
         }
     };
 
-    // convert the definition into a proper `InputTransformation`
-    let transformation: InputTransformation = transformation_def.into();
-
-    // traverse the tree to find the node that we're targeting
     let coords_slice = slice::from_raw_parts(coords, tree_depth as usize);
-    let parent_node = match get_transformation_parent(tree, coords_slice, tree_depth, node_index) {
-        Ok(parent_node) => parent_node,
-        Err(err) => {
-            error(&format!("Error while traversing composition tree: {}", err));
-            return 1;
-        }
-    };
-
-    // make sure that there are as many transformations in the list as we expect there to be
-    let transformation_count = parent_node.transformations.len() as i32;
-    if transformation_count <= transformation_index {
-        error(&format!(
-            "Attempted to remove input transformation at index {} but there are only {} transformations!",
-            transformation_index,
-            transformation_count
-        ));
-        return 1;
-    }
-
-    // actually replace the transformation
-    parent_node.transformations[transformation_index as usize] = transformation;
-
-    0
+    apply_edit(
+        engine,
+        TreeEdit::ReplaceInputTransformation {
+            node: transformation_target(coords_slice, node_index),
+            index: transformation_index as usize,
+            transformation,
+        },
+    )
 }
 
 /// Replaces the `CompositionScheme` of the composed tree node at (depth, index) with the supplied one.
 /// Returns 0 if it's successful and 1 if there's an error.
 #[no_mangle]
 pub unsafe extern "C" fn set_composition_scheme(
-    tree_pointer: *mut CompositionTree,
+    engine_pointer: *mut EngineHandle,
     depth: i32,
     coords: *const i32,
     scheme_json: *const c_char,
 ) -> i32 {
-    let tree = &mut *(tree_pointer);
+    let engine = &mut *engine_pointer;
 
     // Convert the c-str into a &str
     let json_str: &str = match CStr::from_ptr(scheme_json).to_str() {
@@ -480,7 +377,7 @@ pub unsafe extern "C" fn set_composition_scheme(
     };
 
     // Attempt to parse the JSON definition into a `CompositionScheme`
-    let new_scheme = match serde_json::from_str::<CompositionScheme>(json_str) {
+    let scheme = match serde_json::from_str::<CompositionScheme>(json_str) {
         Ok(scheme) => scheme,
         Err(err) => {
             error(&format!(
@@ -493,22 +390,109 @@ pub unsafe extern "C" fn set_composition_scheme(
 
     // Attempt to replace the scheme of the composition node at the supplied coordinates with the new scheme
     let coords_slice = slice::from_raw_parts(coords, depth as usize);
-    match tree.set_composition_scheme(depth as usize, coords_slice, new_scheme) {
-        Ok(_) => 0,
+    apply_edit(
+        engine,
+        TreeEdit::SetCompositionScheme {
+            node: coords_slice.to_vec(),
+            scheme,
+        },
+    )
+}
+
+/// Applies a JSON-encoded list of `TreeEdit`s to the composition tree as a single unit.  All of the edits are validated
+/// against the tree's definition before the tree is modified, so if any of them fails, none of them are applied and the
+/// tree is left unchanged.  The whole list is recorded as one edit in the edit log.  Returns 0 if successful and 1 if
+/// there was an error.
+#[no_mangle]
+pub unsafe extern "C" fn apply_edits(
+    engine_pointer: *mut EngineHandle,
+    edits_json: *const c_char,
+) -> i32 {
+    let engine = &mut *engine_pointer;
+
+    // Convert the c-str into a &str
+    let json_str: &str = match CStr::from_ptr(edits_json).to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            error("Invalid UTF8 string provided to `apply_edits()`");
+            return 1;
+        }
+    };
+
+    let edits = match serde_json::from_str::<Vec<TreeEdit>>(json_str) {
+        Ok(edits) => edits,
+        Err(err) => {
+            error(&format!(
+                "Error while attempting to deserialize list of `TreeEdit`s: {:?}",
+                err
+            ));
+            return 1;
+        }
+    };
+
+    apply_edit(engine, TreeEdit::Batch { edits })
+}
+
+/// Shared implementation of `undo_edit()` and `redo_edit()`.  Returns the edit that was applied to the tree as a
+/// JSON-encoded `TreeEdit` so that the UI can make the same change to its copy of the tree, or a null pointer if there
+/// was nothing to undo/redo or an error occurred.
+unsafe fn step_history(engine_pointer: *mut EngineHandle, undo: bool) -> *mut c_char {
+    let engine = &mut *engine_pointer;
+    let res = if undo { engine.undo() } else { engine.redo() };
+
+    let edit = match res {
+        Ok(Some(edit)) => edit,
+        Ok(None) => return ptr::null_mut(),
         Err(err) => {
             error(&err);
-            1
+            return ptr::null_mut();
+        }
+    };
+
+    match serde_json::to_string(&edit) {
+        Ok(json) => CString::new(json).unwrap().into_raw(),
+        Err(err) => {
+            error(&format!("Error while serializing `TreeEdit`: {:?}", err));
+            ptr::null_mut()
         }
     }
 }
 
+/// Reverses the most recent edit made to the composition tree, rebuilding the affected subtree.  Returns the edit that
+/// was applied in order to do so as a JSON-encoded `TreeEdit`, or a null pointer if there was nothing to undo.  The
+/// returned string must be freed by passing it to `free_string()`.
+#[no_mangle]
+pub unsafe extern "C" fn undo_edit(engine_pointer: *mut EngineHandle) -> *mut c_char {
+    step_history(engine_pointer, true)
+}
+
+/// Re-applies the most recently undone edit, rebuilding the affected subtree.  Returns the edit that was applied as a
+/// JSON-encoded `TreeEdit`, or a null pointer if there was nothing to redo.  The returned string must be freed by
+/// passing it to `free_string()`.
+#[no_mangle]
+pub unsafe extern "C" fn redo_edit(engine_pointer: *mut EngineHandle) -> *mut c_char {
+    step_history(engine_pointer, false)
+}
+
+/// Returns 1 if there is an edit that can be undone and 0 otherwise.
+#[no_mangle]
+pub unsafe extern "C" fn can_undo(engine_pointer: *mut EngineHandle) -> i32 {
+    (&*engine_pointer).edit_log.can_undo() as i32
+}
+
+/// Returns 1 if there is an edit that can be redone and 0 otherwise.
+#[no_mangle]
+pub unsafe extern "C" fn can_redo(engine_pointer: *mut EngineHandle) -> i32 {
+    (&*engine_pointer).edit_log.can_redo() as i32
+}
+
 /// Replaces the entire composition tree with a new one created from the provided definition.
 #[no_mangle]
 pub unsafe extern "C" fn initialize_from_scratch(
-    tree_pointer: *mut CompositionTree,
+    engine_pointer: *mut EngineHandle,
     def: *const c_char,
 ) -> i32 {
-    let tree = &mut *(tree_pointer);
+    let engine = &mut *engine_pointer;
 
     // Convert the c-str into a &str
     let def_str: &str = match CStr::from_ptr(def).to_str() {
@@ -519,7 +503,7 @@ pub unsafe extern "C" fn initialize_from_scratch(
         }
     };
 
-    let (color_fn, new_tree) = match build_tree_from_def(def_str) {
+    let (_, new_def) = match build_def_from_str(def_str) {
         Ok(x) => x,
         Err(err) => {
             error(&format!(
@@ -530,59 +514,222 @@ pub unsafe extern "C" fn initialize_from_scratch(
         }
     };
 
-    // replace the old tree with the new one.
-    let _ = mem::replace(tree, new_tree);
+    // replace the old tree with the new one, starting a new edit log for it.  The color function is also stored in its
+    // global configuration, so it is switched along with the tree.
+    engine.replace_tree(new_def);
+
+    0
+}
+
+/// Renders the subtree at the supplied coordinates into `buf` as `size` x `size` RGBA pixel data, which must have room
+/// for `size * size * 4` bytes.  The same viewport, point in time, and color function as the main canvas are used so
+/// the preview shows what the subtree contributes to the displayed output.  Returns 0 if successful and 1 if there was
+/// an error.
+#[no_mangle]
+pub unsafe extern "C" fn render_node_preview_into(
+    engine_pointer: *mut EngineHandle,
+    depth: i32,
+    coords: *const i32,
+    size: usize,
+    buf: *mut u8,
+) -> i32 {
+    let engine = &*engine_pointer;
+    let coords_slice = slice::from_raw_parts(coords, depth as usize);
 
-    // set the active color function
-    ACTIVE_COLOR_FUNCTION = color_fn;
+    let pixels = match render_node_preview(
+        engine.tree(),
+        coords_slice,
+        engine.tree().global_conf.color_function,
+        engine.stepper.conf.canvas_size,
+        size,
+        engine.seq() as f64,
+    ) {
+        Ok(pixels) => pixels,
+        Err(err) => {
+            error(&format!("Error while rendering node preview: {}", err));
+            return 1;
+        }
+    };
 
+    slice::from_raw_parts_mut(buf, pixels.len()).copy_from_slice(&pixels);
     0
 }
 
+/// Renders the subtree at the supplied coordinates in place of the entire composition tree until `clear_solo_node()` is
+/// called.  Returns 0 if successful and 1 if there is no node at the supplied coordinates.
 #[no_mangle]
-pub unsafe extern "C" fn set_canvas_size(engine_pointer: *mut NoiseStepper, size: usize) {
-    // debug(&format!("Setting canvas size to {} on the Rust side...", size));
+pub unsafe extern "C" fn set_solo_node(
+    engine_pointer: *mut EngineHandle,
+    depth: i32,
+    coords: *const i32,
+) -> i32 {
+    let engine = &mut *engine_pointer;
+    let coords_slice = slice::from_raw_parts(coords, depth as usize);
+
+    match engine.tree_mut().solo(Some(coords_slice)) {
+        Ok(_) => 0,
+        Err(err) => {
+            error(&err);
+            1
+        }
+    }
+}
+
+/// Goes back to rendering the entire composition tree after a node was soloed with `set_solo_node()`.
+#[no_mangle]
+pub unsafe extern "C" fn clear_solo_node(engine_pointer: *mut EngineHandle) {
     let engine = &mut *engine_pointer;
-    engine.conf.canvas_size = size;
-    engine.conf.needs_resize = true;
+    let _ = engine.tree_mut().solo(None);
 }
 
-/// Deallocates the previous engine and tree pointer set up by the runtime and cancels the
-/// Emscripten event loop.
+/// Samples the composition tree using the supplied JSON-encoded `AnalysisConf` and returns statistics about its values
+/// as a JSON-encoded `ValueStats`.  An empty configuration object uses the defaults for all fields.  Returns a null
+/// pointer if there was an error.  The returned string must be freed by passing it to `free_string()`.
 #[no_mangle]
-pub unsafe extern "C" fn cleanup_runtime(
-    engine_pointer: *mut NoiseStepper,
-    tree_pointer: *mut CompositionTree,
-) {
-    emscripten_cancel_main_loop();
+pub unsafe extern "C" fn analyze_composition(
+    engine_pointer: *mut EngineHandle,
+    conf_json: *const c_char,
+) -> *mut c_char {
+    let tree = (&*engine_pointer).tree();
+
+    // Convert the c-str into a &str
+    let json_str: &str = match CStr::from_ptr(conf_json).to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            error("Invalid UTF8 string provided to `analyze_composition()`");
+            return ptr::null_mut();
+        }
+    };
+
+    let conf: AnalysisConf = match serde_json::from_str(json_str) {
+        Ok(conf) => conf,
+        Err(err) => {
+            error(&format!(
+                "Error while attempting to deserialize `AnalysisConf`: {:?}",
+                err
+            ));
+            return ptr::null_mut();
+        }
+    };
+
+    let stats_json = match tree
+        .analyze(&conf)
+        .and_then(|stats| serde_json::to_string(&stats).map_err(|err| format!("{:?}", err)))
+    {
+        Ok(json) => json,
+        Err(err) => {
+            error(&format!("Error while analyzing composition tree: {}", err));
+            return ptr::null_mut();
+        }
+    };
+
+    CString::new(stats_json).unwrap().into_raw()
+}
+
+/// Traces the evaluation of the composition tree at the supplied canvas pixel at the engine's current point in time.
+/// Returns a JSON-encoded `PixelProbe` containing the final value along with the transformed coordinate and output of
+/// every node, or a null pointer if there was an error.  The returned string must be freed by passing it to
+/// `free_string()`.
+#[no_mangle]
+pub unsafe extern "C" fn probe_pixel(
+    engine_pointer: *mut EngineHandle,
+    x: f64,
+    y: f64,
+) -> *mut c_char {
+    let engine = &*engine_pointer;
+    let probe = engine.tree().probe(x, y, engine.seq() as f64);
+
+    match serde_json::to_string(&probe) {
+        Ok(json) => CString::new(json).unwrap().into_raw(),
+        Err(err) => {
+            error(&format!("Error while serializing pixel probe: {:?}", err));
+            ptr::null_mut()
+        }
+    }
+}
 
-    let tree: &mut CompositionTree = &mut *tree_pointer;
-    let old_tree = mem::replace(tree, create_initial_tree());
-    drop(old_tree);
+/// Frees a string that was returned to the JS side by one of the other exported functions.
+#[no_mangle]
+pub unsafe extern "C" fn free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
 }
 
-/// Pauses the simulation by halting the Emscripten browser event loop.
+/// Returns timing statistics about the frames rendered by the engine instance along with the resolution that they're
+/// rendered at as a JSON-encoded `FrameStats`, or a null pointer if there was an error.  The returned string must be
+/// freed by passing it to `free_string()`.
 #[no_mangle]
-pub unsafe extern "C" fn pause_engine() {
-    if !ENGINE_RUNNING {
-        emscripten_pause_main_loop();
-        ENGINE_RUNNING = false;
+pub unsafe extern "C" fn frame_stats(engine_pointer: *mut EngineHandle) -> *mut c_char {
+    let engine = &*engine_pointer;
+
+    match serde_json::to_string(engine.stepper.frame_stats()) {
+        Ok(json) => CString::new(json).unwrap().into_raw(),
+        Err(err) => {
+            error(&format!("Error while serializing frame stats: {:?}", err));
+            ptr::null_mut()
+        }
     }
 }
 
-/// Resumes the simulation by initializing the Emscripten browser event loop.
+/// Sets the time budget for rendering a single frame in milliseconds.  Frames that take longer to render are only
+/// refined down to the finest resolution that fits into it.  A budget of 0 or less disables it, always refining frames
+/// to full resolution.
+#[no_mangle]
+pub unsafe extern "C" fn set_target_frame_time(
+    engine_pointer: *mut EngineHandle,
+    target_frame_time: f64,
+) {
+    let engine = &mut *engine_pointer;
+    engine
+        .stepper
+        .set_target_frame_time(if target_frame_time > 0. {
+            Some(target_frame_time)
+        } else {
+            None
+        });
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn set_canvas_size(engine_pointer: *mut EngineHandle, size: usize) {
+    // debug(&format!("Setting canvas size to {} on the Rust side...", size));
+    let engine = &mut *engine_pointer;
+    engine.stepper.conf.canvas_size = size;
+    engine.stepper.conf.needs_resize = true;
+}
+
+/// Stops the engine instance and frees it along with its composition tree and everything else that it owns.  The
+/// pointer must not be used afterwards.  Calling this again after the instance was freed does nothing as long as no new
+/// instance has been created in the meantime.  Other instances keep running.
 #[no_mangle]
-pub unsafe extern "C" fn resume_engine() {
-    if ENGINE_RUNNING {
-        emscripten_resume_main_loop();
-        ENGINE_RUNNING = false;
+pub unsafe extern "C" fn cleanup_runtime(engine_pointer: *mut EngineHandle) {
+    let res = match SCHEDULER {
+        Some(ref mut scheduler) => scheduler.remove(engine_pointer),
+        None => Err(String::from("No engine instances have been created!")),
+    };
+
+    if let Err(err) = res {
+        error(&err);
     }
 }
 
+/// Pauses the engine instance, freezing its animation.  Other instances keep running.
+#[no_mangle]
+pub unsafe extern "C" fn pause_engine(engine_pointer: *mut EngineHandle) {
+    let engine = &mut *engine_pointer;
+    engine.paused = true;
+}
+
+/// Resumes the animation of a paused engine instance from where it left off.
+#[no_mangle]
+pub unsafe extern "C" fn resume_engine(engine_pointer: *mut EngineHandle) {
+    let engine = &mut *engine_pointer;
+    engine.paused = false;
+}
+
 /// Renders a single frame, setting the canvas to a static image.
 #[no_mangle]
-pub unsafe extern "C" fn render_single_frame() {
-    pause_engine();
-    resume_engine();
-    pause_engine();
+pub unsafe extern "C" fn render_single_frame(engine_pointer: *mut EngineHandle) {
+    let engine = &mut *engine_pointer;
+    engine.tick();
 }
@@ -4,16 +4,21 @@
  */
 
 mergeInto(LibraryManager.library, {
-  canvas_render: function(ptr) {
-    // Module.canvas is defined in the HTML file and given a direct refernce to the actual canvas object
-    var canvas = Module.canvas;
+  canvas_render: function(canvasId, ptr, size, rowsPtr, rowsLen) {
+    // Module.canvas is defined in the HTML file and given a direct refernce to the actual canvas object.  Any other
+    // canvases are registered in `Module.canvases` when the engine instances that render to them are created.
+    var canvas = canvasId === 0 ? Module.canvas : Module.canvases[canvasId];
     var ctx = canvas.getContext('2d');
 
-    // Constructs a new view into the engine's memory containing the pixel values
-    var buf = new Uint8ClampedArray(HEAPU8.buffer, ptr, canvas.width * canvas.width * 4);
-    var imageData = new ImageData(buf, canvas.width, canvas.width);
-    // set the canvas's image data to the buffer
-    ctx.putImageData(imageData, 0, 0);
+    // Constructs a new view into the engine's frame buffer containing the pixel values
+    var buf = new Uint8ClampedArray(HEAPU8.buffer, ptr, size * size * 4);
+    var imageData = new ImageData(buf, size, size);
+    // only draw the ranges of rows that changed since the previous frame, given as `[start, end)` pairs
+    for (var i = 0; i < rowsLen; i += 2) {
+      var start = HEAPU32[(rowsPtr >> 2) + i];
+      var end = HEAPU32[(rowsPtr >> 2) + i + 1];
+      ctx.putImageData(imageData, 0, 0, 0, start, size, end - start);
+    }
   },
 
   /**
@@ -25,11 +30,6 @@ mergeInto(LibraryManager.library, {
     Module.registerEnginePointer(ptr);
   },
 
-  setTreePointer: function(ptr) {
-    console.log('Setting tree pointer...');
-    Module.registerTreePointer(ptr);
-  },
-
   /**
    * Wrappers around `console.log` and `console.error` that circumvents the emulated stdout so it can be used after main exits
    */
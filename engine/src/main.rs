@@ -6,42 +6,82 @@ extern crate serde_json;
 
 extern crate libcomposition;
 
+#[cfg(target_os = "emscripten")]
 use std::ffi::CString;
-use std::os::raw::{c_char, c_void};
+use std::os::raw::c_char;
+#[cfg(target_os = "emscripten")]
+use std::os::raw::c_void;
 
+use libcomposition::budget::{FrameBudget, FrameStats};
+use libcomposition::frame::FrameBuffer;
 use libcomposition::{CompositionTree, MasterConf};
-use minutiae::emscripten::{CanvasRenderer, EmscriptenDriver};
 use minutiae::prelude::*;
-use noise::*;
+#[cfg(target_os = "emscripten")]
+use runtime::FrameRenderer;
 
+#[cfg(target_os = "emscripten")]
 extern "C" {
-    /// Given a pointer to our pixel data buffer, draws its contents to the canvas.
-    pub fn canvas_render(ptr: *const u8);
+    /// Given a pointer to our `size` x `size` pixel data buffer, draws the supplied ranges of rows to the canvas
+    /// registered with the supplied ID.  `rows_ptr` points to `rows_len` row indices forming `[start, end)` pairs.
+    pub fn canvas_render(
+        canvas_id: i32,
+        ptr: *const u8,
+        size: usize,
+        rows_ptr: *const u32,
+        rows_len: usize,
+    );
     /// Given a pointer to the noise engine's state, registers it on the JS side into the Redux store
     pub fn setEnginePointer(ptr: *const c_void);
-    /// Given a pointer to the composition tree, registers it on the JS side in the Redux store.
-    pub fn setTreePointer(ptr: *const c_void);
     /// Direct line to `console.log` from JS since the simulated `stdout` is dead after `main()` completes
     pub fn js_debug(msg: *const c_char);
     /// Direct line to `console.error` from JS since the simulated `stdout` is dead after `main()` completes
     pub fn js_error(msg: *const c_char);
-
-    pub fn emscripten_cancel_main_loop();
+    /// The browser's high resolution timer in milliseconds
+    pub fn emscripten_get_now() -> f64;
 }
 
 /// Wrapper around the JS debug function that accepts a Rust `&str`.
+#[cfg(target_os = "emscripten")]
 pub fn debug(msg: &str) {
     let c_str = CString::new(msg).unwrap();
     unsafe { js_debug(c_str.as_ptr()) };
 }
 
 /// Wrapper around the JS error function that accepts a Rust `&str`.
+#[cfg(target_os = "emscripten")]
 pub fn error(msg: &str) {
     let c_str = CString::new(msg).unwrap();
     unsafe { js_error(c_str.as_ptr()) };
 }
 
+/// Outside of the browser, messages are written to stdout and stderr instead.
+#[cfg(not(target_os = "emscripten"))]
+pub fn debug(msg: &str) {
+    println!("{}", msg);
+}
+
+#[cfg(not(target_os = "emscripten"))]
+pub fn error(msg: &str) {
+    eprintln!("{}", msg);
+}
+
+/// Returns the current time in milliseconds, which is used to measure how long frames take to render.  Only
+/// differences between the returned values are meaningful.
+#[cfg(target_os = "emscripten")]
+pub fn now() -> f64 {
+    unsafe { emscripten_get_now() }
+}
+
+#[cfg(not(target_os = "emscripten"))]
+pub fn now() -> f64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    elapsed.as_secs() as f64 * 1000. + elapsed.subsec_nanos() as f64 / 1_000_000.
+}
+
 pub mod interop;
+pub mod runtime;
 
 #[cfg(test)]
 pub mod tests;
@@ -49,8 +89,9 @@ pub mod tests;
 // Minutiae custom type declarations.
 // Since we're only using a very small subset of Minutiae's capabilities, these are mostly unused.
 
+/// Cells don't hold any state; the noise values are rendered straight into the `NoiseStepper`'s frame buffer instead.
 #[derive(Clone)]
-struct CS(f64);
+struct CS;
 impl CellState for CS {}
 
 #[derive(Clone)]
@@ -78,29 +119,6 @@ impl Engine<CS, ES, MES, CA, EA> for OurEngine {
     }
 }
 
-/// given a buffer containing all of the cells in the universe, calculates values for each of them using
-/// perlin noise and sets their states according to the result.
-fn drive_noise(
-    cells_buf: &mut [Cell<CS>],
-    seq: usize,
-    noise: &NoiseFn<Point3<f64>>,
-    universe_size: usize,
-    zoom: f64,
-    speed: f64,
-) {
-    let fseq = seq as f64;
-    for y in 0..universe_size {
-        for x in 0..universe_size {
-            // calculate noise value for current coordinate and sequence number
-            let val = noise.get([x as f64 * zoom, y as f64 * zoom, fseq * speed]);
-
-            // set the cell's state equal to that value
-            let index = get_index(x, y, universe_size);
-            cells_buf[index].state = CS(val);
-        }
-    }
-}
-
 /// Very custom function for changing the size of the universe by either removing elements from it or expanding
 /// it with elements to match the new length.  Totally ignores all entity-related stuff for now and will almost
 /// certainly break if entities are utilized in any way.
@@ -111,14 +129,38 @@ fn resize_universe(universe: &mut Universe<CS, ES, MES, CA, EA>, new_size: usize
 
     universe
         .cells
-        .resize(new_size * new_size, Cell { state: CS(0.0) });
+        .resize(new_size * new_size, Cell { state: CS });
     universe.conf.size = new_size;
 }
 
-/// Defines a middleware that sets the cell state of
+/// Middleware that renders the composition tree into the engine's frame buffer each tick.
 pub struct NoiseStepper {
-    composition_tree: Box<CompositionTree>, // The root node of the module composition tree
+    composition_tree: CompositionTree, // The root node of the module composition tree
     conf: MasterConf,
+    seq: usize, // The sequence number of the most recently rendered frame
+    /// The colors of the most recently rendered frame along with the rows that changed when it was rendered
+    frame: FrameBuffer,
+    /// Measures the time taken to render frames and picks the resolution that they're rendered at
+    budget: FrameBudget,
+}
+
+impl NoiseStepper {
+    /// The buffer containing the most recently rendered frame
+    pub fn frame(&self) -> &FrameBuffer {
+        &self.frame
+    }
+
+    /// Timing statistics about the rendered frames along with the resolution that they're rendered at
+    pub fn frame_stats(&self) -> &FrameStats {
+        self.budget.stats()
+    }
+
+    /// Sets the time budget for rendering a single frame in milliseconds.  If `None` is supplied, frames are always
+    /// refined to full resolution no matter how long they take to render.
+    pub fn set_target_frame_time(&mut self, target_frame_time: Option<f64>) {
+        self.budget.set_target_frame_time(target_frame_time);
+        self.frame.set_min_scale(self.budget.min_scale());
+    }
 }
 
 impl Middleware<CS, ES, MES, CA, EA, OurEngine> for NoiseStepper {
@@ -126,19 +168,26 @@ impl Middleware<CS, ES, MES, CA, EA, OurEngine> for NoiseStepper {
         // handle any new setting changes before rendering
 
         if self.conf.needs_resize {
-            // resize the universe if the canvas size changed, matching that size.
+            // resize the universe and the frame buffer if the canvas size changed, matching that size.
             resize_universe(universe, self.conf.canvas_size);
+            self.frame
+                .resize(self.conf.canvas_size, self.conf.canvas_size);
             self.conf.needs_resize = false;
         }
 
-        drive_noise(
-            &mut universe.cells,
-            universe.seq,
-            &*self.composition_tree,
-            self.conf.canvas_size,
-            1.0,
-            1.0,
+        self.seq = universe.seq;
+        let start = now();
+        self.frame.render(&self.composition_tree, self.seq as f64);
+        let elapsed = now() - start;
+
+        // lower the resolution that frames are refined to if they take too long to render, or raise it if there's time
+        let min_scale = self.budget.record(
+            elapsed,
+            self.frame.rendered_scale(),
+            self.frame.width(),
+            self.frame.height(),
         );
+        self.frame.set_min_scale(min_scale);
     }
 }
 
@@ -147,15 +196,42 @@ struct WorldGenerator;
 impl Generator<CS, ES, MES, CA, EA> for WorldGenerator {
     fn gen(&mut self, conf: &UniverseConf) -> (Vec<Cell<CS>>, Vec<Vec<Entity<CS, ES, MES>>>) {
         // initialize blank universe
-        (
-            vec![Cell { state: CS(0.0) }; conf.size * conf.size],
-            Vec::new(),
-        )
+        (vec![Cell { state: CS }; conf.size * conf.size], Vec::new())
     }
 }
 
-fn calc_color(cell: &Cell<CS>, _: &[usize], _: &EntityContainer<CS, ES, MES>) -> [u8; 4] {
-    unsafe { libcomposition::ACTIVE_COLOR_FUNCTION.colorize(cell.state.0 as f32) }
+/// Draws the frames rendered by the `NoiseStepper` to one of the canvases registered on the JS side.
+#[cfg(target_os = "emscripten")]
+pub struct CanvasRenderer {
+    canvas_id: i32,
+}
+
+#[cfg(target_os = "emscripten")]
+impl CanvasRenderer {
+    pub fn new(canvas_id: i32) -> Self {
+        CanvasRenderer { canvas_id }
+    }
+}
+
+#[cfg(target_os = "emscripten")]
+impl FrameRenderer for CanvasRenderer {
+    fn render(&mut self, frame: &FrameBuffer) {
+        // only the rows that changed since the previous frame are drawn
+        let changed_rows = frame.changed_rows();
+        if changed_rows.is_empty() {
+            return;
+        }
+
+        unsafe {
+            canvas_render(
+                self.canvas_id,
+                frame.pixels().as_ptr(),
+                frame.width(),
+                changed_rows.as_ptr(),
+                changed_rows.len(),
+            )
+        };
+    }
 }
 
 fn main() {
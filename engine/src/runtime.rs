@@ -0,0 +1,272 @@
+//! Defines the ownership model of running engines.  Everything that makes up an engine instance (the universe, the
+//! middleware, the frame buffer and its renderers, the composition tree and its edit log) is owned by a single
+//! `EngineHandle`, and all live instances are owned by a `Scheduler`.  Pointers to the handles are what get passed to
+//! the JS side.  The scheduler is ticked by a single driver each frame, which in turn ticks each of its instances, so
+//! any number of canvases can be animated independently using one browser event loop.
+
+#[cfg(target_os = "emscripten")]
+use std::os::raw::c_void;
+
+use libcomposition::budget::{FrameBudget, DEFAULT_TARGET_FRAME_TIME};
+use libcomposition::definition::CompositionTreeDefinition;
+use libcomposition::frame::FrameBuffer;
+use libcomposition::history::{EditLog, TreeEdit};
+use libcomposition::initial_tree::create_initial_tree_definition;
+use libcomposition::{CompositionTree, MasterConf};
+use minutiae::prelude::*;
+
+use super::{NoiseStepper, OurEngine, OurUniverse, WorldGenerator};
+
+/// Something that displays the frames rendered by an engine instance, such as the canvas renderer.
+pub trait FrameRenderer {
+    /// Called after each tick with the frame buffer that was just rendered into.
+    fn render(&mut self, frame: &FrameBuffer);
+}
+
+/// Something that repeatedly calls `Scheduler::tick()`, such as the browser's event loop.
+pub trait RuntimeDriver {
+    /// Starts ticking the supplied scheduler.  The pointer stays valid until `stop()` is called.
+    fn start(&mut self, scheduler: *mut Scheduler);
+    /// Stops ticking the scheduler.  The driver must not access the scheduler after this returns.
+    fn stop(&mut self);
+}
+
+/// Owns all of the state of a single engine instance.
+pub struct EngineHandle {
+    universe: OurUniverse,
+    engine: OurEngine,
+    /// Middleware that renders the composition tree into the frame buffer; owns the composition tree
+    pub stepper: NoiseStepper,
+    /// Display the frames rendered by the stepper, such as the canvas renderer
+    renderers: Vec<Box<FrameRenderer>>,
+    /// Records the edits made to the composition tree so that they can be undone and redone
+    pub edit_log: EditLog,
+    /// Paused instances aren't ticked by the scheduler, freezing their clock
+    pub paused: bool,
+}
+
+impl EngineHandle {
+    fn new(canvas_size: usize, renderers: Vec<Box<FrameRenderer>>) -> Self {
+        let initial_def = create_initial_tree_definition();
+        let edit_log = EditLog::new(initial_def.root_node.clone());
+
+        let mut universe_conf = UniverseConf::default();
+        universe_conf.size = canvas_size;
+        let universe = Universe::new(
+            universe_conf,
+            &mut WorldGenerator,
+            |_, _| None,
+            |_, _, _, _, _, _, _| {},
+        );
+
+        let mut conf = MasterConf::default();
+        conf.canvas_size = canvas_size;
+
+        EngineHandle {
+            universe,
+            engine: OurEngine,
+            stepper: NoiseStepper {
+                composition_tree: initial_def.into(),
+                conf,
+                seq: 0,
+                frame: FrameBuffer::new(canvas_size, canvas_size),
+                budget: FrameBudget::new(Some(DEFAULT_TARGET_FRAME_TIME)),
+            },
+            renderers,
+            edit_log,
+            paused: false,
+        }
+    }
+
+    /// Runs a single step of the simulation, rendering the next frame into the frame buffer and passing it to all
+    /// renderers.
+    pub fn tick(&mut self) {
+        self.stepper.before_render(&mut self.universe);
+        self.engine.step(&mut self.universe);
+        self.stepper.after_render(&mut self.universe);
+        self.draw();
+    }
+
+    /// Renders the current frame again without advancing the engine's clock, continuing its progressive render.
+    pub fn refine(&mut self) {
+        self.stepper.after_render(&mut self.universe);
+        self.draw();
+    }
+
+    /// Returns `true` if rendering the current frame again would change it, either because its progressive render
+    /// hasn't reached full resolution yet or because the canvas was resized.
+    pub fn needs_refinement(&self) -> bool {
+        self.stepper.frame.is_refining() || self.stepper.conf.needs_resize
+    }
+
+    fn draw(&mut self) {
+        for renderer in self.renderers.iter_mut() {
+            renderer.render(&self.stepper.frame);
+        }
+    }
+
+    pub fn tree(&self) -> &CompositionTree {
+        &self.stepper.composition_tree
+    }
+
+    /// Returns the composition tree for modification, starting a new progressive render so that the changes show up
+    /// on the next frame.
+    pub fn tree_mut(&mut self) -> &mut CompositionTree {
+        self.stepper.frame.restart();
+        &mut self.stepper.composition_tree
+    }
+
+    /// The sequence number of the most recently rendered frame
+    pub fn seq(&self) -> usize {
+        self.stepper.seq
+    }
+
+    /// Applies the edit to the composition tree, recording it in the edit log so that it can be undone.  The tree is
+    /// left unchanged if there's an error.
+    pub fn apply_edit(&mut self, edit: TreeEdit) -> Result<(), String> {
+        self.edit_log
+            .apply(&mut self.stepper.composition_tree, edit)?;
+        self.stepper.frame.restart();
+        Ok(())
+    }
+
+    /// Reverses the most recent edit, returning the edit that was applied in order to do so.
+    pub fn undo(&mut self) -> Result<Option<TreeEdit>, String> {
+        let edit = self.edit_log.undo(&mut self.stepper.composition_tree)?;
+        if edit.is_some() {
+            self.stepper.frame.restart();
+        }
+        Ok(edit)
+    }
+
+    /// Re-applies the most recently undone edit, returning the edit that was applied.
+    pub fn redo(&mut self) -> Result<Option<TreeEdit>, String> {
+        let edit = self.edit_log.redo(&mut self.stepper.composition_tree)?;
+        if edit.is_some() {
+            self.stepper.frame.restart();
+        }
+        Ok(edit)
+    }
+
+    /// Replaces the composition tree with one built from the supplied definition, starting a new edit log for it.
+    pub fn replace_tree(&mut self, def: CompositionTreeDefinition) {
+        self.edit_log = EditLog::new(def.root_node.clone());
+        self.stepper.composition_tree = def.into();
+        self.stepper.frame.restart();
+    }
+}
+
+/// Owns all live engine instances and ticks them each time it is ticked by its driver.  The driver is only running
+/// while there is at least one instance.  The scheduler must not be moved while its driver is running.
+pub struct Scheduler {
+    engines: Vec<Box<EngineHandle>>,
+    driver: Box<RuntimeDriver>,
+    driver_running: bool,
+}
+
+impl Scheduler {
+    pub fn new(driver: Box<RuntimeDriver>) -> Self {
+        Scheduler {
+            engines: Vec::new(),
+            driver,
+            driver_running: false,
+        }
+    }
+
+    /// Creates a new engine instance rendering the default composition tree, returning a pointer to it that stays valid
+    /// until the instance is removed.  The driver is started if this is the first instance.
+    // only the browser build creates instances outside of tests
+    #[cfg_attr(not(target_os = "emscripten"), allow(dead_code))]
+    pub(crate) fn add(
+        &mut self,
+        canvas_size: usize,
+        renderers: Vec<Box<FrameRenderer>>,
+    ) -> *mut EngineHandle {
+        let mut engine = Box::new(EngineHandle::new(canvas_size, renderers));
+        let handle: *mut EngineHandle = &mut *engine;
+        self.engines.push(engine);
+
+        if !self.driver_running {
+            let scheduler: *mut Scheduler = self;
+            self.driver.start(scheduler);
+            self.driver_running = true;
+        }
+
+        handle
+    }
+
+    /// Frees the supplied engine instance along with everything that it owns, stopping the driver if no instances are
+    /// left.  Returns an error without doing anything if the pointer doesn't belong to a live instance, so removing an
+    /// instance more than once is harmless.
+    pub fn remove(&mut self, handle: *const EngineHandle) -> Result<(), String> {
+        let index = self
+            .engines
+            .iter()
+            .position(|engine| &**engine as *const EngineHandle == handle)
+            .ok_or_else(|| {
+                String::from("The supplied engine pointer doesn't belong to a running engine!")
+            })?;
+        self.engines.remove(index);
+
+        if self.engines.is_empty() && self.driver_running {
+            self.driver.stop();
+            self.driver_running = false;
+        }
+
+        Ok(())
+    }
+
+    /// Ticks every instance that isn't paused.  Each instance keeps its own clock, so pausing one doesn't affect the
+    /// others.  Paused instances keep refining their current frame until it reaches full resolution, so edits made
+    /// while paused still show up at full detail.
+    pub fn tick(&mut self) {
+        for engine in self.engines.iter_mut() {
+            if !engine.paused {
+                engine.tick();
+            } else if engine.needs_refinement() {
+                engine.refine();
+            }
+        }
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        if self.driver_running {
+            self.driver.stop();
+        }
+    }
+}
+
+/// Drives the scheduler using the Emscripten browser event loop.
+#[cfg(target_os = "emscripten")]
+pub struct EmscriptenLoop;
+
+#[cfg(target_os = "emscripten")]
+extern "C" {
+    fn emscripten_set_main_loop_arg(
+        func: unsafe extern "C" fn(*mut c_void),
+        arg: *mut c_void,
+        fps: i32,
+        simulate_infinite_loop: i32,
+    );
+    fn emscripten_cancel_main_loop();
+}
+
+/// Callback registered with the browser event loop that ticks the scheduler that it was started with.
+#[cfg(target_os = "emscripten")]
+unsafe extern "C" fn tick_scheduler(scheduler: *mut c_void) {
+    (*(scheduler as *mut Scheduler)).tick();
+}
+
+#[cfg(target_os = "emscripten")]
+impl RuntimeDriver for EmscriptenLoop {
+    fn start(&mut self, scheduler: *mut Scheduler) {
+        // use the browser's frame rate and return immediately rather than blocking
+        unsafe { emscripten_set_main_loop_arg(tick_scheduler, scheduler as *mut c_void, 0, 0) };
+    }
+
+    fn stop(&mut self) {
+        unsafe { emscripten_cancel_main_loop() };
+    }
+}
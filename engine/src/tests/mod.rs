@@ -1,22 +1,33 @@
 //! Tests for the backend.
 
-use serde_json;
+use std::cell::RefCell;
+use std::ffi::CStr;
+use std::rc::Rc;
 
-use composition_tree::composition::CompositionScheme;
-use composition_tree::conf::{GlobalTreeConf, NoiseModuleConf};
-use composition_tree::definition::{
+use serde_json::{self, Value};
+
+use libcomposition::color_schemes::ColorFunction;
+use libcomposition::composition::CompositionScheme;
+use libcomposition::conf::NoiseModuleConf;
+use libcomposition::definition::{
     CompositionTreeDefinition, CompositionTreeNodeDefinition, InputTransformationDefinition,
     NoiseModuleType,
 };
-use composition_tree::{ComposedNoiseModule, CompositionTree};
-use util::Dim;
+use libcomposition::frame::FrameBuffer;
+use libcomposition::history::TreeEdit;
+use libcomposition::util::Dim;
+use libcomposition::{CompositionTree, MasterConf};
+
+use interop::{frame_stats, free_string, set_target_frame_time};
+use runtime::{EngineHandle, FrameRenderer, RuntimeDriver, Scheduler};
 
 #[test]
 fn composition_tree_definition_serialization() {
     let def = CompositionTreeDefinition {
-        global_conf: GlobalTreeConf {
+        global_conf: MasterConf {
             speed: 0.101,
             zoom: 0.1239,
+            ..MasterConf::default()
         },
         root_node: CompositionTreeNodeDefinition::Composed {
             scheme: CompositionScheme::WeightedAverage(vec![0.2, 0.1, 0.7]),
@@ -77,7 +88,8 @@ fn composition_tree_definition_serialization() {
 #[test]
 fn composition_tree_definition_deserialization_and_building() {
     let serialized_def = r#"
-        {"global_conf":{"speed":0.101,"zoom":0.1239},"root_node":{"Composed":{"scheme":{"WeightedAverage":
+        {"global_conf":{"needs_resize":false,"canvas_size":0,"speed":0.101,"zoom":0.1239,"x_offset":0.0,
+        "y_offset":0.0,"z_offset":0.0},"root_node":{"Composed":{"scheme":{"WeightedAverage":
         [0.2,0.1,0.7]},"children":[{"Leaf":{"module_type":"Fbm","module_conf":[{"MultiFractal":{"octaves":
         5,"frequency":1.1,"lacunarity":2.0,"persistence":1.5}}],"transformations":[{"ZoomScale":{"speed":
         0.812,"zoom":1.021}},{"HigherOrderNoiseModule":{"node_def":{"Composed":{"scheme":"Average","children":
@@ -89,5 +101,219 @@ fn composition_tree_definition_deserialization_and_building() {
     "#;
 
     let parsed_def: CompositionTreeDefinition = serde_json::from_str(serialized_def).unwrap();
-    let parsed_tree: CompositionTree = parsed_def.into();
+    let _parsed_tree: CompositionTree = parsed_def.into();
+}
+
+/// Counts the calls made to the mock driver and middleware by the engine.
+#[derive(Default)]
+struct RuntimeEvents {
+    starts: usize,
+    stops: usize,
+    driver_drops: usize,
+    renders: usize,
+    /// The resolution that each rendered frame was rendered at
+    rendered_scales: Vec<usize>,
+    renderer_drops: usize,
+}
+
+/// Driver that never ticks the scheduler on its own, leaving that to the test.
+struct MockDriver(Rc<RefCell<RuntimeEvents>>);
+
+impl RuntimeDriver for MockDriver {
+    fn start(&mut self, _: *mut Scheduler) {
+        self.0.borrow_mut().starts += 1;
+    }
+
+    fn stop(&mut self) {
+        self.0.borrow_mut().stops += 1;
+    }
+}
+
+impl Drop for MockDriver {
+    fn drop(&mut self) {
+        self.0.borrow_mut().driver_drops += 1;
+    }
+}
+
+struct MockRenderer(Rc<RefCell<RuntimeEvents>>);
+
+impl FrameRenderer for MockRenderer {
+    fn render(&mut self, frame: &FrameBuffer) {
+        assert!(frame.rendered_scale() > 0);
+        assert_eq!(frame.pixels().len(), 16 * 16 * 4);
+        let mut events = self.0.borrow_mut();
+        events.renders += 1;
+        events.rendered_scales.push(frame.rendered_scale());
+    }
+}
+
+impl Drop for MockRenderer {
+    fn drop(&mut self) {
+        self.0.borrow_mut().renderer_drops += 1;
+    }
+}
+
+fn add_mock_engine(
+    scheduler: &mut Scheduler,
+    events: &Rc<RefCell<RuntimeEvents>>,
+) -> *mut EngineHandle {
+    scheduler.add(16, vec![Box::new(MockRenderer(events.clone()))])
+}
+
+#[test]
+fn repeated_init_and_destroy() {
+    let events = Rc::new(RefCell::new(RuntimeEvents::default()));
+    let mut scheduler = Scheduler::new(Box::new(MockDriver(events.clone())));
+
+    for i in 0..3 {
+        let handle = add_mock_engine(&mut scheduler, &events);
+        let engine = unsafe { &mut *handle };
+        scheduler.tick();
+        scheduler.tick();
+        assert_eq!(engine.seq(), 2);
+        engine
+            .apply_edit(TreeEdit::DeleteNode {
+                parent: Vec::new(),
+                index: 0,
+            })
+            .unwrap();
+        assert!(engine.edit_log.can_undo());
+
+        scheduler.remove(handle).unwrap();
+        // destroying the same instance again doesn't free it twice
+        assert!(scheduler.remove(handle).is_err());
+
+        // the driver only runs while there are instances to drive
+        let events = events.borrow();
+        assert_eq!(events.starts, i + 1);
+        assert_eq!(events.stops, i + 1);
+        assert_eq!(events.renders, (i + 1) * 2);
+        assert_eq!(events.renderer_drops, i + 1);
+    }
+
+    // dropping the scheduler frees its remaining instances and its driver
+    add_mock_engine(&mut scheduler, &events);
+    drop(scheduler);
+    let events = events.borrow();
+    assert_eq!(events.stops, 4);
+    assert_eq!(events.driver_drops, 1);
+    assert_eq!(events.renderer_drops, 4);
+}
+
+#[test]
+fn independent_engine_instances() {
+    let events = Rc::new(RefCell::new(RuntimeEvents::default()));
+    let mut scheduler = Scheduler::new(Box::new(MockDriver(events.clone())));
+    let first = add_mock_engine(&mut scheduler, &events);
+    let second = add_mock_engine(&mut scheduler, &events);
+    assert_eq!(events.borrow().starts, 1);
+
+    // each instance has its own tree and color function
+    unsafe { &mut *second }
+        .tree_mut()
+        .global_conf
+        .color_function = ColorFunction::BlackAndWhite;
+    unsafe { &mut *second }
+        .apply_edit(TreeEdit::DeleteNode {
+            parent: Vec::new(),
+            index: 0,
+        })
+        .unwrap();
+    assert!(!unsafe { &*first }.edit_log.can_undo());
+    assert_eq!(
+        unsafe { &*first }.tree().global_conf.color_function,
+        ColorFunction::TieDye
+    );
+
+    // and its own clock, which stops while it is paused
+    scheduler.tick();
+    unsafe { &mut *first }.paused = true;
+    scheduler.tick();
+    scheduler.tick();
+    assert_eq!(unsafe { &*first }.seq(), 1);
+    assert_eq!(unsafe { &*second }.seq(), 3);
+
+    // removing one instance leaves the other running
+    scheduler.remove(first).unwrap();
+    scheduler.tick();
+    assert_eq!(unsafe { &*second }.seq(), 4);
+    assert_eq!(events.borrow().stops, 0);
+    scheduler.remove(second).unwrap();
+    assert_eq!(events.borrow().stops, 1);
+}
+
+#[test]
+fn progressive_rendering() {
+    let events = Rc::new(RefCell::new(RuntimeEvents::default()));
+    let mut scheduler = Scheduler::new(Box::new(MockDriver(events.clone())));
+    let handle = add_mock_engine(&mut scheduler, &events);
+    let engine = unsafe { &mut *handle };
+    engine.stepper.set_target_frame_time(None);
+
+    // each frame doubles the resolution until the full resolution is reached
+    for _ in 0..5 {
+        scheduler.tick();
+    }
+    assert_eq!(events.borrow().rendered_scales, vec![8, 4, 2, 1, 1]);
+    assert!(!engine.needs_refinement());
+
+    // paused instances only render while their frame is being refined, without advancing their clock
+    engine.paused = true;
+    scheduler.tick();
+    assert_eq!(events.borrow().renders, 5);
+
+    engine
+        .apply_edit(TreeEdit::DeleteNode {
+            parent: Vec::new(),
+            index: 0,
+        })
+        .unwrap();
+    for _ in 0..5 {
+        scheduler.tick();
+    }
+    assert_eq!(&events.borrow().rendered_scales[5..], &[8, 4, 2, 1]);
+    assert_eq!(engine.seq(), 5);
+
+    // undoing edits, changing the global configuration and resizing the canvas all start a new progressive render
+    engine.undo().unwrap();
+    scheduler.tick();
+    engine.tree_mut().global_conf.color_function = ColorFunction::BlackAndWhite;
+    scheduler.tick();
+    engine.stepper.conf.needs_resize = true;
+    scheduler.tick();
+    assert_eq!(&events.borrow().rendered_scales[9..], &[8, 8, 8]);
+    assert_eq!(engine.seq(), 5);
+}
+
+#[test]
+fn frame_budget_limits_refinement() {
+    let events = Rc::new(RefCell::new(RuntimeEvents::default()));
+    let mut scheduler = Scheduler::new(Box::new(MockDriver(events.clone())));
+    let handle = add_mock_engine(&mut scheduler, &events);
+
+    // an engine that can't fit any frame into its budget stops refining at the coarsest resolution, even while paused
+    unsafe { set_target_frame_time(handle, 1e-12) };
+    scheduler.tick();
+    unsafe { &mut *handle }.paused = true;
+    scheduler.tick();
+    scheduler.tick();
+    assert_eq!(events.borrow().rendered_scales, vec![8]);
+
+    let stats_ptr = unsafe { frame_stats(handle) };
+    let stats_json = unsafe { CStr::from_ptr(stats_ptr) }.to_str().unwrap();
+    let stats: Value = serde_json::from_str(stats_json).unwrap();
+    unsafe { free_string(stats_ptr) };
+    assert_eq!(stats["render_scale"], 8);
+    assert_eq!(stats["min_scale"], 8);
+    assert_eq!(stats["effective_width"], 2);
+    assert_eq!(stats["target_frame_time"], 1e-12);
+
+    // disabling the budget refines the paused frame to full resolution
+    unsafe { set_target_frame_time(handle, 0.) };
+    for _ in 0..4 {
+        scheduler.tick();
+    }
+    assert_eq!(&events.borrow().rendered_scales[1..], &[4, 2, 1]);
+    let stats = unsafe { &*handle }.stepper.frame_stats();
+    assert_eq!(stats.target_frame_time, None);
 }
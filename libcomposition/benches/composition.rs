@@ -0,0 +1,166 @@
+//! Benchmarks for evaluating composition trees and mapping their output to colors.  Run them with `cargo bench`; the
+//! results of the previous run are kept in `target/criterion` so that changes show up as a difference.
+
+#[macro_use]
+extern crate criterion;
+extern crate libcomposition;
+extern crate noise;
+
+use criterion::{black_box, Benchmark, Criterion, Throughput};
+use noise::NoiseFn;
+
+use libcomposition::color_schemes::ColorFunction;
+use libcomposition::composition::CompositionScheme;
+use libcomposition::conf::NoiseModuleConf;
+use libcomposition::definition::{
+    CompositionTreeDefinition, CompositionTreeNodeDefinition, InputTransformationDefinition,
+    NoiseModuleType,
+};
+use libcomposition::initial_tree::create_initial_tree;
+use libcomposition::render::{colorize_values, render_values};
+use libcomposition::util::Dim;
+use libcomposition::{CompositionTree, MasterConf};
+
+/// The width and height of the grids that are rendered
+const GRID_SIZE: usize = 64;
+
+fn leaf(
+    module_type: NoiseModuleType,
+    module_conf: Vec<NoiseModuleConf>,
+) -> CompositionTreeNodeDefinition {
+    CompositionTreeNodeDefinition::Leaf {
+        module_type,
+        module_conf,
+        transformations: Vec::new(),
+    }
+}
+
+fn multifractal(module_type: NoiseModuleType, octaves: u32) -> CompositionTreeNodeDefinition {
+    leaf(
+        module_type,
+        vec![NoiseModuleConf::MultiFractal {
+            octaves,
+            frequency: 1.0,
+            lacunarity: 2.0,
+            persistence: 0.5,
+        }],
+    )
+}
+
+fn build_tree(root_node: CompositionTreeNodeDefinition) -> CompositionTree {
+    CompositionTreeDefinition {
+        global_conf: MasterConf::default(),
+        root_node,
+    }
+    .into()
+}
+
+/// A chain of composed nodes `depth` levels deep, each averaging a leaf with the next level
+fn nested_node(depth: usize) -> CompositionTreeNodeDefinition {
+    let mut children = vec![leaf(NoiseModuleType::SuperSimplex, Vec::new())];
+    if depth > 1 {
+        children.push(nested_node(depth - 1));
+    }
+
+    CompositionTreeNodeDefinition::Composed {
+        scheme: CompositionScheme::Average,
+        children,
+        transformations: vec![InputTransformationDefinition::ScaleAll(1.1)],
+    }
+}
+
+fn build_deep_tree() -> CompositionTree {
+    build_tree(nested_node(12))
+}
+
+/// A leaf whose coordinates are each replaced by the output of another module, one of which is itself transformed by
+/// a higher order noise module
+fn build_honf_tree() -> CompositionTree {
+    let honf = |node_def, replaced_dim| InputTransformationDefinition::HigherOrderNoiseModule {
+        node_def,
+        replaced_dim,
+    };
+    let inner = CompositionTreeNodeDefinition::Leaf {
+        module_type: NoiseModuleType::Billow,
+        module_conf: Vec::new(),
+        transformations: vec![honf(leaf(NoiseModuleType::OpenSimplex, Vec::new()), Dim::Y)],
+    };
+
+    build_tree(CompositionTreeNodeDefinition::Composed {
+        scheme: CompositionScheme::Average,
+        children: vec![CompositionTreeNodeDefinition::Leaf {
+            module_type: NoiseModuleType::Fbm,
+            module_conf: Vec::new(),
+            transformations: vec![
+                honf(inner, Dim::X),
+                honf(multifractal(NoiseModuleType::Fbm, 4), Dim::Y),
+                honf(leaf(NoiseModuleType::SuperSimplex, Vec::new()), Dim::Z),
+            ],
+        }],
+        transformations: Vec::new(),
+    })
+}
+
+fn build_high_octave_tree() -> CompositionTree {
+    build_tree(CompositionTreeNodeDefinition::Composed {
+        scheme: CompositionScheme::Average,
+        children: vec![
+            multifractal(NoiseModuleType::Fbm, 16),
+            multifractal(NoiseModuleType::RidgedMulti, 16),
+            multifractal(NoiseModuleType::HybridMulti, 16),
+        ],
+        transformations: Vec::new(),
+    })
+}
+
+/// The representative trees that are benchmarked, along with the functions that build them
+const TREES: &[(&str, fn() -> CompositionTree)] = &[
+    ("initial", create_initial_tree),
+    ("deep", build_deep_tree),
+    ("honf", build_honf_tree),
+    ("high_octave", build_high_octave_tree),
+];
+
+fn tree_benchmarks(c: &mut Criterion) {
+    for &(name, build) in TREES {
+        let point_tree = build();
+        let grid_tree = build();
+
+        c.bench(
+            name,
+            Benchmark::new("point", move |b| {
+                b.iter(|| point_tree.get(black_box([12.5, 37.25, 3.0])))
+            })
+            .throughput(Throughput::Elements(1)),
+        );
+        c.bench(
+            name,
+            Benchmark::new("grid", move |b| {
+                b.iter(|| render_values(&grid_tree, GRID_SIZE, GRID_SIZE, black_box(3.0)))
+            })
+            .throughput(Throughput::Elements((GRID_SIZE * GRID_SIZE) as u32)),
+        );
+    }
+}
+
+fn colorization_benchmarks(c: &mut Criterion) {
+    // values spread evenly across the range that color functions accept
+    let count = GRID_SIZE * GRID_SIZE;
+    let values: Vec<f64> = (0..count)
+        .map(|i| (i as f64 / (count - 1) as f64) * 2. - 1.)
+        .collect();
+
+    for &color_fn in ColorFunction::ALL.iter() {
+        let values = values.clone();
+        c.bench(
+            "colorize",
+            Benchmark::new(format!("{}", color_fn), move |b| {
+                b.iter(|| colorize_values(&values, color_fn))
+            })
+            .throughput(Throughput::Elements(count as u32)),
+        );
+    }
+}
+
+criterion_group!(benches, tree_benchmarks, colorization_benchmarks);
+criterion_main!(benches);
@@ -0,0 +1,30 @@
+//! Builds composition trees out of arbitrary definition strings, which the backend accepts from untrusted users.
+//! Building must either succeed or return an error without panicking, and trees that are built must be able to be
+//! evaluated.
+
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate libcomposition;
+extern crate noise;
+
+use std::str;
+
+use libcomposition::util::build_tree_from_def;
+use noise::NoiseFn;
+
+/// Points at which built trees are evaluated, including some far away from the origin
+const POINTS: [[f64; 3]; 3] = [[0., 0., 0.], [12.5, -37.25, 3.], [1e12, -1e12, 1e12]];
+
+fuzz_target!(|data: &[u8]| {
+    let def = match str::from_utf8(data) {
+        Ok(def) => def,
+        Err(_) => return,
+    };
+
+    if let Ok((color_fn, tree)) = build_tree_from_def(def) {
+        for &point in POINTS.iter() {
+            color_fn.colorize(tree.get(point) as f32);
+        }
+    }
+});
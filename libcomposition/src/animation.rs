@@ -0,0 +1,339 @@
+//! Renders compositions as animations over time, which are encoded as animated GIFs, APNGs, or sequences of
+//! numbered PNG frames.
+
+use std::fs::{create_dir_all, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
+use gif::{self, SetParameter};
+
+use color_schemes::ColorFunction;
+use render::{render_looped_values, render_values, shade_values, write_png, LoopConf, LoopMode};
+use CompositionTree;
+
+/// The formats that animations can be exported to.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AnimationFormat {
+    /// Animated GIF using a 256-color palette sampled from the color function
+    Gif,
+    /// Animated PNG
+    Apng,
+    /// A directory of numbered PNG images, one per frame
+    Frames,
+}
+
+impl FromStr for AnimationFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gif" => Ok(AnimationFormat::Gif),
+            "apng" | "png" => Ok(AnimationFormat::Apng),
+            "frames" => Ok(AnimationFormat::Frames),
+            _ => Err(format!(
+                "Unable to convert \"{}\" into `AnimationFormat`!",
+                s
+            )),
+        }
+    }
+}
+
+/// Determines which points in time are rendered as frames of an animation.  Times are in the same units as engine
+/// ticks.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct AnimationConf {
+    pub start_time: f64,
+    pub end_time: f64,
+    pub frame_count: usize,
+    pub fps: f64,
+    /// If set, the animation loops seamlessly with a period of `end_time - start_time`.  The last frame is one step
+    /// before the end time since the end time is the same as the start time.
+    pub seamless_loop: bool,
+}
+
+impl AnimationConf {
+    fn validate(&self) -> Result<(), String> {
+        if self.frame_count == 0 {
+            return Err("Animations must have at least one frame!".into());
+        } else if !(self.fps > 0.) {
+            return Err("Animation frame rate must be greater than zero!".into());
+        } else if self.seamless_loop && !(self.end_time > self.start_time) {
+            return Err("Looping animations must have an end time after their start time!".into());
+        }
+
+        Ok(())
+    }
+
+    /// Returns the point in time that each frame of the animation is rendered at.
+    pub fn frame_times(&self) -> Vec<f64> {
+        let duration = self.end_time - self.start_time;
+        let steps = if self.seamless_loop || self.frame_count == 1 {
+            self.frame_count
+        } else {
+            self.frame_count - 1
+        };
+
+        (0..self.frame_count)
+            .map(|i| self.start_time + duration * (i as f64 / steps as f64))
+            .collect()
+    }
+
+    /// Returns how long each frame is displayed for, rounded to the supplied number of units per second.  Durations
+    /// are calculated from the rounded end times of each frame so that rounding errors don't accumulate.
+    fn frame_durations(&self, units_per_second: f64) -> Vec<u16> {
+        let end_time = |i: usize| (i as f64 * units_per_second / self.fps).round() as u64;
+        (0..self.frame_count)
+            .map(|i| (end_time(i + 1) - end_time(i)).min(u16::max_value() as u64) as u16)
+            .collect()
+    }
+}
+
+/// Renders the values of each frame of the animation.  Looping animations use the best `LoopMode` supported by the
+/// tree.
+pub fn render_animation_values(
+    tree: &CompositionTree,
+    width: usize,
+    height: usize,
+    conf: &AnimationConf,
+) -> Result<Vec<Vec<f64>>, String> {
+    conf.validate()?;
+
+    let loop_conf = LoopConf {
+        mode: LoopMode::best_for(tree),
+        period: conf.end_time - conf.start_time,
+    };
+
+    conf.frame_times()
+        .into_iter()
+        .map(|time| {
+            if conf.seamless_loop {
+                render_looped_values(tree, width, height, time, &loop_conf)
+            } else {
+                Ok(render_values(tree, width, height, time))
+            }
+        })
+        .collect()
+}
+
+/// Returns a palette of 256 colors evenly sampled from the color function over the range [-1, 1].
+fn build_palette(color_fn: ColorFunction) -> Vec<u8> {
+    let mut palette = Vec::with_capacity(256 * 3);
+    for i in 0..256 {
+        let color = color_fn.colorize((-1. + (i as f64 / 255.) * 2.) as f32);
+        palette.extend_from_slice(&color[..3]);
+    }
+
+    palette
+}
+
+/// Returns the index of the color in the palette produced by `build_palette` that is closest to the value's color.
+fn palette_index(val: f64) -> u8 {
+    (((val.max(-1.).min(1.) + 1.) / 2.) * 255.).round() as u8
+}
+
+/// Encodes frames of values as an infinitely repeating animated GIF.  Rather than quantizing the colors of each frame,
+/// the palette is sampled from the color function and values are mapped directly to palette indices.
+pub fn write_gif<W: Write>(
+    writer: W,
+    width: usize,
+    height: usize,
+    frames: &[Vec<f64>],
+    color_fn: ColorFunction,
+    conf: &AnimationConf,
+) -> Result<(), String> {
+    if width > u16::max_value() as usize || height > u16::max_value() as usize {
+        return Err("GIF images can be at most 65535 pixels wide or tall!".into());
+    }
+
+    let map_err = |err| format!("Unable to write GIF data: {}", err);
+    let mut encoder = gif::Encoder::new(
+        writer,
+        width as u16,
+        height as u16,
+        &build_palette(color_fn),
+    )
+    .map_err(map_err)?;
+    encoder.set(gif::Repeat::Infinite).map_err(map_err)?;
+
+    // GIF frame delays are in hundredths of a second
+    for (values, delay) in frames.iter().zip(conf.frame_durations(100.)) {
+        let indices: Vec<u8> = values.iter().map(|&val| palette_index(val)).collect();
+        let mut frame =
+            gif::Frame::from_indexed_pixels(width as u16, height as u16, &indices, None);
+        frame.delay = delay;
+        encoder.write_frame(&frame).map_err(map_err)?;
+    }
+
+    Ok(())
+}
+
+/// Computes the CRC-32 of the supplied bytes as used by PNG chunks.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+fn write_png_chunk(buf: &mut Vec<u8>, chunk_type: &[u8], data: &[u8]) {
+    buf.write_u32::<BigEndian>(data.len() as u32).unwrap();
+    let crc_start = buf.len();
+    buf.extend_from_slice(chunk_type);
+    buf.extend_from_slice(data);
+    let crc = crc32(&buf[crc_start..]);
+    buf.write_u32::<BigEndian>(crc).unwrap();
+}
+
+/// Splits an encoded PNG image into its `IHDR` chunk data and the concatenated data of its `IDAT` chunks.
+fn split_png(png: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let (mut ihdr, mut idat) = (Vec::new(), Vec::new());
+    // skip the signature
+    let mut i = 8;
+    while i + 8 <= png.len() {
+        let len = BigEndian::read_u32(&png[i..i + 4]) as usize;
+        let (chunk_type, data) = (&png[i + 4..i + 8], &png[i + 8..i + 8 + len]);
+        if chunk_type == b"IHDR" {
+            ihdr.extend_from_slice(data);
+        } else if chunk_type == b"IDAT" {
+            idat.extend_from_slice(data);
+        }
+        // length, type, data, and CRC
+        i += 12 + len;
+    }
+
+    (ihdr, idat)
+}
+
+/// Encodes frames of RGBA pixel data as an infinitely repeating animated PNG.  Each frame is compressed as a regular
+/// PNG image and its image data is then moved into the frame data chunks of the animation.
+pub fn write_apng<W: Write>(
+    mut writer: W,
+    width: usize,
+    height: usize,
+    frames: &[Vec<u8>],
+    conf: &AnimationConf,
+) -> Result<(), String> {
+    let mut buf: Vec<u8> = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+    // sequence numbers are shared between frame control and frame data chunks
+    let mut sequence_number = 0u32;
+
+    // APNG frame delays are fractions; use milliseconds
+    for (i, (rgba, delay)) in frames.iter().zip(conf.frame_durations(1000.)).enumerate() {
+        let mut encoded: Vec<u8> = Vec::new();
+        write_png(&mut encoded, width, height, rgba)?;
+        let (ihdr, idat) = split_png(&encoded);
+
+        if i == 0 {
+            write_png_chunk(&mut buf, b"IHDR", &ihdr);
+            let mut actl = Vec::with_capacity(8);
+            actl.write_u32::<BigEndian>(frames.len() as u32).unwrap();
+            // play forever
+            actl.write_u32::<BigEndian>(0).unwrap();
+            write_png_chunk(&mut buf, b"acTL", &actl);
+        }
+
+        let mut fctl = Vec::with_capacity(26);
+        fctl.write_u32::<BigEndian>(sequence_number).unwrap();
+        fctl.write_u32::<BigEndian>(width as u32).unwrap();
+        fctl.write_u32::<BigEndian>(height as u32).unwrap();
+        // x and y offset
+        fctl.write_u32::<BigEndian>(0).unwrap();
+        fctl.write_u32::<BigEndian>(0).unwrap();
+        fctl.write_u16::<BigEndian>(delay).unwrap();
+        fctl.write_u16::<BigEndian>(1000).unwrap();
+        // dispose op `NONE` and blend op `SOURCE`
+        fctl.extend_from_slice(&[0, 0]);
+        write_png_chunk(&mut buf, b"fcTL", &fctl);
+        sequence_number += 1;
+
+        // the first frame doubles as the default image for decoders that don't support APNG
+        if i == 0 {
+            write_png_chunk(&mut buf, b"IDAT", &idat);
+        } else {
+            let mut fdat = Vec::with_capacity(4 + idat.len());
+            fdat.write_u32::<BigEndian>(sequence_number).unwrap();
+            fdat.extend_from_slice(&idat);
+            write_png_chunk(&mut buf, b"fdAT", &fdat);
+            sequence_number += 1;
+        }
+    }
+    write_png_chunk(&mut buf, b"IEND", &[]);
+
+    writer
+        .write_all(&buf)
+        .map_err(|err| format!("Unable to write APNG data: {}", err))
+}
+
+/// Writes each frame of RGBA pixel data as a separate PNG image named `frame_00000.png`, `frame_00001.png`, etc. into
+/// the supplied directory, creating it if it doesn't exist.  Returns the paths of the written files.
+pub fn write_frame_directory(
+    dir: &Path,
+    width: usize,
+    height: usize,
+    frames: &[Vec<u8>],
+) -> Result<Vec<PathBuf>, String> {
+    create_dir_all(dir).map_err(|err| format!("Unable to create directory {:?}: {}", dir, err))?;
+
+    frames
+        .iter()
+        .enumerate()
+        .map(|(i, rgba)| {
+            let path = dir.join(format!("frame_{:05}.png", i));
+            let file = File::create(&path)
+                .map_err(|err| format!("Unable to create file {:?}: {}", path, err))?;
+            write_png(BufWriter::new(file), width, height, rgba)?;
+            Ok(path)
+        })
+        .collect()
+}
+
+/// Renders the animation and encodes it as a GIF or APNG.  Frames are colored using the relief mode from the tree's
+/// global configuration if there is one, which isn't supported for GIFs since their palette is derived from the color
+/// function.
+pub fn encode_animation(
+    tree: &CompositionTree,
+    color_fn: ColorFunction,
+    width: usize,
+    height: usize,
+    format: AnimationFormat,
+    conf: &AnimationConf,
+) -> Result<Vec<u8>, String> {
+    let frames = render_animation_values(tree, width, height, conf)?;
+    let relief_mode = tree.global_conf.relief_mode;
+
+    let mut buf: Vec<u8> = Vec::new();
+    match format {
+        AnimationFormat::Gif => {
+            if relief_mode.is_some() {
+                return Err("Relief modes can't be exported as GIFs; use APNG instead.".into());
+            }
+            write_gif(&mut buf, width, height, &frames, color_fn, conf)?;
+        }
+        AnimationFormat::Apng => {
+            let pixels: Vec<Vec<u8>> = frames
+                .iter()
+                .map(|values| shade_values(values, width, height, color_fn, relief_mode))
+                .collect();
+            write_apng(&mut buf, width, height, &pixels, conf)?;
+        }
+        AnimationFormat::Frames => {
+            return Err(
+                "Frame sequences must be written to a directory with `write_frame_directory`."
+                    .into(),
+            )
+        }
+    }
+
+    Ok(buf)
+}
@@ -0,0 +1,139 @@
+//! Keeps rendering within a per-frame time budget.  The time taken to render each frame is measured and used to
+//! estimate the cost of evaluating the tree at a single point, which determines the finest resolution that frames can be
+//! rendered at without going over budget.  Complex compositions are rendered at a lower resolution rather than freezing
+//! the browser tab.
+//!
+//! The budget doesn't measure time itself since the available clocks differ between targets; each engine measures the
+//! time taken to render a frame and passes it to `FrameBudget::record()`.
+
+use frame::COARSEST_SCALE;
+
+/// The default frame time budget in milliseconds, which holds 60 frames per second
+pub const DEFAULT_TARGET_FRAME_TIME: f64 = 1000. / 60.;
+/// The resolution is only raised if the estimated frame time at the higher resolution fits into this fraction of the
+/// budget, which keeps the resolution from flipping back and forth between frames.
+const HEADROOM: f64 = 0.75;
+/// The weight of the most recent frame in the moving averages of the measured times
+const SMOOTHING: f64 = 0.25;
+
+/// Returns the number of points at which the tree is evaluated to render a `width` x `height` frame at the supplied
+/// scale.
+fn evaluation_count(width: usize, height: usize, scale: usize) -> usize {
+    ((width + scale - 1) / scale) * ((height + scale - 1) / scale)
+}
+
+/// Timing statistics about the rendered frames along with the resolution that they are rendered at.  All times are in
+/// milliseconds.
+#[derive(Clone, Debug, Serialize)]
+pub struct FrameStats {
+    /// The time budget for rendering a single frame, or `None` if frames are always refined to full resolution
+    pub target_frame_time: Option<f64>,
+    pub last_frame_time: f64,
+    /// Moving average of the time taken to render frames
+    pub average_frame_time: f64,
+    /// Moving average of the time taken per point at which the tree is evaluated
+    pub average_evaluation_time: f64,
+    /// The size of the blocks that the most recent frame was rendered in
+    pub render_scale: usize,
+    /// The size of the blocks that frames are refined down to within the budget
+    pub min_scale: usize,
+    /// The number of points along each axis at which the tree was evaluated to render the most recent frame
+    pub effective_width: usize,
+    pub effective_height: usize,
+}
+
+/// Adapts the resolution that frames are rendered at to the measured cost of rendering them.
+pub struct FrameBudget {
+    target_frame_time: Option<f64>,
+    stats: FrameStats,
+}
+
+impl FrameBudget {
+    pub fn new(target_frame_time: Option<f64>) -> Self {
+        FrameBudget {
+            target_frame_time,
+            stats: FrameStats {
+                target_frame_time,
+                last_frame_time: 0.,
+                average_frame_time: 0.,
+                average_evaluation_time: 0.,
+                render_scale: 0,
+                min_scale: 1,
+                effective_width: 0,
+                effective_height: 0,
+            },
+        }
+    }
+
+    pub fn stats(&self) -> &FrameStats {
+        &self.stats
+    }
+
+    /// The size of the blocks that frames should be refined down to in order to stay within the budget
+    pub fn min_scale(&self) -> usize {
+        self.stats.min_scale
+    }
+
+    /// Sets the frame time budget in milliseconds.  If `None` is supplied, frames are always refined to full resolution.
+    pub fn set_target_frame_time(&mut self, target_frame_time: Option<f64>) {
+        self.target_frame_time = target_frame_time;
+        self.stats.target_frame_time = target_frame_time;
+        if target_frame_time.is_none() {
+            self.stats.min_scale = 1;
+        }
+    }
+
+    /// Records that rendering a `width` x `height` frame at the supplied scale took `elapsed` milliseconds and updates
+    /// the finest scale that fits into the budget, which is returned.
+    pub fn record(&mut self, elapsed: f64, scale: usize, width: usize, height: usize) -> usize {
+        let evaluations = evaluation_count(width, height, scale);
+        if evaluations == 0 {
+            return self.stats.min_scale;
+        }
+
+        let first_frame = self.stats.render_scale == 0;
+        let average = |average: f64, val: f64| {
+            if first_frame {
+                val
+            } else {
+                average * (1. - SMOOTHING) + val * SMOOTHING
+            }
+        };
+        self.stats.last_frame_time = elapsed;
+        self.stats.average_frame_time = average(self.stats.average_frame_time, elapsed);
+        self.stats.average_evaluation_time = average(
+            self.stats.average_evaluation_time,
+            elapsed / evaluations as f64,
+        );
+        self.stats.render_scale = scale;
+        self.stats.effective_width = (width + scale - 1) / scale;
+        self.stats.effective_height = (height + scale - 1) / scale;
+
+        if let Some(target) = self.target_frame_time {
+            self.stats.min_scale = self.finest_scale_within(target, width, height);
+        }
+        self.stats.min_scale
+    }
+
+    /// Returns the smallest scale at which the estimated time to render a frame fits into the target frame time.
+    /// Frames are never rendered more coarsely than `COARSEST_SCALE`, even if that goes over budget.
+    fn finest_scale_within(&self, target: f64, width: usize, height: usize) -> usize {
+        let mut scale = COARSEST_SCALE;
+        while scale > 1 {
+            let finer = scale / 2;
+            let limit = if finer < self.stats.min_scale {
+                target * HEADROOM
+            } else {
+                target
+            };
+            let estimate =
+                self.stats.average_evaluation_time * evaluation_count(width, height, finer) as f64;
+            if estimate > limit {
+                break;
+            }
+            scale = finer;
+        }
+
+        scale
+    }
+}
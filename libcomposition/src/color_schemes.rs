@@ -5,11 +5,12 @@ use palette::{
     rgb::{LinSrgb, Rgb, RgbStandard},
     FromColor, Gradient, Hsv,
 };
+use std::fmt::{self, Display, Formatter};
 use std::str::FromStr;
 
 /// Determines the function used to map the output of the noise functions to a pixel color to be displayed
 /// on the canvas.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum ColorFunction {
     TieDye,
     BlackAndWhite,
@@ -22,6 +23,12 @@ pub enum ColorFunction {
     AlgaeFloat,
 }
 
+impl Default for ColorFunction {
+    fn default() -> Self {
+        ColorFunction::TieDye
+    }
+}
+
 impl FromStr for ColorFunction {
     type Err = String;
 
@@ -41,6 +48,25 @@ impl FromStr for ColorFunction {
     }
 }
 
+impl Display for ColorFunction {
+    /// Produces the same key that is accepted by `from_str`, as used by the frontend.
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let key = match self {
+            &ColorFunction::TieDye => "tieDye",
+            &ColorFunction::BlackAndWhite => "blackAndWhite",
+            &ColorFunction::LavaFlow => "lavaFlow",
+            &ColorFunction::Sunset => "sunset",
+            &ColorFunction::Oceanic => "oceanic",
+            &ColorFunction::Cosmos => "cosmos",
+            &ColorFunction::PastelSea => "pastelSea",
+            &ColorFunction::Vaporwave => "vaporwave",
+            &ColorFunction::AlgaeFloat => "algaeFloat",
+        };
+
+        write!(f, "{}", key)
+    }
+}
+
 fn expand_range(byte: u8) -> f32 {
     (byte as f32 / 255.)
 }
@@ -170,6 +196,19 @@ lazy_static! {
 }
 
 impl ColorFunction {
+    /// Every available color function
+    pub const ALL: [ColorFunction; 9] = [
+        ColorFunction::TieDye,
+        ColorFunction::BlackAndWhite,
+        ColorFunction::LavaFlow,
+        ColorFunction::Sunset,
+        ColorFunction::Oceanic,
+        ColorFunction::Cosmos,
+        ColorFunction::PastelSea,
+        ColorFunction::Vaporwave,
+        ColorFunction::AlgaeFloat,
+    ];
+
     pub fn colorize(&self, val: f32) -> [u8; 4] {
         match self {
             &ColorFunction::TieDye => {
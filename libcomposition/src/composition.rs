@@ -1,34 +1,56 @@
 //! Defines a `NoiseModuleComposer` struct that combines the inputs of multiple noise modules into one single output.
 
 use std::convert::TryFrom;
+use std::iter;
 
-use noise::{NoiseFn, Point3};
-use serde_json;
+use noise::NoiseFn;
 
 use super::CompositionTreeNode;
+use dims::NoisePoint;
 use ir::IrNode;
-use util::find_setting_by_name;
+use util::convert_setting;
 
 /// Defines a way to combine the outputs of multiple noise modules into one.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub enum CompositionScheme {
     Average,
+    /// Each child's output is multiplied by the weight at its index and the sum is divided by the total weight.
+    /// Children without a corresponding weight are given a weight of 1.
     WeightedAverage(Vec<f64>),
 }
 
 impl CompositionScheme {
     /// Given a set of children noise generators and a coordinate, combines the outputs of each of the child modules and
     /// returns a single output.
-    pub fn compose(&self, children: &[CompositionTreeNode], coord: Point3<f64>) -> f64 {
+    pub fn compose<P: NoisePoint>(&self, children: &[CompositionTreeNode], coord: P) -> f64
+    where
+        CompositionTreeNode: NoiseFn<P>,
+    {
+        self.combine(children.iter().map(|child| child.get(coord)))
+    }
+
+    /// Combines the outputs of each of the child modules into a single output.
+    pub fn combine<I: Iterator<Item = f64>>(&self, outputs: I) -> f64 {
         match self {
             &CompositionScheme::Average => {
-                let sum = children
-                    .iter()
-                    .fold(0., |acc, child| acc + child.get(coord));
-                sum / children.len() as f64
+                let (sum, count) =
+                    outputs.fold((0., 0), |(sum, count), val| (sum + val, count + 1));
+                sum / count as f64
             }
             &CompositionScheme::WeightedAverage(ref weights) => {
-                unimplemented!(); // TODO
+                let weights = weights.iter().cloned().chain(iter::repeat(1.));
+                let (sum, total_weight) =
+                    outputs
+                        .zip(weights)
+                        .fold((0., 0.), |(sum, total_weight), (val, weight)| {
+                            (sum + val * weight, total_weight + weight)
+                        });
+
+                if total_weight == 0. {
+                    0.
+                } else {
+                    sum / total_weight
+                }
             }
         }
     }
@@ -38,16 +60,14 @@ impl TryFrom<IrNode> for CompositionScheme {
     type Error = String;
 
     fn try_from(node: IrNode) -> Result<Self, Self::Error> {
-        let composition_scheme = find_setting_by_name("compositionScheme", &node.settings)?;
+        let composition_scheme: String = convert_setting("compositionScheme", &node.settings)?;
 
         match composition_scheme.as_str() {
             "average" => Ok(CompositionScheme::Average),
-            "weightedAverage" => Ok(CompositionScheme::WeightedAverage({
-                let raw_val = find_setting_by_name("weights", &node.settings)?;
-                serde_json::from_str(&raw_val).map_err(|_| {
-                    format!("Unable to parse `weights` vector from string: {}", raw_val)
-                })?
-            })),
+            "weightedAverage" => Ok(CompositionScheme::WeightedAverage(convert_setting(
+                "weights",
+                &node.settings,
+            )?)),
             _ => Err(format!(
                 "Unknown composition scheme \"{}\" provided!",
                 composition_scheme
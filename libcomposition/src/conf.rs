@@ -1,10 +1,11 @@
 //! Defines the data types used to create noise modules.
 
 use std::collections::hash_map::DefaultHasher;
+use std::fmt::{self, Display, Formatter};
 use std::hash::{Hash, Hasher};
 use std::str::FromStr;
 
-use noise::{Constant, MultiFractal, RangeFunction, RidgedMulti, Seedable, Worley};
+use noise::{Constant, Fbm, MultiFractal, RangeFunction, RidgedMulti, Seedable, Worley};
 
 use super::error;
 
@@ -15,7 +16,7 @@ fn calculate_hash<T: Hash>(t: &T) -> u64 {
     s.finish()
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, JsonSchema)]
 pub enum InteropRangeFunction {
     Euclidean,
     EuclideanSquared,
@@ -24,6 +25,17 @@ pub enum InteropRangeFunction {
     Quadratic,
 }
 
+impl InteropRangeFunction {
+    /// Every available range function
+    pub const ALL: [InteropRangeFunction; 5] = [
+        InteropRangeFunction::Euclidean,
+        InteropRangeFunction::EuclideanSquared,
+        InteropRangeFunction::Manhattan,
+        InteropRangeFunction::Chebyshev,
+        InteropRangeFunction::Quadratic,
+    ];
+}
+
 impl Into<RangeFunction> for InteropRangeFunction {
     fn into(self) -> RangeFunction {
         match self {
@@ -54,10 +66,24 @@ impl FromStr for InteropRangeFunction {
     }
 }
 
+impl Display for InteropRangeFunction {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let key = match self {
+            &InteropRangeFunction::Euclidean => "euclidean",
+            &InteropRangeFunction::EuclideanSquared => "euclideanSquared",
+            &InteropRangeFunction::Manhattan => "manhattan",
+            &InteropRangeFunction::Chebyshev => "chebyshev",
+            &InteropRangeFunction::Quadratic => "quadratic",
+        };
+
+        write!(f, "{}", key)
+    }
+}
+
 /// Holds all possible configuration options for a noise module.  Since each module supports one or more of
 /// these enum variants, each `GenNoiseModule` will have an array of these that describe the configuration
 /// of that particular `GenNoiseModule`.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub enum NoiseModuleConf {
     MultiFractal {
         octaves: u32,
@@ -118,6 +144,29 @@ pub fn map_setting_to_type(key: &str) -> Result<SettingType, Option<String>> {
     }
 }
 
+/// The largest factor that noise modules may scale their input coordinates by internally.  Larger frequencies don't
+/// produce any visible detail, and they can make the lattice coordinates computed by the noise functions overflow.
+/// Frequencies and octaves are limited when modules are configured, so this doesn't cost anything per point.
+pub const MAX_INPUT_SCALE: f64 = (1u64 << 32) as f64;
+
+fn clamp_frequency(frequency: f64) -> f64 {
+    frequency.max(-MAX_INPUT_SCALE).min(MAX_INPUT_SCALE)
+}
+
+/// Returns the number of octaves that can be generated before the frequency of the highest octave exceeds
+/// `MAX_INPUT_SCALE`, up to the number of octaves supported by the noise modules.
+fn limit_octaves(octaves: u32, frequency: f64, lacunarity: f64) -> usize {
+    let max_octaves = (octaves as usize).min(Fbm::MAX_OCTAVES);
+    let mut highest_frequency = frequency.abs();
+    let mut count = 1;
+    while count < max_octaves && highest_frequency * lacunarity.abs() <= MAX_INPUT_SCALE {
+        highest_frequency *= lacunarity.abs();
+        count += 1;
+    }
+
+    count
+}
+
 pub fn apply_multifractal_conf<T: MultiFractal>(conf: &NoiseModuleConf, module: T) -> T {
     if let &NoiseModuleConf::MultiFractal {
         octaves,
@@ -126,8 +175,9 @@ pub fn apply_multifractal_conf<T: MultiFractal>(conf: &NoiseModuleConf, module:
         persistence,
     } = conf
     {
+        let frequency = clamp_frequency(frequency);
         module
-            .set_octaves(octaves as usize)
+            .set_octaves(limit_octaves(octaves, frequency, lacunarity))
             .set_frequency(frequency)
             .set_lacunarity(lacunarity)
             .set_persistence(persistence)
@@ -160,7 +210,7 @@ pub fn apply_worley_conf(conf: &NoiseModuleConf, module: Worley) -> Worley {
             module.enable_range(false)
         }.set_range_function(range_function.into())
         .set_displacement(displacement)
-        .set_frequency(worley_frequency)
+        .set_frequency(clamp_frequency(worley_frequency))
     } else {
         error(&format!("Attempted to configure module with worley settings but the settings aren't worley: {:?}", conf));
         module
@@ -1,9 +1,8 @@
 //! Defines a meta-format that can be used to represent composition trees in a serialize-able/dematerialize-able manner.
 
-use std::convert::TryFrom;
+use std::convert::{TryFrom, TryInto};
 
 use noise::*;
-use serde_json;
 
 use super::composition::CompositionScheme;
 use super::conf::{
@@ -11,22 +10,24 @@ use super::conf::{
     NoiseModuleConf,
 };
 use super::{
-    ComposedNoiseModule, CompositionTree, CompositionTreeNode, CompositionTreeNodeType, MasterConf,
+    error, ComposedNoiseModule, CompositionTree, CompositionTreeNode, CompositionTreeNodeType,
+    MasterConf,
 };
-use ir::IrNode;
+use dims::{LeafModule, Module3D, Module4D};
+use ir::{FromIrValue, IrNode};
 use transformations::InputTransformation;
-use util::{convert_setting, find_setting_by_name, Dim};
+use util::{build_child, convert_setting, find_setting_by_name, Dim};
 
 /// Defines a meta-representation of a `CompositionTree` designed to be passed into the backend from the JS frontend.  It
 /// contains all information necessary to construct a fully functional composition tree from scratch.
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct CompositionTreeDefinition {
     pub global_conf: MasterConf,
     pub root_node: CompositionTreeNodeDefinition,
 }
 
 /// Includes every possible type of noise module available through the tool.
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum NoiseModuleType {
     Composed,
     Fbm,
@@ -76,7 +77,22 @@ fn build_transformations(
 }
 
 impl NoiseModuleType {
-    pub fn construct_noise_fn(&self, confs: &[NoiseModuleConf]) -> Box<NoiseFn<Point3<f64>>> {
+    /// Every available noise module type
+    pub const ALL: [NoiseModuleType; 11] = [
+        NoiseModuleType::Composed,
+        NoiseModuleType::Fbm,
+        NoiseModuleType::Worley,
+        NoiseModuleType::OpenSimplex,
+        NoiseModuleType::Billow,
+        NoiseModuleType::HybridMulti,
+        NoiseModuleType::SuperSimplex,
+        NoiseModuleType::Value,
+        NoiseModuleType::RidgedMulti,
+        NoiseModuleType::BasicMulti,
+        NoiseModuleType::Constant,
+    ];
+
+    pub fn construct_noise_fn(&self, confs: &[NoiseModuleConf]) -> Box<LeafModule> {
         match self {
             &NoiseModuleType::Fbm => {
                 let configured_module = confs.iter().fold(Fbm::new(), |acc, conf| match conf {
@@ -87,7 +103,7 @@ impl NoiseModuleType {
                         acc
                     }
                 });
-                Box::new(configured_module) as Box<NoiseFn<Point3<f64>>>
+                Box::new(Module4D(configured_module)) as Box<LeafModule>
             }
             &NoiseModuleType::Worley => {
                 let configured_module = confs.iter().fold(Worley::new(), |acc, conf| match conf {
@@ -101,7 +117,7 @@ impl NoiseModuleType {
                         acc
                     }
                 });
-                Box::new(configured_module)
+                Box::new(Module4D(configured_module))
             }
             &NoiseModuleType::OpenSimplex => {
                 let configured_module =
@@ -117,7 +133,7 @@ impl NoiseModuleType {
                                 acc
                             }
                         });
-                Box::new(configured_module)
+                Box::new(Module4D(configured_module))
             }
             &NoiseModuleType::Billow => {
                 let configured_module = confs.iter().fold(Billow::new(), |acc, conf| match conf {
@@ -131,7 +147,7 @@ impl NoiseModuleType {
                         acc
                     }
                 });
-                Box::new(configured_module)
+                Box::new(Module4D(configured_module))
             }
             &NoiseModuleType::HybridMulti => {
                 let configured_module =
@@ -150,7 +166,7 @@ impl NoiseModuleType {
                                 acc
                             }
                         });
-                Box::new(configured_module)
+                Box::new(Module4D(configured_module))
             }
             &NoiseModuleType::SuperSimplex => {
                 let configured_module =
@@ -166,7 +182,7 @@ impl NoiseModuleType {
                                 acc
                             }
                         });
-                Box::new(configured_module)
+                Box::new(Module3D(configured_module))
             }
             &NoiseModuleType::Value => {
                 let configured_module = confs.iter().fold(Value::new(), |acc, conf| match conf {
@@ -176,7 +192,7 @@ impl NoiseModuleType {
                         acc
                     }
                 });
-                Box::new(configured_module)
+                Box::new(Module4D(configured_module))
             }
             &NoiseModuleType::RidgedMulti => {
                 let configured_module =
@@ -195,7 +211,7 @@ impl NoiseModuleType {
                                 acc
                             }
                         });
-                Box::new(configured_module)
+                Box::new(Module4D(configured_module))
             }
             &NoiseModuleType::BasicMulti => {
                 let configured_module =
@@ -214,7 +230,7 @@ impl NoiseModuleType {
                                 acc
                             }
                         });
-                Box::new(configured_module)
+                Box::new(Module4D(configured_module))
             }
             &NoiseModuleType::Constant => {
                 let configured_module =
@@ -230,17 +246,20 @@ impl NoiseModuleType {
                                 acc
                             }
                         });
-                Box::new(configured_module)
+                Box::new(Module4D(configured_module))
+            }
+            &NoiseModuleType::Composed => {
+                // `Composed` is only a placeholder for composed nodes, so leaf definitions with it can only come from
+                // malformed definitions.  Output nothing rather than taking down the whole tree.
+                error("Attempted to build leaf module with type Composed!  That's only a placeholder.");
+                Box::new(Module4D(Constant::new(0.)))
             }
-            &NoiseModuleType::Composed => panic!(
-                "Attempted to build leaf module with type Composed!  That's only a placeholder."
-            ),
         }
     }
 }
 
 /// This is the primary unit of the composition tree.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub enum CompositionTreeNodeDefinition {
     Leaf {
         module_type: NoiseModuleType,
@@ -254,6 +273,89 @@ pub enum CompositionTreeNodeDefinition {
     },
 }
 
+impl CompositionTreeNodeDefinition {
+    /// Traverses the definition, returning a reference to the node at the provided coordinates.
+    pub fn traverse(&self, coords: &[i32]) -> Result<&CompositionTreeNodeDefinition, String> {
+        let index = match coords.first() {
+            Some(&index) => index as usize,
+            None => return Ok(self),
+        };
+
+        match self {
+            &CompositionTreeNodeDefinition::Composed { ref children, .. } => {
+                match children.get(index) {
+                    Some(child) => child.traverse(&coords[1..]),
+                    None => Err(format!(
+                        "Attempted to access child of module at index {} but it only has {} children!",
+                        index,
+                        children.len()
+                    )),
+                }
+            }
+            &CompositionTreeNodeDefinition::Leaf { .. } => Err(format!(
+                "Attempted to access child of module at index {} but it is a leaf node!",
+                index
+            )),
+        }
+    }
+
+    /// Traverses the definition, returning a mutable reference to the node at the provided coordinates.
+    pub fn traverse_mut(
+        &mut self,
+        coords: &[i32],
+    ) -> Result<&mut CompositionTreeNodeDefinition, String> {
+        let index = match coords.first() {
+            Some(&index) => index as usize,
+            None => return Ok(self),
+        };
+
+        match self {
+            &mut CompositionTreeNodeDefinition::Composed {
+                ref mut children, ..
+            } => {
+                let child_count = children.len();
+                match children.get_mut(index) {
+                    Some(child) => child.traverse_mut(&coords[1..]),
+                    None => Err(format!(
+                        "Attempted to access child of module at index {} but it only has {} children!",
+                        index, child_count
+                    )),
+                }
+            }
+            &mut CompositionTreeNodeDefinition::Leaf { .. } => Err(format!(
+                "Attempted to access child of module at index {} but it is a leaf node!",
+                index
+            )),
+        }
+    }
+
+    pub fn transformations(&self) -> &[InputTransformationDefinition] {
+        match self {
+            &CompositionTreeNodeDefinition::Leaf {
+                ref transformations,
+                ..
+            }
+            | &CompositionTreeNodeDefinition::Composed {
+                ref transformations,
+                ..
+            } => transformations,
+        }
+    }
+
+    pub fn transformations_mut(&mut self) -> &mut Vec<InputTransformationDefinition> {
+        match self {
+            &mut CompositionTreeNodeDefinition::Leaf {
+                ref mut transformations,
+                ..
+            }
+            | &mut CompositionTreeNodeDefinition::Composed {
+                ref mut transformations,
+                ..
+            } => transformations,
+        }
+    }
+}
+
 impl Into<CompositionTreeNode> for CompositionTreeNodeDefinition {
     fn into(self) -> CompositionTreeNode {
         let (transformations, function) = match self {
@@ -305,14 +407,18 @@ impl Into<CompositionTreeNode> for CompositionTreeNodeDefinition {
 impl Into<CompositionTree> for CompositionTreeDefinition {
     /// Transforms the tree definition into a actual composition tree capable of producing values.
     fn into(self) -> CompositionTree {
-        CompositionTree {
+        let mut tree = CompositionTree {
             global_conf: self.global_conf,
             root_node: self.root_node.into(),
-        }
+            solo_path: None,
+        };
+        tree.update_normalization();
+
+        tree
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub enum InputTransformationDefinition {
     ZoomScale {
         speed: f64,
@@ -350,7 +456,8 @@ impl TryFrom<IrNode> for InputTransformationDefinition {
     type Error = String;
 
     fn try_from(node: IrNode) -> Result<Self, Self::Error> {
-        let transformation_type = find_setting_by_name("inputTransformationType", &node.settings)?;
+        let transformation_type: String =
+            convert_setting("inputTransformationType", &node.settings)?;
 
         let def: InputTransformationDefinition = match transformation_type.as_str() {
             "zoomScale" => InputTransformationDefinition::ZoomScale {
@@ -358,15 +465,13 @@ impl TryFrom<IrNode> for InputTransformationDefinition {
                 zoom: convert_setting("zoom", &node.settings)?,
             },
             "honf" => {
-                let def_string = find_setting_by_name("inputTransformationType", &node.settings)?;
-                let node_def: CompositionTreeNodeDefinition = match serde_json::from_str(
-                    &def_string,
-                ) {
-                    Ok(d) => d,
-                    Err(err) => {
-                        return Err(format!("Unable to build `CompositionTreeNodeDefinition` from supplied string: {:?}", err));
-                    }
-                };
+                // The inner noise function is supplied either as the value of the transformation node's `noiseModule`
+                // setting or as a `noiseModule` child of it
+                let node_def: CompositionTreeNodeDefinition =
+                    match find_setting_by_name("noiseModule", &node.settings) {
+                        Ok(value) => IrNode::from_ir_value(value)?.try_into()?,
+                        Err(_) => build_child(&node.children, "noiseModule")?,
+                    };
 
                 InputTransformationDefinition::HigherOrderNoiseModule {
                     node_def,
@@ -0,0 +1,185 @@
+//! Abstractions over the dimensionality of the coordinates that the composition tree can be evaluated with.  The tree
+//! is primarily evaluated in 3D with the third dimension as time, but tiling and looping require a fourth dimension
+//! and static images can be rendered more cheaply in 2D.
+
+use noise::{NoiseFn, Point2, Point3, Point4};
+
+use util::Dim;
+
+/// A leaf noise module of the composition tree.  Every module can be evaluated in 2D and 3D, but some of the
+/// underlying generators (such as `SuperSimplex`) don't have a 4D implementation.
+pub trait LeafModule {
+    fn get_2d(&self, coord: Point2<f64>) -> f64;
+
+    fn get_3d(&self, coord: Point3<f64>) -> f64;
+
+    /// Modules that don't support 4D evaluation fold the W coordinate into Z and return the 3D result.
+    fn get_4d(&self, coord: Point4<f64>) -> f64;
+
+    fn supports_4d(&self) -> bool;
+}
+
+/// Wraps a noise module that has implementations for 2D, 3D, and 4D points.
+pub struct Module4D<T>(pub T);
+
+impl<T> LeafModule for Module4D<T>
+where
+    T: NoiseFn<Point2<f64>> + NoiseFn<Point3<f64>> + NoiseFn<Point4<f64>>,
+{
+    fn get_2d(&self, coord: Point2<f64>) -> f64 {
+        self.0.get(coord)
+    }
+
+    fn get_3d(&self, coord: Point3<f64>) -> f64 {
+        self.0.get(coord)
+    }
+
+    fn get_4d(&self, coord: Point4<f64>) -> f64 {
+        self.0.get(coord)
+    }
+
+    fn supports_4d(&self) -> bool {
+        true
+    }
+}
+
+/// Wraps a noise module that can only be evaluated in 2D and 3D.
+pub struct Module3D<T>(pub T);
+
+impl<T: NoiseFn<Point2<f64>> + NoiseFn<Point3<f64>>> LeafModule for Module3D<T> {
+    fn get_2d(&self, coord: Point2<f64>) -> f64 {
+        self.0.get(coord)
+    }
+
+    fn get_3d(&self, coord: Point3<f64>) -> f64 {
+        self.0.get(coord)
+    }
+
+    fn get_4d(&self, coord: Point4<f64>) -> f64 {
+        self.0.get([coord[0], coord[1], coord[2] + coord[3]])
+    }
+
+    fn supports_4d(&self) -> bool {
+        false
+    }
+}
+
+/// A coordinate that the composition tree can be evaluated at.  Defines how input transformations are applied to
+/// points of each dimensionality and how leaf modules are evaluated for them.
+/// The largest magnitude of the coordinates that the tree's nodes are evaluated at.  Together with the limits on the
+/// factors that modules scale their inputs by internally (see `conf::MAX_INPUT_SCALE`), this keeps the lattice
+/// coordinates that the noise functions compute within the range of an `isize`.
+pub const MAX_COORD: f64 = (1u64 << 30) as f64;
+
+fn clamp_coord(val: f64) -> f64 {
+    val.max(-MAX_COORD).min(MAX_COORD)
+}
+
+pub trait NoisePoint: Copy {
+    /// Scales the spatial (X and Y) dimensions by `zoom` and all remaining dimensions by `speed`.
+    fn zoom_scale(self, zoom: f64, speed: f64) -> Self;
+
+    fn scale_all(self, scale: f64) -> Self;
+
+    /// Replaces the value of the supplied dimension.  Dimensions that the point doesn't have are left unchanged.
+    fn replace_dim(self, dim: Dim, val: f64) -> Self;
+
+    /// Clamps every dimension of the point to `MAX_COORD`.
+    fn clamp_coords(self) -> Self;
+
+    fn eval_leaf(module: &LeafModule, point: Self) -> f64;
+}
+
+impl NoisePoint for Point2<f64> {
+    fn zoom_scale(self, zoom: f64, _: f64) -> Self {
+        [self[0] * zoom, self[1] * zoom]
+    }
+
+    fn scale_all(self, scale: f64) -> Self {
+        [self[0] * scale, self[1] * scale]
+    }
+
+    fn replace_dim(self, dim: Dim, val: f64) -> Self {
+        match dim {
+            Dim::X => [val, self[1]],
+            Dim::Y => [self[0], val],
+            Dim::Z | Dim::W => self,
+        }
+    }
+
+    fn clamp_coords(self) -> Self {
+        [clamp_coord(self[0]), clamp_coord(self[1])]
+    }
+
+    fn eval_leaf(module: &LeafModule, point: Self) -> f64 {
+        module.get_2d(point)
+    }
+}
+
+impl NoisePoint for Point3<f64> {
+    fn zoom_scale(self, zoom: f64, speed: f64) -> Self {
+        [self[0] * zoom, self[1] * zoom, self[2] * speed]
+    }
+
+    fn scale_all(self, scale: f64) -> Self {
+        [self[0] * scale, self[1] * scale, self[2] * scale]
+    }
+
+    fn replace_dim(self, dim: Dim, val: f64) -> Self {
+        match dim {
+            Dim::X => [val, self[1], self[2]],
+            Dim::Y => [self[0], val, self[2]],
+            Dim::Z => [self[0], self[1], val],
+            Dim::W => self,
+        }
+    }
+
+    fn clamp_coords(self) -> Self {
+        [
+            clamp_coord(self[0]),
+            clamp_coord(self[1]),
+            clamp_coord(self[2]),
+        ]
+    }
+
+    fn eval_leaf(module: &LeafModule, point: Self) -> f64 {
+        module.get_3d(point)
+    }
+}
+
+impl NoisePoint for Point4<f64> {
+    fn zoom_scale(self, zoom: f64, speed: f64) -> Self {
+        [self[0] * zoom, self[1] * zoom, self[2] * speed, self[3] * speed]
+    }
+
+    fn scale_all(self, scale: f64) -> Self {
+        [
+            self[0] * scale,
+            self[1] * scale,
+            self[2] * scale,
+            self[3] * scale,
+        ]
+    }
+
+    fn replace_dim(self, dim: Dim, val: f64) -> Self {
+        match dim {
+            Dim::X => [val, self[1], self[2], self[3]],
+            Dim::Y => [self[0], val, self[2], self[3]],
+            Dim::Z => [self[0], self[1], val, self[3]],
+            Dim::W => [self[0], self[1], self[2], val],
+        }
+    }
+
+    fn clamp_coords(self) -> Self {
+        [
+            clamp_coord(self[0]),
+            clamp_coord(self[1]),
+            clamp_coord(self[2]),
+            clamp_coord(self[3]),
+        ]
+    }
+
+    fn eval_leaf(module: &LeafModule, point: Self) -> f64 {
+        module.get_4d(point)
+    }
+}
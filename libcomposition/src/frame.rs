@@ -0,0 +1,197 @@
+//! Buffer of RGBA pixel data owned by an engine that frames are rendered into directly.  The JS side reads it straight
+//! out of the engine's memory and only redraws the rows that changed since the previous frame.
+//!
+//! Frames are rendered progressively: after the tree or viewport changes, the first frame is rendered at 1/8 of the
+//! full resolution and upscaled, and each following frame doubles the resolution until the full resolution, or the
+//! finest resolution that fits into the frame time budget, is reached.  This keeps the canvas responsive while editing
+//! trees that are expensive to evaluate.
+
+use std::mem;
+
+use noise::NoiseFn;
+
+use relief::render_relief;
+use CompositionTree;
+
+/// The number of pixels along each side of the blocks that the first frame of a progressive render is rendered in
+pub const COARSEST_SCALE: usize = 8;
+
+/// Holds the pixel data of the most recently rendered frame along with the rows that changed when it was rendered.
+pub struct FrameBuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+    /// Scratch space that each row of blocks is rendered into before it is upscaled
+    coarse_row: Vec<u8>,
+    /// Scratch space that each row is upscaled into before it is compared with the previous frame
+    row: Vec<u8>,
+    /// The size of the blocks that the next frame will be rendered in
+    scale: usize,
+    /// The size of the blocks that the current contents of the buffer were rendered in, or 0 if nothing was rendered
+    rendered_scale: usize,
+    /// The size of the blocks that frames are refined down to, which is raised to stay within the frame time budget
+    min_scale: usize,
+    /// Set when a new progressive render was started since the most recent render, meaning that the contents of the
+    /// buffer are out of date
+    restarted: bool,
+    /// Ranges of rows that changed during the most recent render, flattened into `[start, end)` pairs
+    changed_rows: Vec<u32>,
+}
+
+impl FrameBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        FrameBuffer {
+            width,
+            height,
+            pixels: vec![0; width * height * 4],
+            coarse_row: Vec::with_capacity(width * 4),
+            row: Vec::with_capacity(width * 4),
+            scale: COARSEST_SCALE,
+            rendered_scale: 0,
+            min_scale: 1,
+            restarted: false,
+            changed_rows: Vec::new(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Resizes the buffer, clearing its contents.  The entire image is reported as changed by the next render, which
+    /// starts a new progressive render.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        let min_scale = self.min_scale;
+        *self = FrameBuffer::new(width, height);
+        self.min_scale = min_scale;
+    }
+
+    /// The RGBA pixel data of the most recently rendered frame in row-major order
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Ranges of rows that changed during the most recent render, flattened into `[start, end)` pairs.  Adjacent rows
+    /// are merged into a single range.
+    pub fn changed_rows(&self) -> &[u32] {
+        &self.changed_rows
+    }
+
+    /// The size of the blocks that the current contents of the buffer were rendered in; 1 once the full resolution has
+    /// been reached.  Returns 0 if nothing has been rendered yet.
+    pub fn rendered_scale(&self) -> usize {
+        self.rendered_scale
+    }
+
+    /// Returns `true` if the buffer is out of date or hasn't been refined down to the minimum scale yet, meaning that
+    /// the next render changes it even if the point in time stays the same.
+    pub fn is_refining(&self) -> bool {
+        self.restarted || self.rendered_scale == 0 || self.rendered_scale > self.min_scale
+    }
+
+    /// Sets the size of the blocks that frames are refined down to; 1 refines them to full resolution.  If it is
+    /// coarser than the current resolution, the next frame is rendered at the new minimum scale.
+    pub fn set_min_scale(&mut self, min_scale: usize) {
+        self.min_scale = min_scale.max(1).min(COARSEST_SCALE);
+    }
+
+    /// Starts a new progressive render, rendering the next frame at the coarsest resolution.  This should be called
+    /// whenever the tree or viewport changes.
+    pub fn restart(&mut self) {
+        self.scale = COARSEST_SCALE;
+        self.restarted = true;
+    }
+
+    /// Renders the composition tree at the supplied point in time into the buffer at the current resolution of the
+    /// progressive render, recording which rows changed.  The next render doubles the resolution until the minimum
+    /// scale is reached.
+    pub fn render(&mut self, tree: &CompositionTree, time: f64) {
+        self.changed_rows.clear();
+        self.restarted = false;
+        if self.width == 0 || self.height == 0 {
+            return;
+        }
+
+        let scale = self.scale.max(self.min_scale);
+        self.scale = scale;
+        let coarse_width = (self.width + scale - 1) / scale;
+        let coarse_height = (self.height + scale - 1) / scale;
+        // each block is colored with the value at its top left corner
+        let point = |x: usize, y: usize| [(x * scale) as f64, (y * scale) as f64, time];
+        let mut coarse_row = mem::replace(&mut self.coarse_row, Vec::new());
+        let mut row = mem::replace(&mut self.row, Vec::new());
+
+        // relief shading depends on the values of neighboring pixels, so the whole frame has to be evaluated first
+        if let Some(relief_mode) = tree.global_conf.relief_mode {
+            let mut values = Vec::with_capacity(coarse_width * coarse_height);
+            for y in 0..coarse_height {
+                for x in 0..coarse_width {
+                    values.push(tree.get(point(x, y)));
+                }
+            }
+
+            let pixels = render_relief(&values, coarse_width, coarse_height, relief_mode);
+            for (y, pixels_row) in pixels.chunks(coarse_width * 4).enumerate() {
+                self.update_block_row(y, pixels_row, &mut row);
+            }
+        } else {
+            let color_fn = tree.global_conf.color_function;
+            for y in 0..coarse_height {
+                coarse_row.clear();
+                for x in 0..coarse_width {
+                    let val = tree.get(point(x, y));
+                    coarse_row.extend_from_slice(&color_fn.colorize(val as f32));
+                }
+                self.update_block_row(y, &coarse_row, &mut row);
+            }
+        }
+
+        self.coarse_row = coarse_row;
+        self.row = row;
+        self.rendered_scale = scale;
+        self.scale = (scale / 2).max(self.min_scale);
+    }
+
+    /// Upscales a row of blocks rendered at the current scale into the rows of pixels that it covers.  `row` is used as
+    /// scratch space.
+    fn update_block_row(&mut self, block_y: usize, coarse_row: &[u8], row: &mut Vec<u8>) {
+        let scale = self.scale;
+        if scale == 1 {
+            return self.update_row(block_y, coarse_row);
+        }
+
+        row.clear();
+        for x in 0..self.width {
+            let offset = (x / scale) * 4;
+            row.extend_from_slice(&coarse_row[offset..offset + 4]);
+        }
+
+        let end = ((block_y + 1) * scale).min(self.height);
+        for y in block_y * scale..end {
+            self.update_row(y, row);
+        }
+    }
+
+    /// Replaces the pixels of row `y` with the supplied ones, recording the row as changed if they differ.
+    pub fn update_row(&mut self, y: usize, row: &[u8]) {
+        let row_len = self.width * 4;
+        let dst = &mut self.pixels[y * row_len..(y + 1) * row_len];
+        if dst == row {
+            return;
+        }
+        dst.copy_from_slice(row);
+
+        let y = y as u32;
+        let len = self.changed_rows.len();
+        if len > 0 && self.changed_rows[len - 1] == y {
+            self.changed_rows[len - 1] = y + 1;
+        } else {
+            self.changed_rows.push(y);
+            self.changed_rows.push(y + 1);
+        }
+    }
+}
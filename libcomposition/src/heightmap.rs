@@ -0,0 +1,273 @@
+//! Exports the raw values produced by a composition tree as heightmaps.  Unlike the color functions, which quantize
+//! values into 8-bit colors, these formats preserve enough precision to be used as terrain data.
+
+use std::f64;
+use std::io::Write;
+use std::str::FromStr;
+
+use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
+use png::{self, HasParameters};
+
+/// The file formats that heightmaps can be exported to.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum HeightmapFormat {
+    /// 16-bit grayscale PNG
+    Png16,
+    /// Headerless array of little-endian `f32`s in row-major order
+    RawF32,
+    /// Headerless array of little-endian `f64`s in row-major order
+    RawF64,
+    /// Single-channel Portable Float Map
+    Pfm,
+    /// Uncompressed single-channel OpenEXR image with 32-bit float samples
+    Exr,
+}
+
+impl FromStr for HeightmapFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "png16" | "png" => Ok(HeightmapFormat::Png16),
+            "f32" | "raw" => Ok(HeightmapFormat::RawF32),
+            "f64" => Ok(HeightmapFormat::RawF64),
+            "pfm" => Ok(HeightmapFormat::Pfm),
+            "exr" => Ok(HeightmapFormat::Exr),
+            _ => Err(format!(
+                "Unable to convert \"{}\" into `HeightmapFormat`!",
+                s
+            )),
+        }
+    }
+}
+
+impl HeightmapFormat {
+    /// Returns the file extension typically used for files of this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            &HeightmapFormat::Png16 => "png",
+            &HeightmapFormat::RawF32 | &HeightmapFormat::RawF64 => "raw",
+            &HeightmapFormat::Pfm => "pfm",
+            &HeightmapFormat::Exr => "exr",
+        }
+    }
+}
+
+/// Determines how values are remapped before being written to the output.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ValueRemap {
+    /// Values are written as-is.  Since integer formats can't represent arbitrary values, PNGs assume an input
+    /// range of [-1, 1] in this case.
+    None,
+    /// Values are normalized to [0, 1] using the minimum and maximum observed values.
+    Auto,
+    /// Values are normalized so that `min` maps to 0 and `max` maps to 1.
+    Fixed { min: f64, max: f64 },
+}
+
+impl FromStr for ValueRemap {
+    type Err = String;
+
+    /// Parses either `none`, `auto`, or a fixed range in the format `min:max`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(ValueRemap::None),
+            "auto" => Ok(ValueRemap::Auto),
+            _ => {
+                let mut split = s.splitn(2, ':');
+                let bounds = (
+                    split.next().and_then(|min| min.parse().ok()),
+                    split.next().and_then(|max| max.parse().ok()),
+                );
+                match bounds {
+                    (Some(min), Some(max)) => Ok(ValueRemap::Fixed { min, max }),
+                    _ => Err(format!(
+                        "Unable to convert \"{}\" into `ValueRemap`; expected `none`, `auto`, or `min:max`.",
+                        s
+                    )),
+                }
+            }
+        }
+    }
+}
+
+/// Returns the minimum and maximum of the supplied values, ignoring `NaN`s.
+pub fn value_bounds(values: &[f64]) -> (f64, f64) {
+    values
+        .iter()
+        .filter(|val| !val.is_nan())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &val| {
+            (min.min(val), max.max(val))
+        })
+}
+
+fn normalize(values: &[f64], min: f64, max: f64) -> Vec<f64> {
+    let range = max - min;
+    if range == 0. || !range.is_finite() {
+        return vec![0.; values.len()];
+    }
+
+    values.iter().map(|val| (val - min) / range).collect()
+}
+
+/// Applies the supplied remapping to the values.
+pub fn remap_values(values: &[f64], remap: ValueRemap) -> Vec<f64> {
+    match remap {
+        ValueRemap::None => values.to_vec(),
+        ValueRemap::Auto => {
+            let (min, max) = value_bounds(values);
+            normalize(values, min, max)
+        }
+        ValueRemap::Fixed { min, max } => normalize(values, min, max),
+    }
+}
+
+fn write_png16<W: Write>(writer: W, width: usize, height: usize, values: &[f64]) -> Result<(), String> {
+    let mut data: Vec<u8> = Vec::with_capacity(values.len() * 2);
+    for &val in values {
+        let sample = (val.max(0.).min(1.) * 65535.).round() as u16;
+        data.write_u16::<BigEndian>(sample).unwrap();
+    }
+
+    let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+    encoder
+        .set(png::ColorType::Grayscale)
+        .set(png::BitDepth::Sixteen);
+    let mut png_writer = encoder
+        .write_header()
+        .map_err(|err| format!("Unable to write PNG header: {}", err))?;
+
+    png_writer
+        .write_image_data(&data)
+        .map_err(|err| format!("Unable to write PNG image data: {}", err))
+}
+
+fn write_raw_f32<W: Write>(writer: &mut W, values: &[f64]) -> Result<(), String> {
+    for &val in values {
+        writer
+            .write_f32::<LittleEndian>(val as f32)
+            .map_err(|err| format!("Unable to write heightmap data: {}", err))?;
+    }
+
+    Ok(())
+}
+
+fn write_raw_f64<W: Write>(writer: &mut W, values: &[f64]) -> Result<(), String> {
+    for &val in values {
+        writer
+            .write_f64::<LittleEndian>(val)
+            .map_err(|err| format!("Unable to write heightmap data: {}", err))?;
+    }
+
+    Ok(())
+}
+
+/// PFM images are stored bottom-to-top; a negative scale factor in the header indicates little-endian samples.
+fn write_pfm<W: Write>(writer: &mut W, width: usize, height: usize, values: &[f64]) -> Result<(), String> {
+    write!(writer, "Pf\n{} {}\n-1.0\n", width, height)
+        .map_err(|err| format!("Unable to write PFM header: {}", err))?;
+
+    for row in values.chunks(width).rev() {
+        write_raw_f32(writer, row)?;
+    }
+
+    Ok(())
+}
+
+fn write_exr_attribute(buf: &mut Vec<u8>, name: &str, attr_type: &str, value: &[u8]) {
+    buf.extend_from_slice(name.as_bytes());
+    buf.push(0);
+    buf.extend_from_slice(attr_type.as_bytes());
+    buf.push(0);
+    buf.write_i32::<LittleEndian>(value.len() as i32).unwrap();
+    buf.extend_from_slice(value);
+}
+
+/// Writes a single-part scanline OpenEXR image with a single `Y` channel of 32-bit floats and no compression.
+fn write_exr<W: Write>(writer: &mut W, width: usize, height: usize, values: &[f64]) -> Result<(), String> {
+    let mut buf: Vec<u8> = Vec::new();
+    // magic number followed by version 2 with no flags set
+    buf.extend_from_slice(&[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+
+    // channel list: name, pixel type (2 = FLOAT), pLinear + reserved bytes, and x/y sampling
+    let mut channels: Vec<u8> = b"Y\0".to_vec();
+    channels.write_i32::<LittleEndian>(2).unwrap();
+    channels.extend_from_slice(&[0, 0, 0, 0]);
+    channels.write_i32::<LittleEndian>(1).unwrap();
+    channels.write_i32::<LittleEndian>(1).unwrap();
+    channels.push(0);
+    write_exr_attribute(&mut buf, "channels", "chlist", &channels);
+
+    write_exr_attribute(&mut buf, "compression", "compression", &[0]);
+
+    let mut window: Vec<u8> = Vec::with_capacity(16);
+    for &coord in &[0, 0, width as i32 - 1, height as i32 - 1] {
+        window.write_i32::<LittleEndian>(coord).unwrap();
+    }
+    write_exr_attribute(&mut buf, "dataWindow", "box2i", &window);
+    write_exr_attribute(&mut buf, "displayWindow", "box2i", &window);
+    write_exr_attribute(&mut buf, "lineOrder", "lineOrder", &[0]);
+
+    let mut one: Vec<u8> = Vec::with_capacity(4);
+    one.write_f32::<LittleEndian>(1.).unwrap();
+    write_exr_attribute(&mut buf, "pixelAspectRatio", "float", &one);
+    write_exr_attribute(&mut buf, "screenWindowCenter", "v2f", &[0; 8]);
+    write_exr_attribute(&mut buf, "screenWindowWidth", "float", &one);
+    // end of header
+    buf.push(0);
+
+    // Without compression, every chunk contains a single scanline.  The offset table contains the absolute position
+    // of each of them in the file.
+    let line_size = width * 4;
+    let chunk_size = 8 + line_size;
+    let table_end = buf.len() + height * 8;
+    for y in 0..height {
+        buf.write_u64::<LittleEndian>((table_end + y * chunk_size) as u64)
+            .unwrap();
+    }
+
+    for (y, row) in values.chunks(width).enumerate() {
+        buf.write_i32::<LittleEndian>(y as i32).unwrap();
+        buf.write_i32::<LittleEndian>(line_size as i32).unwrap();
+        write_raw_f32(&mut buf, row)?;
+    }
+
+    writer
+        .write_all(&buf)
+        .map_err(|err| format!("Unable to write EXR image: {}", err))
+}
+
+/// Remaps the supplied values and encodes them as a heightmap in the selected format, writing the result into
+/// `writer`.  Values should be in row-major order, as returned by `render::render_values`.
+pub fn write_heightmap<W: Write>(
+    mut writer: W,
+    format: HeightmapFormat,
+    width: usize,
+    height: usize,
+    values: &[f64],
+    remap: ValueRemap,
+) -> Result<(), String> {
+    if values.len() != width * height || width == 0 {
+        return Err(format!(
+            "Supplied {} values but a {}x{} heightmap requires {}!",
+            values.len(),
+            width,
+            height,
+            width * height
+        ));
+    }
+
+    let remap = match (format, remap) {
+        (HeightmapFormat::Png16, ValueRemap::None) => ValueRemap::Fixed { min: -1., max: 1. },
+        (_, remap) => remap,
+    };
+    let remapped = remap_values(values, remap);
+
+    match format {
+        HeightmapFormat::Png16 => write_png16(writer, width, height, &remapped),
+        HeightmapFormat::RawF32 => write_raw_f32(&mut writer, &remapped),
+        HeightmapFormat::RawF64 => write_raw_f64(&mut writer, &remapped),
+        HeightmapFormat::Pfm => write_pfm(&mut writer, width, height, &remapped),
+        HeightmapFormat::Exr => write_exr(&mut writer, width, height, &remapped),
+    }
+}
@@ -0,0 +1,339 @@
+//! Records the edits made to a composition tree so that they can be undone and redone.  Since built noise modules can't
+//! be converted back into definitions, the edit log keeps a definition of the tree in sync with it.  Edits are applied
+//! to that definition and the affected subtree of the built tree is then rebuilt from it.
+
+use std::mem;
+
+use composition::CompositionScheme;
+use definition::{CompositionTreeNodeDefinition, InputTransformationDefinition};
+use {CompositionTree, CompositionTreeNode};
+
+/// The number of edits that are kept in the history by default
+pub const DEFAULT_HISTORY_LENGTH: usize = 256;
+
+/// A single reversible change to a composition tree.  Nodes are addressed by their coordinates in the tree, the same
+/// way as they are when modifying the built tree directly.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum TreeEdit {
+    AddNode {
+        parent: Vec<i32>,
+        index: usize,
+        node: CompositionTreeNodeDefinition,
+    },
+    DeleteNode {
+        parent: Vec<i32>,
+        index: usize,
+    },
+    ReplaceNode {
+        parent: Vec<i32>,
+        index: usize,
+        node: CompositionTreeNodeDefinition,
+    },
+    SetCompositionScheme {
+        node: Vec<i32>,
+        scheme: CompositionScheme,
+    },
+    AddInputTransformation {
+        node: Vec<i32>,
+        index: usize,
+        transformation: InputTransformationDefinition,
+    },
+    DeleteInputTransformation {
+        node: Vec<i32>,
+        index: usize,
+    },
+    ReplaceInputTransformation {
+        node: Vec<i32>,
+        index: usize,
+        transformation: InputTransformationDefinition,
+    },
+    /// Several edits that are applied in order as a single unit.  If any of them fails, none of them are applied.
+    Batch {
+        edits: Vec<TreeEdit>,
+    },
+}
+
+impl TreeEdit {
+    /// Returns the coordinates of the node whose subtree has to be rebuilt after this edit is applied.  For batches,
+    /// this is the deepest node that contains the targets of all of the contained edits.
+    pub fn target(&self) -> Vec<i32> {
+        match self {
+            &TreeEdit::AddNode { ref parent, .. }
+            | &TreeEdit::DeleteNode { ref parent, .. }
+            | &TreeEdit::ReplaceNode { ref parent, .. } => parent.clone(),
+            &TreeEdit::SetCompositionScheme { ref node, .. }
+            | &TreeEdit::AddInputTransformation { ref node, .. }
+            | &TreeEdit::DeleteInputTransformation { ref node, .. }
+            | &TreeEdit::ReplaceInputTransformation { ref node, .. } => node.clone(),
+            &TreeEdit::Batch { ref edits } => {
+                let mut targets = edits.iter().map(TreeEdit::target);
+                let first = targets.next().unwrap_or_else(Vec::new);
+                targets.fold(first, |mut common, target| {
+                    let shared = common
+                        .iter()
+                        .zip(target.iter())
+                        .take_while(|&(a, b)| a == b)
+                        .count();
+                    common.truncate(shared);
+                    common
+                })
+            }
+        }
+    }
+
+    /// Applies the edit to the supplied definition, returning the edit that reverses it.  The definition is left
+    /// unchanged if there's an error.
+    pub fn apply_to_definition(
+        self,
+        root: &mut CompositionTreeNodeDefinition,
+    ) -> Result<TreeEdit, String> {
+        match self {
+            TreeEdit::AddNode {
+                parent,
+                index,
+                node,
+            } => {
+                let children = children_mut(root.traverse_mut(&parent)?)?;
+                if index > children.len() {
+                    return Err(format!(
+                        "Attempted to add node at index {} but the parent only has {} children!",
+                        index,
+                        children.len()
+                    ));
+                }
+
+                children.insert(index, node);
+                Ok(TreeEdit::DeleteNode { parent, index })
+            }
+            TreeEdit::DeleteNode { parent, index } => {
+                let children = children_mut(root.traverse_mut(&parent)?)?;
+                check_index(index, children.len())?;
+
+                let node = children.remove(index);
+                Ok(TreeEdit::AddNode {
+                    parent,
+                    index,
+                    node,
+                })
+            }
+            TreeEdit::ReplaceNode {
+                parent,
+                index,
+                node,
+            } => {
+                let children = children_mut(root.traverse_mut(&parent)?)?;
+                check_index(index, children.len())?;
+
+                let node = mem::replace(&mut children[index], node);
+                Ok(TreeEdit::ReplaceNode {
+                    parent,
+                    index,
+                    node,
+                })
+            }
+            TreeEdit::SetCompositionScheme { node, scheme } => match root.traverse_mut(&node)? {
+                &mut CompositionTreeNodeDefinition::Composed {
+                    scheme: ref mut old_scheme,
+                    ..
+                } => {
+                    let scheme = mem::replace(old_scheme, scheme);
+                    Ok(TreeEdit::SetCompositionScheme { node, scheme })
+                }
+                &mut CompositionTreeNodeDefinition::Leaf { .. } => Err(String::from(
+                    "Attempted to set composition scheme of a leaf node!",
+                )),
+            },
+            TreeEdit::AddInputTransformation {
+                node,
+                index,
+                transformation,
+            } => {
+                let transformations = root.traverse_mut(&node)?.transformations_mut();
+                if index > transformations.len() {
+                    return Err(format!(
+                        "Attempted to add input transformation at index {} but there are only {} transformations!",
+                        index,
+                        transformations.len()
+                    ));
+                }
+
+                transformations.insert(index, transformation);
+                Ok(TreeEdit::DeleteInputTransformation { node, index })
+            }
+            TreeEdit::DeleteInputTransformation { node, index } => {
+                let transformations = root.traverse_mut(&node)?.transformations_mut();
+                check_index(index, transformations.len())?;
+
+                let transformation = transformations.remove(index);
+                Ok(TreeEdit::AddInputTransformation {
+                    node,
+                    index,
+                    transformation,
+                })
+            }
+            TreeEdit::ReplaceInputTransformation {
+                node,
+                index,
+                transformation,
+            } => {
+                let transformations = root.traverse_mut(&node)?.transformations_mut();
+                check_index(index, transformations.len())?;
+
+                let transformation = mem::replace(&mut transformations[index], transformation);
+                Ok(TreeEdit::ReplaceInputTransformation {
+                    node,
+                    index,
+                    transformation,
+                })
+            }
+            TreeEdit::Batch { edits } => {
+                // apply all edits to a copy so that the original is untouched if any of them fail
+                let mut edited = root.clone();
+                let mut inverses = Vec::with_capacity(edits.len());
+                for (i, edit) in edits.into_iter().enumerate() {
+                    let inverse = edit.apply_to_definition(&mut edited).map_err(|err| {
+                        format!("Error while applying edit {} of batch: {}", i, err)
+                    })?;
+                    inverses.push(inverse);
+                }
+
+                *root = edited;
+                // the inverse edits have to be applied in reverse order to undo the batch
+                inverses.reverse();
+                Ok(TreeEdit::Batch { edits: inverses })
+            }
+        }
+    }
+}
+
+fn children_mut(
+    node: &mut CompositionTreeNodeDefinition,
+) -> Result<&mut Vec<CompositionTreeNodeDefinition>, String> {
+    match node {
+        &mut CompositionTreeNodeDefinition::Composed {
+            ref mut children, ..
+        } => Ok(children),
+        &mut CompositionTreeNodeDefinition::Leaf { .. } => Err(String::from(
+            "Attempted to access the children of a leaf node!",
+        )),
+    }
+}
+
+fn check_index(index: usize, len: usize) -> Result<(), String> {
+    if index < len {
+        Ok(())
+    } else {
+        Err(format!(
+            "Attempted to access element at index {} but there are only {}!",
+            index, len
+        ))
+    }
+}
+
+/// Applies edits to a composition tree, keeping track of them so that they can be undone and redone.
+pub struct EditLog {
+    /// Definition of the root node of the tree, kept in sync with the built tree
+    root: CompositionTreeNodeDefinition,
+    /// Edits that reverse the most recently applied edits, with the most recent one last
+    undo_stack: Vec<TreeEdit>,
+    /// Edits that re-apply the most recently undone edits, with the most recently undone one last
+    redo_stack: Vec<TreeEdit>,
+    max_length: usize,
+}
+
+impl EditLog {
+    /// Creates a new edit log for a tree built from the supplied definition.
+    pub fn new(root: CompositionTreeNodeDefinition) -> Self {
+        EditLog {
+            root,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            max_length: DEFAULT_HISTORY_LENGTH,
+        }
+    }
+
+    /// Returns the definition of the root node of the tree in its current state.
+    pub fn definition(&self) -> &CompositionTreeNodeDefinition {
+        &self.root
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Applies the edit to the definition and rebuilds the affected subtree of the tree, returning the edit that
+    /// reverses it.
+    fn apply_inner(
+        &mut self,
+        tree: &mut CompositionTree,
+        edit: TreeEdit,
+    ) -> Result<TreeEdit, String> {
+        let target = edit.target();
+        // make sure that the target exists in the built tree before touching the definition
+        tree.root_node.traverse_mut(&target)?;
+        let inverse = edit.apply_to_definition(&mut self.root)?;
+
+        let rebuilt: CompositionTreeNode = self.root.traverse(&target)?.clone().into();
+        *tree.root_node.traverse_mut(&target)? = rebuilt;
+        tree.update_normalization();
+
+        Ok(inverse)
+    }
+
+    /// Applies the edit to the tree and records it so that it can be undone.  Clears any edits that could be redone.
+    pub fn apply(&mut self, tree: &mut CompositionTree, edit: TreeEdit) -> Result<(), String> {
+        let inverse = self.apply_inner(tree, edit)?;
+
+        self.undo_stack.push(inverse);
+        if self.undo_stack.len() > self.max_length {
+            self.undo_stack.remove(0);
+        }
+        self.redo_stack.clear();
+
+        Ok(())
+    }
+
+    /// Reverses the most recently applied edit, returning the edit that was applied to the tree in order to do so or
+    /// `None` if there is nothing to undo.
+    pub fn undo(&mut self, tree: &mut CompositionTree) -> Result<Option<TreeEdit>, String> {
+        let edit = match self.undo_stack.pop() {
+            Some(edit) => edit,
+            None => return Ok(None),
+        };
+
+        match self.apply_inner(tree, edit.clone()) {
+            Ok(inverse) => {
+                self.redo_stack.push(inverse);
+                Ok(Some(edit))
+            }
+            Err(err) => {
+                self.undo_stack.push(edit);
+                Err(err)
+            }
+        }
+    }
+
+    /// Re-applies the most recently undone edit, returning the edit that was applied to the tree or `None` if there is
+    /// nothing to redo.
+    pub fn redo(&mut self, tree: &mut CompositionTree) -> Result<Option<TreeEdit>, String> {
+        let edit = match self.redo_stack.pop() {
+            Some(edit) => edit,
+            None => return Ok(None),
+        };
+
+        match self.apply_inner(tree, edit.clone()) {
+            Ok(inverse) => {
+                self.undo_stack.push(inverse);
+                Ok(Some(edit))
+            }
+            Err(err) => {
+                self.redo_stack.push(edit);
+                Err(err)
+            }
+        }
+    }
+}
@@ -6,7 +6,7 @@ use super::{
 };
 use definition::NoiseModuleType;
 
-fn create_initial_tree_definition() -> CompositionTreeDefinition {
+pub fn create_initial_tree_definition() -> CompositionTreeDefinition {
     CompositionTreeDefinition {
         global_conf: MasterConf::default(),
         root_node: CompositionTreeNodeDefinition::Composed {
@@ -2,20 +2,209 @@
 //! composition tree definitions used by the backend.
 
 use std::convert::{TryFrom, TryInto};
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
 
 use itertools::Itertools;
+use serde_json;
 
+use color_schemes::ColorFunction;
 use composition::CompositionScheme;
-use definition::{CompositionTreeNodeDefinition, InputTransformationDefinition, NoiseModuleType};
-use util::{build_child, build_noise_module_settings, find_setting_by_name};
+use conf::{InteropRangeFunction, NoiseModuleConf};
+use definition::{
+    CompositionTreeDefinition, CompositionTreeNodeDefinition, InputTransformationDefinition,
+    NoiseModuleType,
+};
+use relief::ReliefMode;
+use util::{build_child, build_noise_module_settings, convert_setting, Dim};
+use MasterConf;
 
-#[derive(Clone, Serialize, Deserialize)]
+/// The value of a setting.  Values are normally supplied with their natural JSON types, but definitions produced by
+/// older versions of the frontend encode every value as a string, so those are accepted everywhere as well.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum IrValue {
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<IrValue>),
+    Node(Box<IrNode>),
+}
+
+impl Display for IrValue {
+    /// Formats the value as JSON.
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let json = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+        write!(f, "{}", json)
+    }
+}
+
+impl From<bool> for IrValue {
+    fn from(val: bool) -> Self {
+        IrValue::Bool(val)
+    }
+}
+
+impl From<f64> for IrValue {
+    fn from(val: f64) -> Self {
+        IrValue::Number(val)
+    }
+}
+
+impl From<u32> for IrValue {
+    fn from(val: u32) -> Self {
+        IrValue::Number(val as f64)
+    }
+}
+
+impl From<String> for IrValue {
+    fn from(val: String) -> Self {
+        IrValue::String(val)
+    }
+}
+
+impl<'a> From<&'a str> for IrValue {
+    fn from(val: &str) -> Self {
+        IrValue::String(val.into())
+    }
+}
+
+impl From<Vec<f64>> for IrValue {
+    fn from(vals: Vec<f64>) -> Self {
+        IrValue::Array(vals.into_iter().map(IrValue::Number).collect())
+    }
+}
+
+impl From<IrNode> for IrValue {
+    fn from(node: IrNode) -> Self {
+        IrValue::Node(Box::new(node))
+    }
+}
+
+/// Conversion out of the value of a setting.  Values with the JSON type corresponding to `Self` are accepted along
+/// with the legacy string encoding of them.
+pub trait FromIrValue: Sized {
+    fn from_ir_value(value: &IrValue) -> Result<Self, String>;
+}
+
+/// Parses a value in the legacy string encoding, returning an error naming what was expected for other values.
+fn parse_string_value<T>(value: &IrValue, expected: &str) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match value {
+        &IrValue::String(ref s) => s
+            .parse()
+            .map_err(|err| format!("Unable to convert value from string \"{}\": {}", s, err)),
+        _ => Err(format!("Expected {} but found {}", expected, value)),
+    }
+}
+
+impl FromIrValue for f64 {
+    fn from_ir_value(value: &IrValue) -> Result<Self, String> {
+        match value {
+            &IrValue::Number(val) => Ok(val),
+            _ => parse_string_value(value, "a number"),
+        }
+    }
+}
+
+impl FromIrValue for f32 {
+    fn from_ir_value(value: &IrValue) -> Result<Self, String> {
+        f64::from_ir_value(value).map(|val| val as f32)
+    }
+}
+
+impl FromIrValue for u32 {
+    fn from_ir_value(value: &IrValue) -> Result<Self, String> {
+        match value {
+            &IrValue::Number(val)
+                if val >= 0. && val <= u32::max_value() as f64 && val.fract() == 0. =>
+            {
+                Ok(val as u32)
+            }
+            _ => parse_string_value(value, "a non-negative integer"),
+        }
+    }
+}
+
+impl FromIrValue for bool {
+    fn from_ir_value(value: &IrValue) -> Result<Self, String> {
+        match value {
+            &IrValue::Bool(val) => Ok(val),
+            _ => parse_string_value(value, "a boolean"),
+        }
+    }
+}
+
+impl FromIrValue for String {
+    fn from_ir_value(value: &IrValue) -> Result<Self, String> {
+        match value {
+            &IrValue::String(ref val) => Ok(val.clone()),
+            _ => Err(format!("Expected a string but found {}", value)),
+        }
+    }
+}
+
+impl FromIrValue for Vec<f64> {
+    fn from_ir_value(value: &IrValue) -> Result<Self, String> {
+        match value {
+            &IrValue::Array(ref vals) => vals.iter().map(f64::from_ir_value).collect(),
+            // legacy definitions encode arrays as JSON strings
+            &IrValue::String(ref s) => serde_json::from_str(s)
+                .map_err(|_| format!("Unable to parse array of numbers from string: {}", s)),
+            _ => Err(format!("Expected an array of numbers but found {}", value)),
+        }
+    }
+}
+
+impl FromIrValue for IrNode {
+    fn from_ir_value(value: &IrValue) -> Result<Self, String> {
+        match value {
+            &IrValue::Node(ref node) => Ok((**node).clone()),
+            // legacy definitions encode nodes as JSON strings
+            &IrValue::String(ref s) => serde_json::from_str(s)
+                .map_err(|err| format!("Unable to parse node from string: {}", err)),
+            _ => Err(format!("Expected a node but found {}", value)),
+        }
+    }
+}
+
+impl FromIrValue for Dim {
+    fn from_ir_value(value: &IrValue) -> Result<Self, String> {
+        parse_string_value(value, "a dimension")
+    }
+}
+
+impl FromIrValue for InteropRangeFunction {
+    fn from_ir_value(value: &IrValue) -> Result<Self, String> {
+        parse_string_value(value, "a range function")
+    }
+}
+
+impl FromIrValue for ColorFunction {
+    fn from_ir_value(value: &IrValue) -> Result<Self, String> {
+        parse_string_value(value, "a color function")
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct IrSetting {
     pub key: String,
-    pub value: String,
+    pub value: IrValue,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+impl IrSetting {
+    pub fn new<T: Into<IrValue>>(key: &str, value: T) -> Self {
+        IrSetting {
+            key: key.into(),
+            value: value.into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct IrNode {
     #[serde(rename = "type")]
     pub _type: String,
@@ -23,6 +212,16 @@ pub struct IrNode {
     pub children: Vec<IrNode>,
 }
 
+impl IrNode {
+    pub fn new(_type: &str, settings: Vec<IrSetting>, children: Vec<IrNode>) -> Self {
+        IrNode {
+            _type: _type.into(),
+            settings,
+            children,
+        }
+    }
+}
+
 /// Attempts to convert a `Vec` of `IrNode`s to a `Vec` or something else, returning `Err` if any of the conversions failed.
 pub fn map_ir_nodes<T>(nodes: Vec<IrNode>) -> Result<Vec<T>, T::Error>
 where
@@ -39,8 +238,6 @@ where
     )
 }
 
-// TODO: Create functions for converting settings from the IR format into inner representations.
-
 /// Attempts to return a vector of all child nodes that are of a certain type.
 pub fn build_children<T>(children: Vec<IrNode>, child_type: &str) -> Result<Vec<T>, T::Error>
 where
@@ -61,7 +258,7 @@ impl TryFrom<IrNode> for CompositionTreeNodeDefinition {
         match node._type.as_str() {
             "noiseModule" | "root" => {
                 let transformations: Vec<InputTransformationDefinition> = build_child(&node.children, "inputTransformations")?;
-                let module_type: NoiseModuleType = find_setting_by_name("moduleType", &node.settings)?
+                let module_type: NoiseModuleType = convert_setting::<String>("moduleType", &node.settings)?
                     .try_into()?;
 
                 let built_def = if module_type != NoiseModuleType::Composed {
@@ -102,3 +299,171 @@ impl TryFrom<IrNode> for Vec<InputTransformationDefinition> {
         }
     }
 }
+
+/// Converts a noise module configuration back into the flat list of settings that the frontend uses to represent it.
+fn build_module_conf_settings(conf: &NoiseModuleConf) -> Vec<IrSetting> {
+    match conf {
+        &NoiseModuleConf::MultiFractal {
+            octaves,
+            frequency,
+            lacunarity,
+            persistence,
+        } => vec![
+            IrSetting::new("octaves", octaves),
+            IrSetting::new("frequency", frequency),
+            IrSetting::new("lacunarity", lacunarity),
+            IrSetting::new("persistence", persistence),
+        ],
+        &NoiseModuleConf::Seedable { ref seed } => vec![IrSetting::new("seed", seed.as_str())],
+        &NoiseModuleConf::Worley {
+            range_function,
+            range_function_enabled,
+            worley_frequency,
+            displacement,
+        } => vec![
+            IrSetting::new("rangeFunction", range_function.to_string()),
+            IrSetting::new("enableRange", range_function_enabled),
+            IrSetting::new("worleyFrequency", worley_frequency),
+            IrSetting::new("displacement", displacement),
+        ],
+        &NoiseModuleConf::Constant { constant } => vec![IrSetting::new("constant", constant)],
+        &NoiseModuleConf::RidgedMulti { attenuation } => {
+            vec![IrSetting::new("attenuation", attenuation)]
+        }
+        // These are handled by the `globalConf` node rather than by individual modules
+        &NoiseModuleConf::MasterConf { .. } => Vec::new(),
+    }
+}
+
+impl<'a> From<&'a CompositionScheme> for IrNode {
+    fn from(scheme: &CompositionScheme) -> Self {
+        let settings = match scheme {
+            &CompositionScheme::Average => vec![IrSetting::new("compositionScheme", "average")],
+            &CompositionScheme::WeightedAverage(ref weights) => vec![
+                IrSetting::new("compositionScheme", "weightedAverage"),
+                IrSetting::new("weights", weights.clone()),
+            ],
+        };
+
+        IrNode::new("compositionScheme", settings, Vec::new())
+    }
+}
+
+impl<'a> From<&'a InputTransformationDefinition> for IrNode {
+    fn from(def: &InputTransformationDefinition) -> Self {
+        let (settings, children) = match def {
+            &InputTransformationDefinition::ZoomScale { speed, zoom } => (
+                vec![
+                    IrSetting::new("inputTransformationType", "zoomScale"),
+                    IrSetting::new("speed", speed),
+                    IrSetting::new("zoom", zoom),
+                ],
+                Vec::new(),
+            ),
+            &InputTransformationDefinition::HigherOrderNoiseModule {
+                ref node_def,
+                replaced_dim,
+            } => (
+                vec![
+                    IrSetting::new("inputTransformationType", "honf"),
+                    IrSetting::new("replacedDim", replaced_dim.to_string()),
+                ],
+                vec![node_def.into()],
+            ),
+            &InputTransformationDefinition::ScaleAll(scale) => (
+                vec![
+                    IrSetting::new("inputTransformationType", "scaleAll"),
+                    IrSetting::new("scaleFactor", scale),
+                ],
+                Vec::new(),
+            ),
+        };
+
+        IrNode::new("inputTransformation", settings, children)
+    }
+}
+
+fn build_transformations_node(transformations: &[InputTransformationDefinition]) -> IrNode {
+    IrNode::new(
+        "inputTransformations",
+        Vec::new(),
+        transformations.iter().map(IrNode::from).collect(),
+    )
+}
+
+impl<'a> From<&'a CompositionTreeNodeDefinition> for IrNode {
+    /// Converts a node definition into a `noiseModule` node in the format produced by the frontend.
+    fn from(def: &CompositionTreeNodeDefinition) -> Self {
+        match def {
+            &CompositionTreeNodeDefinition::Leaf {
+                ref module_type,
+                ref module_conf,
+                ref transformations,
+            } => {
+                let mut settings = vec![IrSetting::new("moduleType", format!("{:?}", module_type))];
+                for conf in module_conf {
+                    settings.extend(build_module_conf_settings(conf));
+                }
+
+                IrNode::new(
+                    "noiseModule",
+                    settings,
+                    vec![build_transformations_node(transformations)],
+                )
+            }
+            &CompositionTreeNodeDefinition::Composed {
+                ref scheme,
+                ref children,
+                ref transformations,
+            } => {
+                let mut ir_children = vec![scheme.into(), build_transformations_node(transformations)];
+                ir_children.extend(children.iter().map(IrNode::from));
+
+                IrNode::new(
+                    "noiseModule",
+                    vec![IrSetting::new("moduleType", "Composed")],
+                    ir_children,
+                )
+            }
+        }
+    }
+}
+
+/// Builds the `globalConf` node for the provided configuration, including its color function.
+pub fn build_global_conf_node(conf: &MasterConf) -> IrNode {
+    let mut settings = vec![
+        IrSetting::new("speed", conf.speed),
+        IrSetting::new("zoom", conf.zoom),
+        IrSetting::new("xOffset", conf.x_offset),
+        IrSetting::new("yOffset", conf.y_offset),
+        IrSetting::new("zOffset", conf.z_offset),
+        IrSetting::new("colorFunction", conf.color_function.to_string()),
+        IrSetting::new("loopPeriod", conf.loop_period.unwrap_or(0.)),
+        IrSetting::new("autoNormalize", conf.auto_normalize),
+    ];
+
+    if let Some(relief_mode) = conf.relief_mode {
+        settings.push(IrSetting::new("reliefMode", relief_mode.key()));
+        settings.push(IrSetting::new("reliefStrength", relief_mode.strength()));
+        if let ReliefMode::Hillshade {
+            azimuth, altitude, ..
+        } = relief_mode
+        {
+            settings.push(IrSetting::new("sunAzimuth", azimuth));
+            settings.push(IrSetting::new("sunAltitude", altitude));
+        }
+    }
+
+    IrNode::new("globalConf", settings, Vec::new())
+}
+
+/// Converts a full tree definition into an IR tree that can be passed to `build_tree_from_def`.  This is the inverse
+/// of the conversion performed by `build_tree_from_def`.
+pub fn build_ir_from_def(def: &CompositionTreeDefinition) -> IrNode {
+    let mut root: IrNode = (&def.root_node).into();
+    root._type = "root".into();
+    root.children
+        .insert(0, build_global_conf_node(&def.global_conf));
+
+    root
+}
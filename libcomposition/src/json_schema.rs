@@ -0,0 +1,215 @@
+//! JSON Schemas for the formats that composition trees are exchanged in, allowing editors and third-party tools to
+//! validate documents.  The schema for `CompositionTreeDefinition`s is derived from the types themselves.  The IR
+//! format stores settings as generic key/value pairs, so its schema is assembled from the setting keys and values that
+//! the conversions out of `IrNode`s accept.  Values may either have their natural JSON types or be encoded as strings
+//! as in legacy definitions.
+
+use serde_json::{self, Value};
+
+use color_schemes::ColorFunction;
+use conf::InteropRangeFunction;
+use definition::{CompositionTreeDefinition, NoiseModuleType};
+use relief::ReliefMode;
+use util::Dim;
+
+const SCHEMA_VERSION: &str = "http://json-schema.org/draft-07/schema#";
+/// Matches the strings that can be parsed into floats, other than `inf` and `NaN`
+const NUMBER_PATTERN: &str = r"^[-+]?(\d+\.?\d*|\.\d+)([eE][-+]?\d+)?$";
+/// Matches the strings that can be parsed into unsigned integers
+const INTEGER_PATTERN: &str = r"^\+?\d+$";
+
+/// Every type of node that can appear in an IR tree
+const IR_NODE_TYPES: [&str; 6] = [
+    "root",
+    "globalConf",
+    "noiseModule",
+    "inputTransformations",
+    "inputTransformation",
+    "compositionScheme",
+];
+
+fn number_value() -> Value {
+    json!({
+        "anyOf": [
+            { "type": "number" },
+            { "type": "string", "pattern": NUMBER_PATTERN },
+        ]
+    })
+}
+
+fn integer_value() -> Value {
+    json!({
+        "anyOf": [
+            { "type": "integer", "minimum": 0 },
+            { "type": "string", "pattern": INTEGER_PATTERN },
+        ]
+    })
+}
+
+fn bool_value() -> Value {
+    json!({
+        "anyOf": [
+            { "type": "boolean" },
+            { "type": "string", "enum": ["true", "false"] },
+        ]
+    })
+}
+
+fn enum_value<T: ToString>(keys: &[T]) -> Value {
+    let keys: Vec<String> = keys.iter().map(ToString::to_string).collect();
+    json!({ "type": "string", "enum": keys })
+}
+
+/// Builds the schema of a setting with the supplied key and a value matching `value`.
+fn setting(key: &str, value: Value) -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "key": { "const": key },
+            "value": value,
+        },
+        "required": ["key", "value"],
+    })
+}
+
+fn noise_module_settings() -> Value {
+    let module_types: Vec<String> = NoiseModuleType::ALL
+        .iter()
+        .map(|module_type| format!("{:?}", module_type))
+        .collect();
+
+    json!({
+        "oneOf": [
+            setting("moduleType", enum_value(&module_types)),
+            setting("octaves", integer_value()),
+            setting("frequency", number_value()),
+            setting("lacunarity", number_value()),
+            setting("persistence", number_value()),
+            setting("seed", json!({ "type": "string" })),
+            setting("rangeFunction", enum_value(&InteropRangeFunction::ALL)),
+            setting("enableRange", bool_value()),
+            setting("worleyFrequency", number_value()),
+            setting("displacement", number_value()),
+            setting("constant", number_value()),
+            setting("attenuation", number_value()),
+        ]
+    })
+}
+
+fn global_conf_settings() -> Value {
+    json!({
+        "oneOf": [
+            setting("speed", number_value()),
+            setting("zoom", number_value()),
+            setting("xOffset", number_value()),
+            setting("yOffset", number_value()),
+            setting("zOffset", number_value()),
+            setting("loopPeriod", number_value()),
+            setting("autoNormalize", bool_value()),
+            setting("reliefMode", enum_value(&ReliefMode::KEYS)),
+            setting("reliefStrength", number_value()),
+            setting("sunAzimuth", number_value()),
+            setting("sunAltitude", number_value()),
+            setting("colorFunction", enum_value(&ColorFunction::ALL)),
+        ]
+    })
+}
+
+fn input_transformation_settings() -> Value {
+    // both lowercase and uppercase dimension names are accepted
+    let dims: Vec<String> = Dim::ALL
+        .iter()
+        .flat_map(|dim| vec![dim.to_string(), dim.to_string().to_uppercase()])
+        .collect();
+
+    json!({
+        "oneOf": [
+            setting("inputTransformationType", enum_value(&["zoomScale", "honf", "scaleAll"])),
+            setting("speed", number_value()),
+            setting("zoom", number_value()),
+            setting("replacedDim", enum_value(&dims)),
+            setting("scaleFactor", number_value()),
+            setting("noiseModule", json!({
+                "anyOf": [
+                    { "$ref": "#/definitions/node" },
+                    { "type": "string", "description": "A JSON-encoded node" },
+                ]
+            })),
+        ]
+    })
+}
+
+fn composition_scheme_settings() -> Value {
+    json!({
+        "oneOf": [
+            setting("compositionScheme", enum_value(&["average", "weightedAverage"])),
+            setting("weights", json!({
+                "description": "The weight of each child, in order",
+                "anyOf": [
+                    { "type": "array", "items": { "type": "number" } },
+                    { "type": "string", "description": "A JSON-encoded array of numbers" },
+                ]
+            })),
+        ]
+    })
+}
+
+/// Restricts the settings of nodes with one of the supplied types to those matching the named definition.
+fn settings_rule(node_types: &[&str], settings_definition: &str) -> Value {
+    json!({
+        "if": { "properties": { "type": { "enum": node_types } } },
+        "then": {
+            "properties": {
+                "settings": { "items": { "$ref": format!("#/definitions/{}", settings_definition) } }
+            }
+        },
+    })
+}
+
+/// Returns a JSON Schema describing IR trees as produced by the frontend and accepted by `build_tree_from_def`.
+pub fn ir_schema() -> Value {
+    json!({
+        "$schema": SCHEMA_VERSION,
+        "title": "IrNode",
+        "description": "The root node of a composition tree in the intermediate representation used by the frontend",
+        "allOf": [{ "$ref": "#/definitions/node" }],
+        "properties": {
+            "type": { "const": "root" },
+            "children": { "contains": { "properties": { "type": { "const": "globalConf" } } } },
+        },
+        "definitions": {
+            "node": {
+                "type": "object",
+                "properties": {
+                    "type": { "enum": IR_NODE_TYPES },
+                    "settings": { "type": "array", "items": { "$ref": "#/definitions/setting" } },
+                    "children": { "type": "array", "items": { "$ref": "#/definitions/node" } },
+                },
+                "required": ["type", "settings", "children"],
+                "allOf": [
+                    settings_rule(&["root", "noiseModule"], "noiseModuleSetting"),
+                    settings_rule(&["globalConf"], "globalConfSetting"),
+                    settings_rule(&["inputTransformation"], "inputTransformationSetting"),
+                    settings_rule(&["compositionScheme"], "compositionSchemeSetting"),
+                ],
+            },
+            "setting": {
+                "type": "object",
+                "properties": {
+                    "key": { "type": "string" },
+                    "value": { "type": ["boolean", "number", "string", "array", "object"] },
+                },
+                "required": ["key", "value"],
+            },
+            "noiseModuleSetting": noise_module_settings(),
+            "globalConfSetting": global_conf_settings(),
+            "inputTransformationSetting": input_transformation_settings(),
+            "compositionSchemeSetting": composition_scheme_settings(),
+        },
+    })
+}
+
+/// Returns a JSON Schema describing `CompositionTreeDefinition`s in their serialized form.
+pub fn definition_schema() -> Value {
+    serde_json::to_value(schema_for!(CompositionTreeDefinition)).unwrap()
+}
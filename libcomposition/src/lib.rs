@@ -3,18 +3,28 @@
 
 #![feature(const_fn, try_from)]
 
+extern crate byteorder;
+extern crate gif;
 extern crate itertools;
 #[macro_use]
 extern crate lazy_static;
 extern crate noise;
 extern crate palette;
+extern crate png;
+#[cfg(test)]
+#[macro_use]
+extern crate proptest;
+#[macro_use]
+extern crate schemars;
 #[macro_use]
 extern crate serde_derive;
+#[macro_use]
 extern crate serde_json;
 
 use std::convert::TryFrom;
 #[cfg(target_os = "emscripten")]
 use std::ffi::CString;
+use std::f64::consts::PI;
 use std::fmt::Debug;
 #[cfg(target_os = "emscripten")]
 use std::os::raw::c_char;
@@ -22,6 +32,8 @@ use std::str::FromStr;
 
 use noise::*;
 
+pub mod animation;
+pub mod budget;
 pub mod color_schemes;
 use color_schemes::ColorFunction;
 pub mod composition;
@@ -32,13 +44,30 @@ pub mod definition;
 use self::definition::{
     CompositionTreeDefinition, CompositionTreeNodeDefinition, InputTransformationDefinition,
 };
+pub mod dims;
+use dims::{LeafModule, NoisePoint};
+pub mod frame;
+pub mod heightmap;
+pub mod history;
 pub mod initial_tree;
 pub mod ir;
-use ir::IrNode;
+use ir::{FromIrValue, IrNode};
+pub mod json_schema;
+pub mod limits;
+pub mod mesh;
+pub mod probe;
+pub mod relief;
+use relief::{ReliefMode, DEFAULT_RELIEF_STRENGTH, DEFAULT_SUN_ALTITUDE, DEFAULT_SUN_AZIMUTH};
+pub mod render;
+use render::{LoopConf, LoopMode};
+pub mod stats;
 pub mod transformations;
 use self::transformations::{apply_transformations, InputTransformation};
 pub mod util;
 
+#[cfg(test)]
+mod tests;
+
 #[cfg(target_os = "emscripten")]
 extern "C" {
     /// Direct line to `console.log` from JS since the simulated `stdout` is dead after `main()` completes
@@ -71,8 +100,6 @@ pub fn error(msg: &str) {
     unsafe { js_error(c_str.as_ptr()) };
 }
 
-pub static mut ACTIVE_COLOR_FUNCTION: ColorFunction = ColorFunction::TieDye;
-
 pub fn parse_setting<T, D: Debug>(val: &str) -> Result<T, String>
 where
     T: FromStr<Err = D>,
@@ -82,7 +109,7 @@ where
 }
 
 /// Configuration status and state for the entire backend.
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct MasterConf {
     pub needs_resize: bool,
     pub canvas_size: usize,
@@ -91,6 +118,25 @@ pub struct MasterConf {
     pub x_offset: f64,
     pub y_offset: f64,
     pub z_offset: f64,
+    /// If set, the relief of the output is visualized instead of mapping values through the color function
+    #[serde(default)]
+    pub relief_mode: Option<ReliefMode>,
+    /// If set, the output repeats seamlessly every `loop_period` units of time
+    #[serde(default)]
+    pub loop_period: Option<f64>,
+    /// If set, the output is remapped to [-1, 1] using the bounds measured by `CompositionTree::update_normalization`
+    #[serde(default)]
+    pub auto_normalize: bool,
+    /// The measured minimum and maximum output of the tree used for auto-normalization
+    #[serde(skip)]
+    pub normalization_bounds: Option<(f64, f64)>,
+    /// The looping mode used for the rendered node if a loop period is set, selected by
+    /// `CompositionTree::update_normalization` so that the tree doesn't have to be walked for every sample
+    #[serde(skip)]
+    pub loop_mode: Option<LoopMode>,
+    /// The function used to map the tree's output to colors when it is displayed
+    #[serde(default)]
+    pub color_function: ColorFunction,
 }
 
 impl Default for MasterConf {
@@ -103,6 +149,12 @@ impl Default for MasterConf {
             x_offset: 0.0,
             y_offset: 0.0,
             z_offset: 0.0,
+            relief_mode: None,
+            loop_period: None,
+            auto_normalize: false,
+            normalization_bounds: None,
+            loop_mode: None,
+            color_function: ColorFunction::default(),
         }
     }
 }
@@ -112,25 +164,35 @@ impl TryFrom<IrNode> for MasterConf {
 
     fn try_from(node: IrNode) -> Result<Self, Self::Error> {
         let mut conf = MasterConf::default();
+        // relief settings are spread over multiple `IrSetting`s, so collect them and build the mode at the end
+        let mut relief_mode: Option<String> = None;
+        let mut relief_strength = DEFAULT_RELIEF_STRENGTH;
+        let mut sun_azimuth = DEFAULT_SUN_AZIMUTH;
+        let mut sun_altitude = DEFAULT_SUN_ALTITUDE;
         // the actual settings are stored as `IrSetting`s, so iterate through those and construct a
         // new `MasterConf` struct using their values
         for setting in node.settings {
             let key = setting.key.as_str();
             match key {
-                "speed" => conf.speed = parse_setting(&setting.value)?,
-                "zoom" => conf.zoom = parse_setting(&setting.value)?,
+                "speed" => conf.speed = FromIrValue::from_ir_value(&setting.value)?,
+                "zoom" => conf.zoom = FromIrValue::from_ir_value(&setting.value)?,
+                "xOffset" => conf.x_offset = FromIrValue::from_ir_value(&setting.value)?,
+                "yOffset" => conf.y_offset = FromIrValue::from_ir_value(&setting.value)?,
+                "zOffset" => conf.z_offset = FromIrValue::from_ir_value(&setting.value)?,
+                "loopPeriod" => {
+                    // non-positive periods disable looping
+                    let period: f64 = FromIrValue::from_ir_value(&setting.value)?;
+                    conf.loop_period = if period > 0. { Some(period) } else { None };
+                }
+                "autoNormalize" => {
+                    conf.auto_normalize = FromIrValue::from_ir_value(&setting.value)?
+                }
+                "reliefMode" => relief_mode = Some(String::from_ir_value(&setting.value)?),
+                "reliefStrength" => relief_strength = FromIrValue::from_ir_value(&setting.value)?,
+                "sunAzimuth" => sun_azimuth = FromIrValue::from_ir_value(&setting.value)?,
+                "sunAltitude" => sun_altitude = FromIrValue::from_ir_value(&setting.value)?,
                 "colorFunction" => {
-                    let color_function: ColorFunction =
-                        match ColorFunction::from_str(setting.value.as_str()) {
-                            Ok(cf) => cf,
-                            Err(err) => {
-                                return Err(err);
-                            }
-                        };
-
-                    unsafe {
-                        ACTIVE_COLOR_FUNCTION = color_function;
-                    }
+                    conf.color_function = ColorFunction::from_ir_value(&setting.value)?
                 }
                 _ => {
                     return Err(format!(
@@ -141,6 +203,11 @@ impl TryFrom<IrNode> for MasterConf {
             }
         }
 
+        if let Some(key) = relief_mode {
+            conf.relief_mode =
+                ReliefMode::from_settings(&key, relief_strength, sun_azimuth, sun_altitude)?;
+        }
+
         Ok(conf)
     }
 }
@@ -150,6 +217,8 @@ impl TryFrom<IrNode> for MasterConf {
 pub struct CompositionTree {
     pub root_node: CompositionTreeNode,
     pub global_conf: MasterConf,
+    /// Coordinates of a node that is rendered in place of the root node, set with `CompositionTree::solo`
+    pub solo_path: Option<Vec<i32>>,
 }
 
 impl CompositionTree {
@@ -227,15 +296,185 @@ impl CompositionTree {
 
         Ok(())
     }
-}
 
-impl NoiseFn<Point3<f64>> for CompositionTree {
-    fn get(&self, coord: Point3<f64>) -> f64 {
-        self.root_node.get([
+    /// Returns the node that values are retrieved from: the soloed node if there is one and the root node otherwise.
+    /// If the tree has been modified so that the solo path no longer exists, the root node is used.
+    pub fn rendered_root(&self) -> &CompositionTreeNode {
+        match self.solo_path {
+            Some(ref coords) => self.root_node.traverse(coords).unwrap_or(&self.root_node),
+            None => &self.root_node,
+        }
+    }
+
+    /// Temporarily renders the subtree at the supplied coordinates in place of the entire tree, or restores rendering
+    /// of the entire tree if `None` is supplied.  Since the solo path is stored as coordinates, it has to be updated
+    /// if the tree is modified in a way that moves the soloed node.
+    pub fn solo(&mut self, coords: Option<&[i32]>) -> Result<(), String> {
+        if let Some(coords) = coords {
+            self.root_node.traverse(coords)?;
+        }
+        self.solo_path = coords.map(|coords| coords.to_vec());
+        self.update_normalization();
+
+        Ok(())
+    }
+
+    /// Evaluates the tree at the supplied pixel coordinate and point in time, ignoring the loop period.
+    pub fn get_linear(&self, coord: Point3<f64>) -> f64 {
+        self.get_linear_from(self.rendered_root(), coord)
+    }
+
+    /// Maps a pixel coordinate and point in time to the point at which the rendered node is evaluated.  Like every
+    /// point that the tree is evaluated at, it's clamped to `dims::MAX_COORD` since the global configuration can move
+    /// it arbitrarily far away from the origin.
+    fn linear_point(&self, coord: Point3<f64>) -> Point3<f64> {
+        [
             (coord[0] * self.global_conf.zoom) + self.global_conf.x_offset,
             (coord[1] * self.global_conf.zoom) + self.global_conf.y_offset,
             (coord[2] * self.global_conf.speed) + self.global_conf.z_offset,
-        ])
+        ]
+            .clamp_coords()
+    }
+
+    fn get_linear_from(&self, node: &CompositionTreeNode, coord: Point3<f64>) -> f64 {
+        node.get(self.linear_point(coord))
+    }
+
+    /// Maps a pixel coordinate and point in time onto a circle through Z and W so that the point is the same every
+    /// `period` units of time.
+    fn circle_point(&self, coord: Point3<f64>, period: f64) -> Point4<f64> {
+        let MasterConf {
+            zoom,
+            speed,
+            x_offset,
+            y_offset,
+            z_offset,
+            ..
+        } = self.global_conf;
+        let [x, y, time] = coord;
+
+        // The radius is chosen so that one period traverses the same distance along the circle as a regular
+        // animation of the same length would along the Z axis.
+        let angle = 2. * PI * time / period;
+        let radius = period * speed / (2. * PI);
+        [
+            (x * zoom) + x_offset,
+            (y * zoom) + y_offset,
+            z_offset + radius * angle.cos(),
+            radius * angle.sin(),
+        ]
+            .clamp_coords()
+    }
+
+    /// Returns the time within the current period and the weight of the sample one period earlier for crossfaded
+    /// looping.  At the start of the period the sample is unchanged and at its end it consists entirely of the earlier
+    /// sample, which is the same point as the start.
+    fn crossfade_weight(time: f64, period: f64) -> (f64, f64) {
+        let time = ((time % period) + period) % period;
+        (time, time / period)
+    }
+
+    /// Evaluates the tree at the supplied pixel coordinate and point in time such that the output repeats every
+    /// `conf.period` units of time.
+    pub fn get_looped(&self, coord: Point3<f64>, conf: &LoopConf) -> f64 {
+        self.get_looped_from(self.rendered_root(), coord, conf)
+    }
+
+    fn get_looped_from(
+        &self,
+        node: &CompositionTreeNode,
+        coord: Point3<f64>,
+        conf: &LoopConf,
+    ) -> f64 {
+        match conf.mode {
+            LoopMode::Circle => node.get(self.circle_point(coord, conf.period)),
+            LoopMode::Crossfade => {
+                let [x, y, time] = coord;
+                let (time, weight) = CompositionTree::crossfade_weight(time, conf.period);
+                self.get_linear_from(node, [x, y, time]) * (1. - weight)
+                    + self.get_linear_from(node, [x, y, time - conf.period]) * weight
+            }
+        }
+    }
+
+    /// Evaluates the tree at the supplied pixel coordinate and point in time, respecting the loop period but without
+    /// applying auto-normalization.
+    pub fn get_raw(&self, coord: Point3<f64>) -> f64 {
+        self.get_node(self.rendered_root(), coord)
+    }
+
+    /// Evaluates the supplied node, which should be part of this tree, at the supplied pixel coordinate and point in
+    /// time using the tree's global configuration.  This makes it possible to see what any subtree contributes to the
+    /// output.  Auto-normalization isn't applied since the measured bounds only apply to the rendered root.
+    pub fn get_node(&self, node: &CompositionTreeNode, coord: Point3<f64>) -> f64 {
+        match self.global_conf.loop_period {
+            Some(period) => {
+                let conf = LoopConf {
+                    mode: self.loop_mode(),
+                    period,
+                };
+                self.get_looped_from(node, coord, &conf)
+            }
+            None => self.get_linear_from(node, coord),
+        }
+    }
+
+    /// Returns the looping mode selected for the rendered node.  Crossfading works with any tree, so it's used if the
+    /// mode hasn't been selected since the loop period was set.
+    pub fn loop_mode(&self) -> LoopMode {
+        self.global_conf.loop_mode.unwrap_or(LoopMode::Crossfade)
+    }
+
+    /// Remaps the value to [-1, 1] using the measured normalization bounds, if there are any.  Since the bounds are
+    /// measured from a finite number of samples, values outside of them are clamped.
+    pub fn normalize(&self, val: f64) -> f64 {
+        match self.global_conf.normalization_bounds {
+            Some((min, max)) => ((val - min) / (max - min) * 2. - 1.).max(-1.).min(1.),
+            None => val,
+        }
+    }
+}
+
+impl NoiseFn<Point2<f64>> for CompositionTree {
+    /// Evaluates the tree at the supplied pixel coordinate using 2D noise.  This is cheaper than 3D evaluation but
+    /// has no concept of time, so it's only suitable for static images.
+    fn get(&self, coord: Point2<f64>) -> f64 {
+        self.normalize(
+            self.rendered_root().get(
+                [
+                    (coord[0] * self.global_conf.zoom) + self.global_conf.x_offset,
+                    (coord[1] * self.global_conf.zoom) + self.global_conf.y_offset,
+                ]
+                    .clamp_coords(),
+            ),
+        )
+    }
+}
+
+impl NoiseFn<Point4<f64>> for CompositionTree {
+    /// Evaluates the tree in 4D at the supplied pixel coordinate, point in time, and W coordinate.  W is scaled by the
+    /// speed just like time is.  Modules that don't support 4D evaluation fold W into Z.
+    fn get(&self, coord: Point4<f64>) -> f64 {
+        self.normalize(
+            self.rendered_root().get(
+                [
+                    (coord[0] * self.global_conf.zoom) + self.global_conf.x_offset,
+                    (coord[1] * self.global_conf.zoom) + self.global_conf.y_offset,
+                    (coord[2] * self.global_conf.speed) + self.global_conf.z_offset,
+                    coord[3] * self.global_conf.speed,
+                ]
+                    .clamp_coords(),
+            ),
+        )
+    }
+}
+
+impl NoiseFn<Point3<f64>> for CompositionTree {
+    /// Evaluates the tree at the supplied pixel coordinate and point in time.  If a loop period is set in the global
+    /// configuration, the output repeats with that period.  If auto-normalization is enabled, the output is remapped
+    /// to [-1, 1].
+    fn get(&self, coord: Point3<f64>) -> f64 {
+        self.normalize(self.get_raw(coord))
     }
 }
 
@@ -245,7 +484,7 @@ pub struct CompositionTreeNode {
 }
 
 pub enum CompositionTreeNodeType {
-    Leaf(Box<NoiseFn<Point3<f64>>>),
+    Leaf(Box<LeafModule>),
     Combined(ComposedNoiseModule),
 }
 
@@ -275,6 +514,59 @@ impl CompositionTreeNode {
         }
     }
 
+    /// Returns `true` if every module in this node's subtree, including those used by input transformations, has a
+    /// native 4D implementation.
+    pub fn supports_4d(&self) -> bool {
+        let transformations_support_4d =
+            self.transformations
+                .iter()
+                .all(|transformation| match transformation {
+                    &InputTransformation::HigherOrderNoiseModule { ref node, .. } => {
+                        node.supports_4d()
+                    }
+                    _ => true,
+                });
+
+        transformations_support_4d && match self.function {
+            CompositionTreeNodeType::Leaf(ref module) => module.supports_4d(),
+            CompositionTreeNodeType::Combined(ref composed_module) => composed_module
+                .children
+                .iter()
+                .all(|child| child.supports_4d()),
+        }
+    }
+
+    /// Traverses the composition tree, returning a reference to the node at the provided coordinates.
+    pub fn traverse(&self, coords: &[i32]) -> Result<&CompositionTreeNode, String> {
+        let index = match coords.first() {
+            Some(&index) => index as usize,
+            None => return Ok(self),
+        };
+
+        let child = match self.function {
+            CompositionTreeNodeType::Combined(ref composed_module) => {
+                match composed_module.children.get(index) {
+                    Some(child) => child,
+                    None => {
+                        return Err(format!(
+                            "Attempted to access child of module at index {} but it only has {} children!",
+                            index,
+                            composed_module.children.len()
+                        ));
+                    }
+                }
+            }
+            CompositionTreeNodeType::Leaf(_) => {
+                return Err(format!(
+                    "Attempted to access child of module at index {} but it is a leaf node!",
+                    index
+                ));
+            }
+        };
+
+        child.traverse(&coords[1..])
+    }
+
     /// Traverses the composition tree, returning a mutable reference to the node at the provided coordinates.
     pub fn traverse_mut(&mut self, coords: &[i32]) -> Result<&mut CompositionTreeNode, String> {
         if coords.len() == 0 {
@@ -315,12 +607,12 @@ impl CompositionTreeNode {
     }
 }
 
-impl NoiseFn<Point3<f64>> for CompositionTreeNode {
-    fn get(&self, coord: Point3<f64>) -> f64 {
+impl<P: NoisePoint> NoiseFn<P> for CompositionTreeNode {
+    fn get(&self, coord: P) -> f64 {
         let transformed_coord = apply_transformations(&self.transformations, coord);
 
         match self.function {
-            CompositionTreeNodeType::Leaf(ref module) => module.get(transformed_coord),
+            CompositionTreeNodeType::Leaf(ref module) => P::eval_leaf(&**module, transformed_coord),
             // TODO: apply input transformations unless I'm missing where they're actually applied.
             CompositionTreeNodeType::Combined(ref composed_module) => {
                 composed_module.get(transformed_coord)
@@ -364,8 +656,8 @@ impl ComposedNoiseModule {
     }
 }
 
-impl NoiseFn<Point3<f64>> for ComposedNoiseModule {
-    fn get(&self, coord: Point3<f64>) -> f64 {
+impl<P: NoisePoint> NoiseFn<P> for ComposedNoiseModule {
+    fn get(&self, coord: P) -> f64 {
         self.composer.compose(&self.children, coord)
     }
 }
@@ -0,0 +1,168 @@
+//! Limits on the size and complexity of composition trees built from untrusted definitions.  The cost of evaluating a
+//! tree at a single point grows with the number of octaves of its multifractal modules and with every level of nested
+//! modules and higher order noise functions, so definitions are measured and checked against the limits before they're
+//! built.
+
+use noise::Fbm;
+
+use conf::NoiseModuleConf;
+use definition::{CompositionTreeNodeDefinition, InputTransformationDefinition, NoiseModuleType};
+
+/// The number of octaves that multifractal modules generate if no multifractal configuration is supplied
+const DEFAULT_OCTAVES: u32 = 6;
+/// The cost of applying input transformations and combining the outputs of child modules at each node
+const NODE_OVERHEAD: f64 = 0.1;
+
+/// Measurements of a node definition's subtree that are checked against the resource limits.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DefinitionMetrics {
+    /// The largest number of nested nodes, counting both the children of composed nodes and the nodes of higher
+    /// order noise functions
+    pub depth: usize,
+    pub node_count: usize,
+    /// The largest number of octaves configured for any module
+    pub max_octaves: u32,
+    /// The estimated cost of evaluating the tree at a single point, measured in evaluations of one octave of noise
+    pub cost: f64,
+}
+
+impl DefinitionMetrics {
+    pub fn measure(node: &CompositionTreeNodeDefinition) -> Self {
+        let mut metrics = DefinitionMetrics {
+            depth: 0,
+            node_count: 0,
+            max_octaves: 0,
+            cost: 0.,
+        };
+        metrics.cost = metrics.measure_node(node, 1);
+
+        metrics
+    }
+
+    /// Records the node's subtree in the metrics and returns its cost.
+    fn measure_node(&mut self, node: &CompositionTreeNodeDefinition, depth: usize) -> f64 {
+        self.depth = self.depth.max(depth);
+        self.node_count += 1;
+
+        let transformations_cost: f64 = node
+            .transformations()
+            .iter()
+            .map(|transformation| match transformation {
+                &InputTransformationDefinition::HigherOrderNoiseModule { ref node_def, .. } => {
+                    self.measure_node(node_def, depth + 1)
+                }
+                _ => 0.,
+            })
+            .sum();
+
+        let function_cost = match node {
+            &CompositionTreeNodeDefinition::Leaf {
+                ref module_type,
+                ref module_conf,
+                ..
+            } => self.measure_leaf(module_type, module_conf),
+            &CompositionTreeNodeDefinition::Composed { ref children, .. } => children
+                .iter()
+                .map(|child| self.measure_node(child, depth + 1))
+                .sum(),
+        };
+
+        NODE_OVERHEAD + transformations_cost + function_cost
+    }
+
+    fn measure_leaf(&mut self, module_type: &NoiseModuleType, confs: &[NoiseModuleConf]) -> f64 {
+        // like when the module is built, the last multifractal configuration takes precedence
+        let octaves = confs
+            .iter()
+            .rev()
+            .filter_map(|conf| match conf {
+                &NoiseModuleConf::MultiFractal { octaves, .. } => Some(octaves),
+                _ => None,
+            })
+            .next();
+        if let Some(octaves) = octaves {
+            self.max_octaves = self.max_octaves.max(octaves);
+        }
+        // modules never generate more octaves than they support, no matter how many are configured
+        let octaves = octaves
+            .unwrap_or(DEFAULT_OCTAVES)
+            .max(1)
+            .min(Fbm::MAX_OCTAVES as u32) as f64;
+
+        match module_type {
+            &NoiseModuleType::Fbm
+            | &NoiseModuleType::Billow
+            | &NoiseModuleType::HybridMulti
+            | &NoiseModuleType::RidgedMulti
+            | &NoiseModuleType::BasicMulti => octaves,
+            // Worley noise checks the distance to the seed points of every neighboring cell
+            &NoiseModuleType::Worley => 3.,
+            &NoiseModuleType::OpenSimplex | &NoiseModuleType::SuperSimplex => 1.,
+            &NoiseModuleType::Value => 0.5,
+            &NoiseModuleType::Constant | &NoiseModuleType::Composed => 0.,
+        }
+    }
+}
+
+/// Limits on the size and complexity of composition trees.  Trees at the default limits can still be rendered at
+/// interactive speeds on the backend.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResourceLimits {
+    pub max_depth: usize,
+    pub max_nodes: usize,
+    /// The most octaves that any multifractal module may have.  This is lower than `Fbm::MAX_OCTAVES` since octaves are
+    /// the largest part of the cost of most modules.
+    pub max_octaves: u32,
+    /// The largest estimated cost of evaluating the tree at a single point; see `DefinitionMetrics::cost`
+    pub max_cost: f64,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        ResourceLimits {
+            max_depth: 16,
+            max_nodes: 256,
+            max_octaves: 16,
+            max_cost: 200.,
+        }
+    }
+}
+
+impl ResourceLimits {
+    /// Returns an error describing the first limit that the metrics exceed, if any.
+    pub fn check(&self, metrics: &DefinitionMetrics) -> Result<(), String> {
+        if metrics.depth > self.max_depth {
+            return Err(format!(
+                "The composition is nested {} levels deep, but at most {} levels are allowed.",
+                metrics.depth, self.max_depth
+            ));
+        }
+        if metrics.node_count > self.max_nodes {
+            return Err(format!(
+                "The composition contains {} noise modules, but at most {} are allowed.",
+                metrics.node_count, self.max_nodes
+            ));
+        }
+        if metrics.max_octaves > self.max_octaves {
+            return Err(format!(
+                "A noise module in the composition has {} octaves, but at most {} are allowed.",
+                metrics.max_octaves, self.max_octaves
+            ));
+        }
+        if metrics.cost > self.max_cost {
+            return Err(format!(
+                "The composition is too expensive to render: its estimated cost of {:.1} per pixel exceeds the limit \
+                 of {:.1}.  Try using fewer octaves or fewer nested modules.",
+                metrics.cost, self.max_cost
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Measures the node definition's subtree and checks it against the limits.
+    pub fn check_definition(&self, node: &CompositionTreeNodeDefinition) -> Result<(), String> {
+        self.check(&DefinitionMetrics::measure(node))
+    }
+}
@@ -0,0 +1,464 @@
+//! Exports the output of a composition tree as a 3D terrain mesh.  The tree is sampled over a regular grid and the
+//! values are used as the heights of the vertices, producing an indexed triangle mesh that can be written out as
+//! Wavefront OBJ, binary STL, or binary glTF 2.0.
+//!
+//! Meshes are Y-up: the grid spans the X and Z axes, with the top of the rendered image facing towards -Z.
+
+use std::f32;
+use std::io::Write;
+use std::str::FromStr;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use noise::NoiseFn;
+
+use color_schemes::ColorFunction;
+use CompositionTree;
+
+/// The file formats that meshes can be exported to.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MeshFormat {
+    /// Wavefront OBJ.  Vertex colors are written using the widely supported `v x y z r g b` extension.
+    Obj,
+    /// Binary STL.  The format has no standard way of storing colors, so vertex colors are ignored.
+    Stl,
+    /// Binary glTF 2.0
+    Glb,
+}
+
+impl FromStr for MeshFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "obj" => Ok(MeshFormat::Obj),
+            "stl" => Ok(MeshFormat::Stl),
+            "glb" | "gltf" => Ok(MeshFormat::Glb),
+            _ => Err(format!("Unable to convert \"{}\" into `MeshFormat`!", s)),
+        }
+    }
+}
+
+impl MeshFormat {
+    /// Returns the file extension typically used for files of this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            &MeshFormat::Obj => "obj",
+            &MeshFormat::Stl => "stl",
+            &MeshFormat::Glb => "glb",
+        }
+    }
+}
+
+/// Determines how the composition tree is sampled and how the samples are turned into a mesh.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MeshConf {
+    /// Number of vertices along each side of the grid
+    pub resolution: usize,
+    /// Width of the sampled area in pixels of the rendered image.  The tree's global configuration is applied to the
+    /// pixel coordinates just like when rendering images, so a value equal to `resolution` produces a mesh matching an
+    /// image of that size.
+    pub sample_size: f64,
+    /// Width and depth of the mesh in output units
+    pub extent: f64,
+    /// Multiplier applied to the output of the tree to get the height of each vertex
+    pub height_scale: f64,
+    /// If set, adds a skirt of this depth around the edges of the mesh which hides cracks between adjacent tiles.
+    pub skirt_depth: Option<f64>,
+    /// If set, vertices are colored by mapping their values through this color function.
+    pub vertex_colors: Option<ColorFunction>,
+    /// Sequence number to sample at, in the same units as ticks of the engine
+    pub time: f64,
+}
+
+impl MeshConf {
+    /// Creates a configuration that samples the same area as an image with the given resolution, producing a mesh
+    /// with an extent of 1 and no skirts or vertex colors.
+    pub fn new(resolution: usize) -> Self {
+        MeshConf {
+            resolution,
+            sample_size: resolution as f64,
+            extent: 1.0,
+            height_scale: 0.1,
+            skirt_depth: None,
+            vertex_colors: None,
+            time: 0.0,
+        }
+    }
+}
+
+/// An indexed triangle mesh.  Triangles are wound counter-clockwise when viewed from their front face.
+pub struct Mesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    /// RGBA colors for each vertex, if vertex colors were requested
+    pub colors: Option<Vec<[u8; 4]>>,
+    pub indices: Vec<u32>,
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let len = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    if len == 0. {
+        return [0., 1., 0.];
+    }
+
+    [v[0] / len, v[1] / len, v[2] / len]
+}
+
+impl Mesh {
+    fn triangles<'a>(&'a self) -> impl Iterator<Item = [usize; 3]> + 'a {
+        self.indices
+            .chunks(3)
+            .map(|tri| [tri[0] as usize, tri[1] as usize, tri[2] as usize])
+    }
+
+    fn face_normal(&self, [a, b, c]: [usize; 3]) -> [f32; 3] {
+        let (a, b, c) = (self.positions[a], self.positions[b], self.positions[c]);
+        cross(sub(b, a), sub(c, a))
+    }
+
+    /// Sets the normal of each vertex to the area-weighted average of the normals of the triangles that contain it.
+    fn compute_normals(&mut self, vertex_range: (usize, usize)) {
+        let (start, end) = vertex_range;
+        let mut sums = vec![[0f32; 3]; end - start];
+        for tri in self.triangles() {
+            // the unnormalized cross product is proportional to the area of the triangle
+            let normal = self.face_normal(tri);
+            for &i in &tri {
+                if i >= start && i < end {
+                    let sum = &mut sums[i - start];
+                    sum[0] += normal[0];
+                    sum[1] += normal[1];
+                    sum[2] += normal[2];
+                }
+            }
+        }
+
+        for (i, sum) in sums.into_iter().enumerate() {
+            self.normals[start + i] = normalize(sum);
+        }
+    }
+
+    fn bounds(&self) -> ([f32; 3], [f32; 3]) {
+        self.positions.iter().fold(
+            ([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]),
+            |(min, max), pos| {
+                (
+                    [min[0].min(pos[0]), min[1].min(pos[1]), min[2].min(pos[2])],
+                    [max[0].max(pos[0]), max[1].max(pos[1]), max[2].max(pos[2])],
+                )
+            },
+        )
+    }
+}
+
+/// Returns the indices of the border vertices of a `resolution` x `resolution` grid, walking around it clockwise
+/// when viewed from above starting at the top left corner.
+fn border_indices(resolution: usize) -> Vec<usize> {
+    let last = resolution - 1;
+    let top = 0..last;
+    let right = (0..last).map(|z| z * resolution + last);
+    let bottom = (1..resolution).rev().map(|x| last * resolution + x);
+    let left = (1..resolution).rev().map(|z| z * resolution);
+
+    top.chain(right).chain(bottom).chain(left).collect()
+}
+
+/// Samples the composition tree over a grid and builds a mesh from the results.
+pub fn build_mesh(tree: &CompositionTree, conf: &MeshConf) -> Result<Mesh, String> {
+    let res = conf.resolution;
+    if res < 2 {
+        return Err("Mesh resolution must be at least 2!".into());
+    } else if (res * res * 2) > u32::max_value() as usize {
+        return Err("Mesh resolution is too large to be indexed!".into());
+    }
+
+    let sample_step = conf.sample_size / (res - 1) as f64;
+    let grid_step = conf.extent / (res - 1) as f64;
+    let half_extent = conf.extent / 2.;
+
+    let mut positions = Vec::with_capacity(res * res);
+    let mut values = Vec::with_capacity(res * res);
+    for z in 0..res {
+        for x in 0..res {
+            let val = tree.get([x as f64 * sample_step, z as f64 * sample_step, conf.time]);
+            values.push(val);
+            positions.push([
+                (x as f64 * grid_step - half_extent) as f32,
+                (val * conf.height_scale) as f32,
+                (z as f64 * grid_step - half_extent) as f32,
+            ]);
+        }
+    }
+
+    let mut indices: Vec<u32> = Vec::with_capacity((res - 1) * (res - 1) * 6);
+    for z in 0..(res - 1) {
+        for x in 0..(res - 1) {
+            let top_left = (z * res + x) as u32;
+            let top_right = top_left + 1;
+            let bottom_left = top_left + res as u32;
+            let bottom_right = bottom_left + 1;
+
+            indices.extend_from_slice(&[top_left, bottom_left, top_right]);
+            indices.extend_from_slice(&[top_right, bottom_left, bottom_right]);
+        }
+    }
+
+    let mut mesh = Mesh {
+        normals: vec![[0., 1., 0.]; positions.len()],
+        positions,
+        colors: conf.vertex_colors.map(|color_fn| {
+            values
+                .iter()
+                .map(|&val| color_fn.colorize(val as f32))
+                .collect()
+        }),
+        indices,
+    };
+    mesh.compute_normals((0, res * res));
+
+    if let Some(depth) = conf.skirt_depth {
+        add_skirt(&mut mesh, res, depth as f32);
+    }
+
+    Ok(mesh)
+}
+
+/// Adds a vertical strip of triangles hanging down from the border of the grid.  The skirt has its own vertices so
+/// that its normals (which face outwards) don't affect the shading of the terrain.
+fn add_skirt(mesh: &mut Mesh, resolution: usize, depth: f32) {
+    let skirt_start = mesh.positions.len();
+    let border = border_indices(resolution);
+
+    for &i in &border {
+        let top = mesh.positions[i];
+        mesh.positions.push(top);
+        mesh.positions.push([top[0], top[1] - depth, top[2]]);
+        if let Some(ref mut colors) = mesh.colors {
+            let color = colors[i];
+            colors.push(color);
+            colors.push(color);
+        }
+    }
+    mesh.normals
+        .resize(mesh.positions.len(), [0., 0., 0.]);
+
+    for i in 0..border.len() {
+        let top = (skirt_start + i * 2) as u32;
+        let bottom = top + 1;
+        let next_top = (skirt_start + ((i + 1) % border.len()) * 2) as u32;
+        let next_bottom = next_top + 1;
+
+        mesh.indices.extend_from_slice(&[top, next_top, bottom]);
+        mesh.indices
+            .extend_from_slice(&[next_top, next_bottom, bottom]);
+    }
+
+    let skirt_end = mesh.positions.len();
+    mesh.compute_normals((skirt_start, skirt_end));
+}
+
+fn write_obj<W: Write>(writer: &mut W, mesh: &Mesh) -> Result<(), String> {
+    let map_err = |err| format!("Unable to write OBJ data: {}", err);
+
+    writeln!(writer, "# Generated by noise-asmjs").map_err(map_err)?;
+    for (i, pos) in mesh.positions.iter().enumerate() {
+        match mesh.colors {
+            Some(ref colors) => writeln!(
+                writer,
+                "v {} {} {} {} {} {}",
+                pos[0],
+                pos[1],
+                pos[2],
+                f32::from(colors[i][0]) / 255.,
+                f32::from(colors[i][1]) / 255.,
+                f32::from(colors[i][2]) / 255.
+            ),
+            None => writeln!(writer, "v {} {} {}", pos[0], pos[1], pos[2]),
+        }.map_err(map_err)?;
+    }
+
+    for normal in &mesh.normals {
+        writeln!(writer, "vn {} {} {}", normal[0], normal[1], normal[2]).map_err(map_err)?;
+    }
+
+    // OBJ indices start at 1
+    for [a, b, c] in mesh.triangles() {
+        let (a, b, c) = (a + 1, b + 1, c + 1);
+        writeln!(writer, "f {}//{} {}//{} {}//{}", a, a, b, b, c, c).map_err(map_err)?;
+    }
+
+    Ok(())
+}
+
+fn write_stl<W: Write>(writer: &mut W, mesh: &Mesh) -> Result<(), String> {
+    let mut buf: Vec<u8> = Vec::with_capacity(84 + (mesh.indices.len() / 3) * 50);
+    let mut header = b"Binary STL generated by noise-asmjs".to_vec();
+    header.resize(80, 0);
+    buf.extend_from_slice(&header);
+    buf.write_u32::<LittleEndian>((mesh.indices.len() / 3) as u32)
+        .unwrap();
+
+    for tri in mesh.triangles() {
+        let normal = normalize(mesh.face_normal(tri));
+        for &component in normal
+            .iter()
+            .chain(tri.iter().flat_map(|&i| mesh.positions[i].iter()))
+        {
+            buf.write_f32::<LittleEndian>(component).unwrap();
+        }
+        // attribute byte count
+        buf.write_u16::<LittleEndian>(0).unwrap();
+    }
+
+    writer
+        .write_all(&buf)
+        .map_err(|err| format!("Unable to write STL data: {}", err))
+}
+
+fn pad_to_4(buf: &mut Vec<u8>, padding: u8) {
+    while buf.len() % 4 != 0 {
+        buf.push(padding);
+    }
+}
+
+/// Writes a single-mesh binary glTF file containing positions, normals, optional vertex colors, and `u32` indices
+/// in a single buffer.
+fn write_glb<W: Write>(writer: &mut W, mesh: &Mesh) -> Result<(), String> {
+    const ARRAY_BUFFER: u32 = 34962;
+    const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+    const FLOAT: u32 = 5126;
+    const UNSIGNED_BYTE: u32 = 5121;
+    const UNSIGNED_INT: u32 = 5125;
+
+    let vertex_count = mesh.positions.len();
+    let mut bin: Vec<u8> = Vec::new();
+    let mut buffer_views = Vec::new();
+    let mut accessors = Vec::new();
+    let mut attributes = json!({});
+
+    let (min, max) = mesh.bounds();
+    let vec3_views: [(&str, &[[f32; 3]]); 2] =
+        [("POSITION", &mesh.positions), ("NORMAL", &mesh.normals)];
+    for &(name, data) in &vec3_views {
+        let offset = bin.len();
+        for &component in data.iter().flat_map(|v| v.iter()) {
+            bin.write_f32::<LittleEndian>(component).unwrap();
+        }
+        buffer_views.push(json!({
+            "buffer": 0,
+            "byteOffset": offset,
+            "byteLength": bin.len() - offset,
+            "target": ARRAY_BUFFER,
+        }));
+        let mut accessor = json!({
+            "bufferView": buffer_views.len() - 1,
+            "componentType": FLOAT,
+            "count": vertex_count,
+            "type": "VEC3",
+        });
+        if name == "POSITION" {
+            accessor["min"] = json!(min);
+            accessor["max"] = json!(max);
+        }
+        accessors.push(accessor);
+        attributes[name] = json!(accessors.len() - 1);
+    }
+
+    if let Some(ref colors) = mesh.colors {
+        let offset = bin.len();
+        for color in colors {
+            bin.extend_from_slice(color);
+        }
+        buffer_views.push(json!({
+            "buffer": 0,
+            "byteOffset": offset,
+            "byteLength": bin.len() - offset,
+            "target": ARRAY_BUFFER,
+        }));
+        accessors.push(json!({
+            "bufferView": buffer_views.len() - 1,
+            "componentType": UNSIGNED_BYTE,
+            "normalized": true,
+            "count": vertex_count,
+            "type": "VEC4",
+        }));
+        attributes["COLOR_0"] = json!(accessors.len() - 1);
+    }
+
+    let offset = bin.len();
+    for &index in &mesh.indices {
+        bin.write_u32::<LittleEndian>(index).unwrap();
+    }
+    buffer_views.push(json!({
+        "buffer": 0,
+        "byteOffset": offset,
+        "byteLength": bin.len() - offset,
+        "target": ELEMENT_ARRAY_BUFFER,
+    }));
+    accessors.push(json!({
+        "bufferView": buffer_views.len() - 1,
+        "componentType": UNSIGNED_INT,
+        "count": mesh.indices.len(),
+        "type": "SCALAR",
+    }));
+    pad_to_4(&mut bin, 0);
+
+    let gltf = json!({
+        "asset": { "version": "2.0", "generator": "noise-asmjs" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "mesh": 0 }],
+        "meshes": [{
+            "primitives": [{
+                "attributes": attributes,
+                "indices": accessors.len() - 1,
+                "mode": 4,
+            }],
+        }],
+        "accessors": accessors,
+        "bufferViews": buffer_views,
+        "buffers": [{ "byteLength": bin.len() }],
+    });
+    let mut json_chunk = gltf.to_string().into_bytes();
+    pad_to_4(&mut json_chunk, b' ');
+
+    let total_length = 12 + 8 + json_chunk.len() + 8 + bin.len();
+    let mut buf: Vec<u8> = Vec::with_capacity(total_length);
+    buf.extend_from_slice(b"glTF");
+    buf.write_u32::<LittleEndian>(2).unwrap();
+    buf.write_u32::<LittleEndian>(total_length as u32).unwrap();
+
+    buf.write_u32::<LittleEndian>(json_chunk.len() as u32)
+        .unwrap();
+    buf.extend_from_slice(b"JSON");
+    buf.extend_from_slice(&json_chunk);
+
+    buf.write_u32::<LittleEndian>(bin.len() as u32).unwrap();
+    buf.extend_from_slice(b"BIN\0");
+    buf.extend_from_slice(&bin);
+
+    writer
+        .write_all(&buf)
+        .map_err(|err| format!("Unable to write glTF data: {}", err))
+}
+
+/// Encodes the mesh in the selected format and writes it into `writer`.
+pub fn write_mesh<W: Write>(mut writer: W, format: MeshFormat, mesh: &Mesh) -> Result<(), String> {
+    match format {
+        MeshFormat::Obj => write_obj(&mut writer, mesh),
+        MeshFormat::Stl => write_stl(&mut writer, mesh),
+        MeshFormat::Glb => write_glb(&mut writer, mesh),
+    }
+}
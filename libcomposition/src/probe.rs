@@ -0,0 +1,139 @@
+//! Traces the evaluation of a composition tree at a single pixel, recording the point that each node is evaluated at
+//! and the value it produces.  This makes it possible to see how each node contributes to the final output.
+
+use noise::NoiseFn;
+
+use dims::NoisePoint;
+use render::LoopMode;
+use transformations::apply_transformations;
+use {CompositionTree, CompositionTreeNode, CompositionTreeNodeType};
+
+/// The evaluation of a single node of the composition tree.
+#[derive(Clone, Debug, Serialize)]
+pub struct NodeProbe {
+    /// Coordinates of the node in the tree, in the same format used to address nodes when modifying the tree
+    pub path: Vec<i32>,
+    /// Either `leaf` or `composed`
+    pub kind: &'static str,
+    /// The point that was passed to the node
+    pub input: Vec<f64>,
+    /// The point after the node's input transformations were applied to it
+    pub transformed: Vec<f64>,
+    pub output: f64,
+    pub children: Vec<NodeProbe>,
+}
+
+/// A single evaluation of the rendered root node and the weight with which its output contributes to the final value.
+#[derive(Clone, Debug, Serialize)]
+pub struct ProbeSample {
+    pub weight: f64,
+    pub root: NodeProbe,
+}
+
+/// The trace of the evaluation of the composition tree at a single pixel and point in time.
+#[derive(Clone, Debug, Serialize)]
+pub struct PixelProbe {
+    pub x: f64,
+    pub y: f64,
+    pub time: f64,
+    /// The combined output of the samples before auto-normalization is applied
+    pub raw_value: f64,
+    /// The final value, the same one that is produced by `CompositionTree::get`
+    pub value: f64,
+    /// The evaluations that make up the output.  There is only one unless the tree loops by crossfading, in which
+    /// case two samples one period apart are blended.
+    pub samples: Vec<ProbeSample>,
+}
+
+fn probe_node<P: NoisePoint + AsRef<[f64]>>(
+    node: &CompositionTreeNode,
+    path: Vec<i32>,
+    coord: P,
+) -> NodeProbe
+where
+    CompositionTreeNode: NoiseFn<P>,
+{
+    let transformed = apply_transformations(&node.transformations, coord);
+
+    let (kind, output, children) = match node.function {
+        CompositionTreeNodeType::Leaf(ref module) => {
+            ("leaf", P::eval_leaf(&**module, transformed), Vec::new())
+        }
+        CompositionTreeNodeType::Combined(ref composed_module) => {
+            let children: Vec<NodeProbe> = composed_module
+                .children
+                .iter()
+                .enumerate()
+                .map(|(i, child)| {
+                    let mut child_path = path.clone();
+                    child_path.push(i as i32);
+                    probe_node(child, child_path, transformed)
+                })
+                .collect();
+            let output = composed_module
+                .composer
+                .combine(children.iter().map(|child| child.output));
+
+            ("composed", output, children)
+        }
+    };
+
+    NodeProbe {
+        path,
+        kind,
+        input: coord.as_ref().to_vec(),
+        transformed: transformed.as_ref().to_vec(),
+        output,
+        children,
+    }
+}
+
+impl CompositionTree {
+    /// Evaluates the tree at the supplied pixel coordinate and point in time, recording the input, transformed input,
+    /// and output of every node in the rendered subtree.
+    pub fn probe(&self, x: f64, y: f64, time: f64) -> PixelProbe {
+        let root = self.rendered_root();
+        let path = match self.solo_path {
+            Some(ref coords) if self.root_node.traverse(coords).is_ok() => coords.clone(),
+            _ => Vec::new(),
+        };
+
+        let samples = match self.global_conf.loop_period {
+            None => vec![ProbeSample {
+                weight: 1.,
+                root: probe_node(root, path, self.linear_point([x, y, time])),
+            }],
+            Some(period) => match self.loop_mode() {
+                LoopMode::Circle => vec![ProbeSample {
+                    weight: 1.,
+                    root: probe_node(root, path, self.circle_point([x, y, time], period)),
+                }],
+                LoopMode::Crossfade => {
+                    let (time, weight) = CompositionTree::crossfade_weight(time, period);
+                    vec![
+                        ProbeSample {
+                            weight: 1. - weight,
+                            root: probe_node(root, path.clone(), self.linear_point([x, y, time])),
+                        },
+                        ProbeSample {
+                            weight,
+                            root: probe_node(root, path, self.linear_point([x, y, time - period])),
+                        },
+                    ]
+                }
+            },
+        };
+
+        let raw_value = samples
+            .iter()
+            .fold(0., |acc, sample| acc + sample.root.output * sample.weight);
+        PixelProbe {
+            x,
+            y,
+            time,
+            raw_value,
+            value: self.normalize(raw_value),
+            samples,
+        }
+    }
+}
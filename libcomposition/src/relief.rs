@@ -0,0 +1,167 @@
+//! Render modes that treat the output of the composition tree as a heightfield and visualize its relief rather than
+//! mapping each value to a color independently.  Since these require the values of neighboring pixels, they operate on
+//! full buffers of values rather than single values like `ColorFunction::colorize`.
+
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+/// The default multiplier applied to the differences between neighboring values
+pub const DEFAULT_RELIEF_STRENGTH: f64 = 10.0;
+/// The default direction of the sun for hillshading in degrees clockwise from the top of the image
+pub const DEFAULT_SUN_AZIMUTH: f64 = 315.0;
+/// The default angle of the sun above the horizon for hillshading in degrees
+pub const DEFAULT_SUN_ALTITUDE: f64 = 45.0;
+
+/// Determines how the relief of the heightfield is visualized.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum ReliefMode {
+    /// Tangent-space normal map using the OpenGL (Y+) convention
+    NormalMap { strength: f64 },
+    /// Grayscale shading as if lit by a sun at the given azimuth and altitude (both in degrees)
+    Hillshade {
+        strength: f64,
+        azimuth: f64,
+        altitude: f64,
+    },
+    /// Grayscale steepness, with flat areas black and vertical areas white
+    Slope { strength: f64 },
+}
+
+impl ReliefMode {
+    /// The keys used by the frontend to identify each mode, including `none` which disables relief visualization
+    pub const KEYS: [&'static str; 4] = ["none", "normalMap", "hillshade", "slope"];
+
+    /// Builds a relief mode from its key as used by the frontend and its settings.  Returns `Ok(None)` for `none`.
+    pub fn from_settings(
+        key: &str,
+        strength: f64,
+        azimuth: f64,
+        altitude: f64,
+    ) -> Result<Option<Self>, String> {
+        match key {
+            "none" => Ok(None),
+            "normalMap" => Ok(Some(ReliefMode::NormalMap { strength })),
+            "hillshade" => Ok(Some(ReliefMode::Hillshade {
+                strength,
+                azimuth,
+                altitude,
+            })),
+            "slope" => Ok(Some(ReliefMode::Slope { strength })),
+            _ => Err(format!("Unable to convert \"{}\" into `ReliefMode`!", key)),
+        }
+    }
+
+    /// Returns the key used by the frontend to identify this mode.
+    pub fn key(&self) -> &'static str {
+        match self {
+            &ReliefMode::NormalMap { .. } => "normalMap",
+            &ReliefMode::Hillshade { .. } => "hillshade",
+            &ReliefMode::Slope { .. } => "slope",
+        }
+    }
+
+    pub fn strength(&self) -> f64 {
+        match self {
+            &ReliefMode::NormalMap { strength }
+            | &ReliefMode::Hillshade { strength, .. }
+            | &ReliefMode::Slope { strength } => strength,
+        }
+    }
+}
+
+impl FromStr for ReliefMode {
+    type Err = String;
+
+    /// Parses a relief mode with default settings.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ReliefMode::from_settings(
+            s,
+            DEFAULT_RELIEF_STRENGTH,
+            DEFAULT_SUN_AZIMUTH,
+            DEFAULT_SUN_ALTITUDE,
+        )?
+        .ok_or_else(|| String::from("`none` isn't a valid relief mode here!"))
+    }
+}
+
+/// Computes the gradient of the heightfield at the given pixel using central differences.  Edge pixels use one-sided
+/// differences instead.  The Y component points towards the top of the image.
+fn gradient(values: &[f64], width: usize, height: usize, x: usize, y: usize) -> (f64, f64) {
+    let get = |x: usize, y: usize| values[y * width + x];
+    let (left, right) = (x.saturating_sub(1), (x + 1).min(width - 1));
+    let (top, bottom) = (y.saturating_sub(1), (y + 1).min(height - 1));
+
+    let dx = if right > left {
+        (get(right, y) - get(left, y)) / (right - left) as f64
+    } else {
+        0.
+    };
+    let dy = if bottom > top {
+        (get(x, top) - get(x, bottom)) / (bottom - top) as f64
+    } else {
+        0.
+    };
+
+    (dx, dy)
+}
+
+/// Returns the unit surface normal for the supplied gradient.
+fn normal((dx, dy): (f64, f64), strength: f64) -> [f64; 3] {
+    let (nx, ny, nz) = (-dx * strength, -dy * strength, 1.);
+    let len = (nx * nx + ny * ny + nz * nz).sqrt();
+    [nx / len, ny / len, nz / len]
+}
+
+fn to_byte(val: f64) -> u8 {
+    (val.max(0.).min(1.) * 255.).round() as u8
+}
+
+/// Visualizes the relief of a `width` x `height` heightfield of values in row-major order, returning RGBA pixel data.
+pub fn render_relief(values: &[f64], width: usize, height: usize, mode: ReliefMode) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(width * height * 4);
+    if values.len() != width * height {
+        return pixels;
+    }
+
+    // direction towards the sun, with Y pointing towards the top of the image
+    let light = match mode {
+        ReliefMode::Hillshade {
+            azimuth, altitude, ..
+        } => {
+            let (azimuth, altitude) = (azimuth * PI / 180., altitude * PI / 180.);
+            [
+                azimuth.sin() * altitude.cos(),
+                azimuth.cos() * altitude.cos(),
+                altitude.sin(),
+            ]
+        }
+        _ => [0., 0., 1.],
+    };
+
+    for y in 0..height {
+        for x in 0..width {
+            let n = normal(gradient(values, width, height, x, y), mode.strength());
+
+            let pixel = match mode {
+                ReliefMode::NormalMap { .. } => [
+                    to_byte(n[0] * 0.5 + 0.5),
+                    to_byte(n[1] * 0.5 + 0.5),
+                    to_byte(n[2] * 0.5 + 0.5),
+                    255,
+                ],
+                ReliefMode::Hillshade { .. } => {
+                    let shade = to_byte(n[0] * light[0] + n[1] * light[1] + n[2] * light[2]);
+                    [shade, shade, shade, 255]
+                }
+                ReliefMode::Slope { .. } => {
+                    // angle between the normal and straight up, normalized from [0, pi/2]
+                    let slope = to_byte(n[2].min(1.).acos() / (PI / 2.));
+                    [slope, slope, slope, 255]
+                }
+            };
+            pixels.extend_from_slice(&pixel);
+        }
+    }
+
+    pixels
+}
@@ -0,0 +1,296 @@
+//! Functions for rendering a `CompositionTree` into buffers of values or pixels outside of the browser.
+
+use std::f64::consts::PI;
+use std::io::Write;
+
+use noise::{NoiseFn, Point2, Point4};
+use png::{self, HasParameters};
+
+use color_schemes::ColorFunction;
+use dims::NoisePoint;
+use relief::{render_relief, ReliefMode};
+use {CompositionTree, CompositionTreeNode, MasterConf};
+
+/// Evaluates the composition tree for every pixel of a `width` x `height` image at the supplied point in time.  The
+/// tree's global configuration is applied just like it is in the engine, so `time` is in units of engine ticks.
+/// Values are returned in row-major order.
+pub fn render_values(tree: &CompositionTree, width: usize, height: usize, time: f64) -> Vec<f64> {
+    let mut values = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            values.push(tree.get([x as f64, y as f64, time]));
+        }
+    }
+
+    values
+}
+
+/// Same as `render_values`, but evaluates the tree using 2D noise.  This is cheaper for static images, but the output
+/// differs from that of 3D evaluation at any point in time.
+pub fn render_values_2d(tree: &CompositionTree, width: usize, height: usize) -> Vec<f64> {
+    let mut values = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            values.push(NoiseFn::<Point2<f64>>::get(tree, [x as f64, y as f64]));
+        }
+    }
+
+    values
+}
+
+/// Determines how seamlessly tileable images are produced.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TileMode {
+    /// Maps the two axes of the tile onto two circles in 4D space, forming a torus.  This produces the best results
+    /// but requires every module in the tree to support 4D evaluation.
+    Torus,
+    /// Blends each sample with samples offset by one period along each axis so that opposite edges match.  Works
+    /// with any tree, but reduces contrast towards the center of the tile.
+    BlendedEdges,
+}
+
+impl TileMode {
+    /// Returns the best tiling mode supported by the provided tree.
+    pub fn best_for(tree: &CompositionTree) -> Self {
+        if tree.rendered_root().supports_4d() {
+            TileMode::Torus
+        } else {
+            TileMode::BlendedEdges
+        }
+    }
+}
+
+/// Configuration for rendering tileable images.  Periods are in pixels; an image with the same width and height as
+/// the periods wraps seamlessly on both axes.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct TileConf {
+    pub mode: TileMode,
+    pub period_x: f64,
+    pub period_y: f64,
+}
+
+/// Returns the value of the tree at the given pixel, mapped onto a torus so that it repeats every period.  The radii
+/// are chosen so that the circumference of each circle matches the length of its period at the current zoom.  Time
+/// moves the center of the torus along the Z and W axes, which keeps the output tileable while animating.
+fn sample_torus(tree: &CompositionTree, x: f64, y: f64, time: f64, conf: &TileConf) -> f64 {
+    let MasterConf {
+        zoom,
+        speed,
+        x_offset,
+        y_offset,
+        z_offset,
+        ..
+    } = tree.global_conf;
+    let (x_angle, y_angle) = (
+        2. * PI * x / conf.period_x,
+        2. * PI * y / conf.period_y,
+    );
+    let (x_radius, y_radius) = (
+        conf.period_x * zoom / (2. * PI),
+        conf.period_y * zoom / (2. * PI),
+    );
+    let time_offset = (time * speed) + z_offset;
+
+    let coord: Point4<f64> = [
+        x_offset + x_radius * x_angle.cos(),
+        y_offset + y_radius * y_angle.cos(),
+        time_offset + x_radius * x_angle.sin(),
+        time_offset + y_radius * y_angle.sin(),
+    ];
+    tree.normalize(tree.rendered_root().get(coord.clamp_coords()))
+}
+
+/// Returns the value of the tree at the given pixel, bilinearly blended with the values one period away on each axis.
+/// At the start of a period the sample is unchanged and at its end it consists entirely of the offset sample, which is
+/// the same point as the start of the period.
+fn sample_blended(tree: &CompositionTree, x: f64, y: f64, time: f64, conf: &TileConf) -> f64 {
+    let (x, y) = (x % conf.period_x, y % conf.period_y);
+    let (x_weight, y_weight) = (x / conf.period_x, y / conf.period_y);
+    let (x_shifted, y_shifted) = (x - conf.period_x, y - conf.period_y);
+
+    tree.get([x, y, time]) * (1. - x_weight) * (1. - y_weight)
+        + tree.get([x_shifted, y, time]) * x_weight * (1. - y_weight)
+        + tree.get([x, y_shifted, time]) * (1. - x_weight) * y_weight
+        + tree.get([x_shifted, y_shifted, time]) * x_weight * y_weight
+}
+
+/// Same as `render_values`, but the output repeats seamlessly along both axes with the periods set in `conf`.
+pub fn render_tileable_values(
+    tree: &CompositionTree,
+    width: usize,
+    height: usize,
+    time: f64,
+    conf: &TileConf,
+) -> Result<Vec<f64>, String> {
+    if !(conf.period_x > 0.) || !(conf.period_y > 0.) {
+        return Err("Tile periods must be greater than zero!".into());
+    }
+
+    let sample: fn(&CompositionTree, f64, f64, f64, &TileConf) -> f64 = match conf.mode {
+        TileMode::Torus => {
+            if !tree.rendered_root().supports_4d() {
+                return Err(String::from(
+                    "Torus tiling requires all modules in the tree to support 4D evaluation; use blended edges instead.",
+                ));
+            }
+            sample_torus
+        }
+        TileMode::BlendedEdges => sample_blended,
+    };
+
+    let mut values = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            values.push(sample(tree, x as f64, y as f64, time, conf));
+        }
+    }
+
+    Ok(values)
+}
+
+/// Determines how seamlessly looping animations are produced.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LoopMode {
+    /// Maps time onto a circle in the Z and W dimensions so that the end of the period is the same point as its
+    /// start.  Requires every module in the tree to support 4D evaluation.
+    Circle,
+    /// Crossfades each frame with the frame one period earlier.  Works with any tree, but reduces contrast in the
+    /// middle of the period.
+    Crossfade,
+}
+
+impl LoopMode {
+    /// Returns the best looping mode supported by the provided tree.
+    pub fn best_for(tree: &CompositionTree) -> Self {
+        LoopMode::best_for_node(tree.rendered_root())
+    }
+
+    /// Returns the best looping mode supported by the subtree defined by the provided node.
+    pub fn best_for_node(node: &CompositionTreeNode) -> Self {
+        if node.supports_4d() {
+            LoopMode::Circle
+        } else {
+            LoopMode::Crossfade
+        }
+    }
+}
+
+/// Configuration for rendering looping animations.  The period is in the same units as engine ticks.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct LoopConf {
+    pub mode: LoopMode,
+    pub period: f64,
+}
+
+/// Same as `render_values`, but the output repeats every `conf.period` units of time.
+pub fn render_looped_values(
+    tree: &CompositionTree,
+    width: usize,
+    height: usize,
+    time: f64,
+    conf: &LoopConf,
+) -> Result<Vec<f64>, String> {
+    if !(conf.period > 0.) {
+        return Err("Loop period must be greater than zero!".into());
+    }
+
+    if conf.mode == LoopMode::Circle && !tree.rendered_root().supports_4d() {
+        return Err(String::from(
+            "Circular looping requires all modules in the tree to support 4D evaluation; use crossfading instead.",
+        ));
+    }
+
+    let mut values = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            values.push(tree.normalize(tree.get_looped([x as f64, y as f64, time], conf)));
+        }
+    }
+
+    Ok(values)
+}
+
+/// Maps each of the provided values to a color using the supplied color function, returning RGBA pixel data.
+pub fn colorize_values(values: &[f64], color_fn: ColorFunction) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(values.len() * 4);
+    for &val in values {
+        pixels.extend_from_slice(&color_fn.colorize(val as f32));
+    }
+
+    pixels
+}
+
+/// Converts a `width` x `height` buffer of values into RGBA pixel data, visualizing their relief if a relief mode is
+/// supplied and mapping them through the color function otherwise.
+pub fn shade_values(
+    values: &[f64],
+    width: usize,
+    height: usize,
+    color_fn: ColorFunction,
+    relief_mode: Option<ReliefMode>,
+) -> Vec<u8> {
+    match relief_mode {
+        Some(mode) => render_relief(values, width, height, mode),
+        None => colorize_values(values, color_fn),
+    }
+}
+
+/// Renders the composition tree into a buffer of RGBA pixel data using the supplied color function, or the relief mode
+/// set in the tree's global configuration if there is one.
+pub fn render_rgba(
+    tree: &CompositionTree,
+    color_fn: ColorFunction,
+    width: usize,
+    height: usize,
+    time: f64,
+) -> Vec<u8> {
+    let values = render_values(tree, width, height, time);
+    shade_values(&values, width, height, color_fn, tree.global_conf.relief_mode)
+}
+
+/// Renders the subtree at the supplied coordinates into a `size` x `size` buffer of RGBA pixel data using the supplied
+/// color function.  The preview covers the same area as a `canvas_size` x `canvas_size` rendering of the whole tree, so
+/// it shows what the subtree contributes to the visible output at that point in time.
+pub fn render_node_preview(
+    tree: &CompositionTree,
+    coords: &[i32],
+    color_fn: ColorFunction,
+    canvas_size: usize,
+    size: usize,
+    time: f64,
+) -> Result<Vec<u8>, String> {
+    let node = tree.root_node.traverse(coords)?;
+    let scale = canvas_size as f64 / size as f64;
+
+    let mut values = Vec::with_capacity(size * size);
+    for y in 0..size {
+        for x in 0..size {
+            values.push(tree.get_node(node, [x as f64 * scale, y as f64 * scale, time]));
+        }
+    }
+
+    Ok(colorize_values(&values, color_fn))
+}
+
+/// Encodes a buffer of RGBA pixel data as a PNG image and writes it into the supplied writer.
+pub fn write_png<W: Write>(writer: W, width: usize, height: usize, rgba: &[u8]) -> Result<(), String> {
+    if rgba.len() != width * height * 4 {
+        return Err(format!(
+            "Pixel buffer has length {} but a {}x{} RGBA image requires {} bytes!",
+            rgba.len(),
+            width,
+            height,
+            width * height * 4
+        ));
+    }
+
+    let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+    encoder.set(png::ColorType::RGBA).set(png::BitDepth::Eight);
+    let mut png_writer = encoder
+        .write_header()
+        .map_err(|err| format!("Unable to write PNG header: {}", err))?;
+
+    png_writer
+        .write_image_data(rgba)
+        .map_err(|err| format!("Unable to write PNG image data: {}", err))
+}
@@ -0,0 +1,246 @@
+//! Statistical analysis of the values produced by a composition tree.  Compositions often only span a small part of
+//! the [-1, 1] range that the color functions expect, so this makes it possible to find out what range a composition
+//! actually covers and to normalize its output to the full range.
+
+use std::cmp::Ordering;
+use std::f64;
+
+use render::LoopMode;
+use CompositionTree;
+
+/// The percentiles that are computed if none are specified
+pub const DEFAULT_PERCENTILES: [f64; 7] = [1., 5., 25., 50., 75., 95., 99.];
+/// The number of ticks that are sampled when measuring normalization bounds for trees that don't loop
+pub const DEFAULT_NORMALIZATION_TIME_SPAN: f64 = 1000.;
+/// The size of the region in pixels that is sampled when measuring normalization bounds if the canvas size is unknown
+pub const DEFAULT_NORMALIZATION_REGION_SIZE: f64 = 256.;
+
+/// Determines which values of a composition tree are sampled for analysis.  The region is given in pixel coordinates
+/// and the time range in engine ticks, just like the inputs to `CompositionTree::get`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnalysisConf {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    /// The number of samples taken along each axis of the region
+    pub resolution: usize,
+    pub start_time: f64,
+    pub end_time: f64,
+    /// The number of points in time that are sampled between `start_time` and `end_time`, inclusive
+    pub time_samples: usize,
+    /// The number of buckets in the produced histogram
+    pub bins: usize,
+    /// The percentiles to compute, each between 0 and 100
+    pub percentiles: Vec<f64>,
+}
+
+impl Default for AnalysisConf {
+    fn default() -> Self {
+        AnalysisConf {
+            x: 0.,
+            y: 0.,
+            width: DEFAULT_NORMALIZATION_REGION_SIZE,
+            height: DEFAULT_NORMALIZATION_REGION_SIZE,
+            resolution: 64,
+            start_time: 0.,
+            end_time: 0.,
+            time_samples: 1,
+            bins: 32,
+            percentiles: DEFAULT_PERCENTILES.to_vec(),
+        }
+    }
+}
+
+impl AnalysisConf {
+    /// Returns the configuration used to measure the bounds used for auto-normalization.  This covers the visible
+    /// canvas over one loop period, or over `DEFAULT_NORMALIZATION_TIME_SPAN` ticks if the tree doesn't loop.
+    pub fn for_normalization(tree: &CompositionTree) -> Self {
+        let size = if tree.global_conf.canvas_size > 0 {
+            tree.global_conf.canvas_size as f64
+        } else {
+            DEFAULT_NORMALIZATION_REGION_SIZE
+        };
+
+        AnalysisConf {
+            width: size,
+            height: size,
+            resolution: 32,
+            end_time: tree
+                .global_conf
+                .loop_period
+                .unwrap_or(DEFAULT_NORMALIZATION_TIME_SPAN),
+            time_samples: 8,
+            bins: 1,
+            percentiles: Vec::new(),
+            ..AnalysisConf::default()
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.resolution == 0 || self.time_samples == 0 || self.bins == 0 {
+            return Err(String::from(
+                "The resolution, number of time samples, and number of bins must all be at least 1!",
+            ));
+        }
+        if self.width < 0. || self.height < 0. || self.end_time < self.start_time {
+            return Err(String::from(
+                "The sampled region and time range must not be negative!",
+            ));
+        }
+        if let Some(p) = self
+            .percentiles
+            .iter()
+            .find(|&&p| p < 0. || p > 100. || p.is_nan())
+        {
+            return Err(format!(
+                "Percentiles must be between 0 and 100, but got {}!",
+                p
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns `count` evenly spaced values covering `[start, start + span]`.  A single sample is taken at `start`.
+fn sample_positions(start: f64, span: f64, count: usize) -> Vec<f64> {
+    if count == 1 {
+        return vec![start];
+    }
+
+    (0..count)
+        .map(|i| start + span * (i as f64 / (count - 1) as f64))
+        .collect()
+}
+
+/// A single computed percentile
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Percentile {
+    pub percentile: f64,
+    pub value: f64,
+}
+
+/// The distribution of values split into equally sized buckets between `min` and `max`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Histogram {
+    pub min: f64,
+    pub max: f64,
+    pub counts: Vec<usize>,
+}
+
+/// Summary of a set of sampled values.  `NaN`s are ignored and counted separately.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ValueStats {
+    pub sample_count: usize,
+    pub nan_count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub std_dev: f64,
+    pub percentiles: Vec<Percentile>,
+    pub histogram: Histogram,
+}
+
+/// Returns the value at the supplied percentile of sorted values, linearly interpolating between the closest ranks.
+fn percentile_of_sorted(sorted: &[f64], percentile: f64) -> f64 {
+    if sorted.is_empty() {
+        return f64::NAN;
+    }
+
+    let rank = (percentile / 100.) * (sorted.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    let weight = rank - lower as f64;
+    sorted[lower] * (1. - weight) + sorted[upper] * weight
+}
+
+/// Computes statistics for the supplied values, splitting them into `bins` buckets for the histogram.
+pub fn compute_stats(values: &[f64], bins: usize, percentiles: &[f64]) -> ValueStats {
+    let mut sorted: Vec<f64> = values.iter().cloned().filter(|val| !val.is_nan()).collect();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    let count = sorted.len();
+
+    let (min, max) = match (sorted.first(), sorted.last()) {
+        (Some(&min), Some(&max)) => (min, max),
+        _ => (f64::NAN, f64::NAN),
+    };
+    let mean = sorted.iter().sum::<f64>() / count as f64;
+    let variance = sorted.iter().map(|val| (val - mean).powi(2)).sum::<f64>() / count as f64;
+
+    let mut counts = vec![0; bins.max(1)];
+    let range = max - min;
+    for &val in &sorted {
+        // the maximum value is included in the last bucket
+        let bin = if range > 0. {
+            (((val - min) / range) * counts.len() as f64) as usize
+        } else {
+            0
+        };
+        let last = counts.len() - 1;
+        counts[bin.min(last)] += 1;
+    }
+
+    ValueStats {
+        sample_count: count,
+        nan_count: values.len() - count,
+        min,
+        max,
+        mean,
+        std_dev: variance.sqrt(),
+        percentiles: percentiles
+            .iter()
+            .map(|&percentile| Percentile {
+                percentile,
+                value: percentile_of_sorted(&sorted, percentile),
+            })
+            .collect(),
+        histogram: Histogram { min, max, counts },
+    }
+}
+
+impl CompositionTree {
+    /// Samples the tree over the region and time range of the supplied configuration and computes statistics of the
+    /// produced values.  Values are sampled before any auto-normalization is applied.
+    pub fn analyze(&self, conf: &AnalysisConf) -> Result<ValueStats, String> {
+        conf.validate()?;
+
+        let xs = sample_positions(conf.x, conf.width, conf.resolution);
+        let ys = sample_positions(conf.y, conf.height, conf.resolution);
+        let times = sample_positions(
+            conf.start_time,
+            conf.end_time - conf.start_time,
+            conf.time_samples,
+        );
+
+        let mut values = Vec::with_capacity(xs.len() * ys.len() * times.len());
+        for &time in &times {
+            for &y in &ys {
+                for &x in &xs {
+                    values.push(self.get_raw([x, y, time]));
+                }
+            }
+        }
+
+        Ok(compute_stats(&values, conf.bins, &conf.percentiles))
+    }
+
+    /// Measures the bounds of the tree's output if auto-normalization is enabled and stores them in the global
+    /// configuration, clearing them otherwise.  The looping mode that the output is measured with is selected first.
+    /// This must be called whenever the tree or its configuration changes.
+    pub fn update_normalization(&mut self) {
+        let loop_mode = self
+            .global_conf
+            .loop_period
+            .map(|_| LoopMode::best_for(self));
+        self.global_conf.loop_mode = loop_mode;
+        self.global_conf.normalization_bounds = if self.global_conf.auto_normalize {
+            self.analyze(&AnalysisConf::for_normalization(self))
+                .ok()
+                .map(|stats| (stats.min, stats.max))
+                .filter(|&(min, max)| max > min)
+        } else {
+            None
+        };
+    }
+}
@@ -0,0 +1,236 @@
+//! Golden image tests.  A curated set of composition trees is rendered at a small size with every color function, and
+//! the output is compared to reference images committed in `src/tests/golden`.  Each reference image contains the
+//! renders of a single tree with each of the color functions side by side.
+//!
+//! After an intentional change to the rendered output, re-bless the references by running the tests with the
+//! `BLESS_GOLDEN` environment variable set: `BLESS_GOLDEN=1 cargo test golden`.  Review the changed images before
+//! committing them.
+
+use std::env;
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+use png;
+
+use color_schemes::ColorFunction;
+use composition::CompositionScheme;
+use conf::{InteropRangeFunction, NoiseModuleConf};
+use definition::{
+    CompositionTreeDefinition, CompositionTreeNodeDefinition, InputTransformationDefinition,
+    NoiseModuleType,
+};
+use initial_tree::create_initial_tree_definition;
+use render::{render_rgba, write_png};
+use util::Dim;
+use {CompositionTree, MasterConf};
+
+/// The width and height of each render
+const SIZE: usize = 24;
+/// The point in time at which the trees are rendered
+const TIME: f64 = 5.;
+/// The largest difference allowed between a channel of a rendered pixel and the reference.  This absorbs differences
+/// in floating point rounding between platforms.
+const TOLERANCE: u8 = 2;
+
+fn references_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/tests/golden")
+}
+
+fn leaf(
+    module_type: NoiseModuleType,
+    module_conf: Vec<NoiseModuleConf>,
+    transformations: Vec<InputTransformationDefinition>,
+) -> CompositionTreeNodeDefinition {
+    CompositionTreeNodeDefinition::Leaf {
+        module_type,
+        module_conf,
+        transformations,
+    }
+}
+
+fn multifractal(octaves: u32) -> NoiseModuleConf {
+    NoiseModuleConf::MultiFractal {
+        octaves,
+        frequency: 1.5,
+        lacunarity: 2.,
+        persistence: 0.5,
+    }
+}
+
+fn zoomed_def(root_node: CompositionTreeNodeDefinition) -> CompositionTreeDefinition {
+    CompositionTreeDefinition {
+        global_conf: MasterConf {
+            zoom: 0.1,
+            x_offset: 3.,
+            ..MasterConf::default()
+        },
+        root_node,
+    }
+}
+
+/// The trees that are rendered, chosen to cover each kind of node, input transformation, and composition scheme
+fn golden_definitions() -> Vec<(&'static str, CompositionTreeDefinition)> {
+    vec![
+        ("initial", create_initial_tree_definition()),
+        (
+            "fbm",
+            zoomed_def(leaf(
+                NoiseModuleType::Fbm,
+                vec![multifractal(4)],
+                vec![InputTransformationDefinition::ZoomScale {
+                    speed: 2.,
+                    zoom: 0.5,
+                }],
+            )),
+        ),
+        (
+            "worley_ridged",
+            zoomed_def(CompositionTreeNodeDefinition::Composed {
+                scheme: CompositionScheme::Average,
+                children: vec![
+                    leaf(
+                        NoiseModuleType::Worley,
+                        vec![NoiseModuleConf::Worley {
+                            range_function: InteropRangeFunction::Manhattan,
+                            range_function_enabled: true,
+                            worley_frequency: 1.,
+                            displacement: 0.5,
+                        }],
+                        Vec::new(),
+                    ),
+                    leaf(
+                        NoiseModuleType::RidgedMulti,
+                        vec![multifractal(3)],
+                        Vec::new(),
+                    ),
+                ],
+                transformations: vec![InputTransformationDefinition::ScaleAll(1.3)],
+            }),
+        ),
+        (
+            "honf",
+            zoomed_def(leaf(
+                NoiseModuleType::Billow,
+                vec![NoiseModuleConf::Seedable {
+                    seed: "golden".into(),
+                }],
+                vec![InputTransformationDefinition::HigherOrderNoiseModule {
+                    node_def: leaf(NoiseModuleType::SuperSimplex, Vec::new(), Vec::new()),
+                    replaced_dim: Dim::Z,
+                }],
+            )),
+        ),
+    ]
+}
+
+/// Renders the tree with each of the color functions and places the renders side by side in a single image.
+fn render_strip(tree: &CompositionTree) -> Vec<u8> {
+    let renders: Vec<Vec<u8>> = ColorFunction::ALL
+        .iter()
+        .map(|&color_fn| render_rgba(tree, color_fn, SIZE, SIZE, TIME))
+        .collect();
+
+    let mut strip = Vec::with_capacity(renders.len() * SIZE * SIZE * 4);
+    for y in 0..SIZE {
+        for render in &renders {
+            strip.extend_from_slice(&render[y * SIZE * 4..(y + 1) * SIZE * 4]);
+        }
+    }
+
+    strip
+}
+
+/// Reads a reference image, returning its width, height, and RGBA pixel data.
+fn read_reference(path: &PathBuf) -> Result<(usize, usize, Vec<u8>), String> {
+    let file = File::open(path).map_err(|err| format!("{}", err))?;
+    let (info, mut reader) = png::Decoder::new(file)
+        .read_info()
+        .map_err(|err| format!("Unable to read PNG header: {}", err))?;
+    if info.color_type != png::ColorType::RGBA || info.bit_depth != png::BitDepth::Eight {
+        return Err(String::from("Reference images must be 8-bit RGBA"));
+    }
+
+    let mut pixels = vec![0; info.buffer_size()];
+    reader
+        .next_frame(&mut pixels)
+        .map_err(|err| format!("Unable to read PNG image data: {}", err))?;
+    Ok((info.width as usize, info.height as usize, pixels))
+}
+
+/// Compares the rendered strip to the reference, returning a description of the differences if there are any.
+fn compare(rendered: &[u8], reference: &[u8]) -> Option<String> {
+    let strip_width = SIZE * ColorFunction::ALL.len();
+    let mut mismatches = 0;
+    let mut first_mismatch = None;
+
+    for (i, (a, b)) in rendered.chunks(4).zip(reference.chunks(4)).enumerate() {
+        if a.iter()
+            .zip(b)
+            .all(|(&a, &b)| (a as i16 - b as i16).abs() <= TOLERANCE as i16)
+        {
+            continue;
+        }
+
+        mismatches += 1;
+        if first_mismatch.is_none() {
+            let (x, y) = (i % strip_width, i / strip_width);
+            first_mismatch = Some(format!(
+                "({}, {}) with color function {}: rendered {:?}, expected {:?}",
+                x % SIZE,
+                y,
+                ColorFunction::ALL[x / SIZE],
+                a,
+                b
+            ));
+        }
+    }
+
+    first_mismatch.map(|first| format!("{} pixels differ; the first is at {}", mismatches, first))
+}
+
+#[test]
+fn golden_images() {
+    let bless = env::var_os("BLESS_GOLDEN").is_some();
+    let dir = references_dir();
+    if bless {
+        fs::create_dir_all(&dir).unwrap();
+    }
+
+    let mut failures = Vec::new();
+    for (name, def) in golden_definitions() {
+        let tree: CompositionTree = def.into();
+        let rendered = render_strip(&tree);
+        let path = dir.join(format!("{}.png", name));
+        let width = SIZE * ColorFunction::ALL.len();
+
+        if bless {
+            let file = File::create(&path).unwrap();
+            write_png(file, width, SIZE, &rendered).unwrap();
+            continue;
+        }
+
+        match read_reference(&path) {
+            Ok((ref_width, ref_height, _)) if (ref_width, ref_height) != (width, SIZE) => failures
+                .push(format!(
+                    "{}: reference is {}x{} but the render is {}x{}",
+                    name, ref_width, ref_height, width, SIZE
+                )),
+            Ok((_, _, reference)) => {
+                if let Some(diff) = compare(&rendered, &reference) {
+                    failures.push(format!("{}: {}", name, diff));
+                }
+            }
+            Err(err) => failures.push(format!(
+                "{}: unable to read reference image {:?}: {}",
+                name, path, err
+            )),
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "Rendered output doesn't match the golden images:\n{}\n\nIf the change is intentional, re-bless the references \
+         by running `BLESS_GOLDEN=1 cargo test golden`.",
+        failures.join("\n")
+    );
+}
@@ -0,0 +1,997 @@
+//! Tests for the composition tree and rendering.
+
+mod golden;
+mod properties;
+
+use std::convert::{TryFrom, TryInto};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use noise::NoiseFn;
+use png::{self, HasParameters};
+use serde_json::{self, Value};
+
+use animation::{encode_animation, AnimationConf, AnimationFormat};
+use budget::FrameBudget;
+use color_schemes::ColorFunction;
+use composition::CompositionScheme;
+use conf::{InteropRangeFunction, NoiseModuleConf};
+use definition::{
+    CompositionTreeDefinition, CompositionTreeNodeDefinition, InputTransformationDefinition,
+    NoiseModuleType,
+};
+use heightmap::{remap_values, write_heightmap, HeightmapFormat, ValueRemap};
+use history::{EditLog, TreeEdit};
+use initial_tree::{create_initial_tree, create_initial_tree_definition};
+use ir::{build_ir_from_def, IrNode, IrSetting, IrValue};
+use json_schema::{definition_schema, ir_schema};
+use limits::{DefinitionMetrics, ResourceLimits};
+use mesh::{build_mesh, write_mesh, MeshConf, MeshFormat};
+use relief::{render_relief, ReliefMode};
+use render::{
+    colorize_values, render_looped_values, render_node_preview, render_tileable_values,
+    render_values_2d, LoopConf, LoopMode, TileConf, TileMode,
+};
+use stats::{compute_stats, AnalysisConf};
+use util::{
+    build_def_from_ir, build_noise_module_settings, try_build_tree_from_def, BuildStage, Dim,
+};
+use {CompositionTree, MasterConf};
+
+const TILE_SIZE: usize = 32;
+
+/// Builds a tree consisting of a single leaf module with the supplied configuration.
+fn build_single_leaf_tree(
+    module_type: NoiseModuleType,
+    module_conf: Vec<NoiseModuleConf>,
+) -> CompositionTree {
+    let def = CompositionTreeDefinition {
+        global_conf: MasterConf::default(),
+        root_node: CompositionTreeNodeDefinition::Composed {
+            scheme: CompositionScheme::Average,
+            children: vec![CompositionTreeNodeDefinition::Leaf {
+                module_type,
+                module_conf,
+                transformations: Vec::new(),
+            }],
+            transformations: Vec::new(),
+        },
+    };
+
+    def.into()
+}
+
+fn build_super_simplex_tree() -> CompositionTree {
+    build_single_leaf_tree(
+        NoiseModuleType::SuperSimplex,
+        vec![NoiseModuleConf::Seedable {
+            seed: "tileable".into(),
+        }],
+    )
+}
+
+/// Renders one pixel more than the tile period on each axis so that the last row and column should be the same as
+/// the first, then asserts that they are.
+fn assert_edges_match(tree: &CompositionTree, mode: TileMode) {
+    let conf = TileConf {
+        mode,
+        period_x: TILE_SIZE as f64,
+        period_y: TILE_SIZE as f64,
+    };
+    let size = TILE_SIZE + 1;
+    let values = render_tileable_values(tree, size, size, 12.0, &conf).unwrap();
+    let get = |x: usize, y: usize| values[y * size + x];
+
+    for i in 0..size {
+        let (left, right) = (get(0, i), get(TILE_SIZE, i));
+        assert!(
+            (left - right).abs() < 1e-9,
+            "Left and right edges differ at row {}: {} != {}",
+            i,
+            left,
+            right
+        );
+
+        let (top, bottom) = (get(i, 0), get(i, TILE_SIZE));
+        assert!(
+            (top - bottom).abs() < 1e-9,
+            "Top and bottom edges differ at column {}: {} != {}",
+            i,
+            top,
+            bottom
+        );
+    }
+}
+
+#[test]
+fn torus_tiling_edges_match() {
+    let tree = create_initial_tree();
+    assert_eq!(TileMode::best_for(&tree), TileMode::Torus);
+    assert_edges_match(&tree, TileMode::Torus);
+}
+
+#[test]
+fn blended_tiling_edges_match() {
+    assert_edges_match(&create_initial_tree(), TileMode::BlendedEdges);
+
+    let tree = build_super_simplex_tree();
+    assert_eq!(TileMode::best_for(&tree), TileMode::BlendedEdges);
+    assert_edges_match(&tree, TileMode::BlendedEdges);
+}
+
+#[test]
+fn torus_tiling_requires_4d() {
+    let conf = TileConf {
+        mode: TileMode::Torus,
+        period_x: TILE_SIZE as f64,
+        period_y: TILE_SIZE as f64,
+    };
+    assert!(render_tileable_values(&build_super_simplex_tree(), 4, 4, 0.0, &conf).is_err());
+}
+
+#[test]
+fn relief_of_flat_and_sloped_heightfields() {
+    let flat = vec![0.25; 9];
+    let normals = render_relief(&flat, 3, 3, ReliefMode::NormalMap { strength: 1. });
+    assert_eq!(&normals[16..20], &[128, 128, 255, 255]);
+    let slope = render_relief(&flat, 3, 3, ReliefMode::Slope { strength: 1. });
+    assert_eq!(slope[16], 0);
+
+    // rises towards the right of the image, so it faces left
+    let ramp: Vec<f64> = (0..9).map(|i| (i % 3) as f64).collect();
+    let normals = render_relief(&ramp, 3, 3, ReliefMode::NormalMap { strength: 1. });
+    assert!(normals[16] < 128);
+    assert_eq!(normals[17], 128);
+
+    let lit_from = |azimuth: f64| {
+        let mode = ReliefMode::Hillshade {
+            strength: 1.,
+            azimuth,
+            altitude: 45.,
+        };
+        render_relief(&ramp, 3, 3, mode)[16]
+    };
+    assert!(lit_from(270.) > lit_from(90.));
+}
+
+#[test]
+fn flat_mesh_with_skirt() {
+    let tree = build_single_leaf_tree(
+        NoiseModuleType::Constant,
+        vec![NoiseModuleConf::Constant { constant: 0.5 }],
+    );
+    let mut conf = MeshConf::new(4);
+    conf.extent = 3.;
+    conf.height_scale = 2.;
+    conf.skirt_depth = Some(1.);
+    let mesh = build_mesh(&tree, &conf).unwrap();
+
+    // 16 grid vertices plus a top and bottom vertex for each of the 12 border vertices
+    assert_eq!(mesh.positions.len(), 16 + 24);
+    // 18 grid triangles plus 2 for each of the 12 skirt segments
+    assert_eq!(mesh.indices.len(), (18 + 24) * 3);
+    assert_eq!(mesh.positions[0], [-1.5, 1., -1.5]);
+    assert_eq!(mesh.positions[15], [1.5, 1., 1.5]);
+    for normal in &mesh.normals[..16] {
+        assert_eq!(normal, &[0., 1., 0.]);
+    }
+    // the skirt hangs below the border, and its segments along the top edge face -Z
+    assert_eq!(mesh.positions[17], [-1.5, 0., -1.5]);
+    assert!(mesh.normals[18][2] < -0.9);
+
+    for &format in &[MeshFormat::Obj, MeshFormat::Stl, MeshFormat::Glb] {
+        let mut buf: Vec<u8> = Vec::new();
+        write_mesh(&mut buf, format, &mesh).unwrap();
+        match format {
+            MeshFormat::Stl => assert_eq!(buf.len(), 84 + 42 * 50),
+            MeshFormat::Glb => {
+                assert_eq!(&buf[..4], b"glTF");
+                assert_eq!(buf.len() % 4, 0);
+            }
+            MeshFormat::Obj => {}
+        }
+    }
+}
+
+/// A 3x2 heightmap spanning [-1, 1] with each row increasing from left to right
+const HEIGHTMAP_VALUES: [f64; 6] = [-1., -0.5, 0., 0.25, 0.5, 1.];
+
+fn encode_heightmap(format: HeightmapFormat, remap: ValueRemap) -> Vec<u8> {
+    let mut buf: Vec<u8> = Vec::new();
+    write_heightmap(&mut buf, format, 3, 2, &HEIGHTMAP_VALUES, remap).unwrap();
+    buf
+}
+
+/// Returns the position of the first occurrence of `needle` in `haystack`.
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> usize {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .unwrap_or_else(|| panic!("{:?} not found", String::from_utf8_lossy(needle)))
+}
+
+fn read_f32s_le(buf: &[u8]) -> Vec<f32> {
+    buf.chunks(4).map(LittleEndian::read_f32).collect()
+}
+
+#[test]
+fn heightmap_value_remapping() {
+    assert_eq!(
+        remap_values(&HEIGHTMAP_VALUES, ValueRemap::None),
+        HEIGHTMAP_VALUES.to_vec()
+    );
+
+    // the observed minimum and maximum map to the ends of [0, 1]
+    let auto = remap_values(&HEIGHTMAP_VALUES, ValueRemap::Auto);
+    assert_eq!((auto[0], auto[5]), (0., 1.));
+    assert_eq!(auto[2], 0.5);
+
+    let fixed: ValueRemap = "-2:2".parse().unwrap();
+    assert_eq!(fixed, ValueRemap::Fixed { min: -2., max: 2. });
+    let fixed = remap_values(&HEIGHTMAP_VALUES, fixed);
+    assert_eq!((fixed[0], fixed[2], fixed[5]), (0.25, 0.5, 0.75));
+
+    // a range without any extent can't be normalized
+    assert_eq!(remap_values(&[3., 3.], ValueRemap::Auto), vec![0., 0.]);
+}
+
+#[test]
+fn heightmap_encodings() {
+    let expected_f32: Vec<f32> = HEIGHTMAP_VALUES.iter().map(|&val| val as f32).collect();
+
+    // raw formats are headerless, little-endian, and in row-major order
+    let raw_f32 = encode_heightmap(HeightmapFormat::RawF32, ValueRemap::None);
+    assert_eq!(raw_f32.len(), 6 * 4);
+    assert_eq!(read_f32s_le(&raw_f32), expected_f32);
+    assert_eq!(&raw_f32[..4], &[0x00, 0x00, 0x80, 0xbf]);
+    let raw_f64 = encode_heightmap(HeightmapFormat::RawF64, ValueRemap::None);
+    assert_eq!(raw_f64.len(), 6 * 8);
+    let values_f64: Vec<f64> = raw_f64.chunks(8).map(LittleEndian::read_f64).collect();
+    assert_eq!(values_f64, HEIGHTMAP_VALUES.to_vec());
+
+    // PFM has a text header, and a negative scale marks the samples as little-endian.  Rows go from bottom to top.
+    let pfm = encode_heightmap(HeightmapFormat::Pfm, ValueRemap::None);
+    let header = b"Pf\n3 2\n-1.0\n";
+    assert_eq!(&pfm[..header.len()], header);
+    let pfm_values = read_f32s_le(&pfm[header.len()..]);
+    assert_eq!(pfm_values.len(), 6);
+    assert_eq!(&pfm_values[..3], &expected_f32[3..]);
+    assert_eq!(&pfm_values[3..], &expected_f32[..3]);
+
+    // EXR starts with the magic number and version 2, followed by the attributes of the header
+    let exr = encode_heightmap(HeightmapFormat::Exr, ValueRemap::None);
+    assert_eq!(&exr[..8], &[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+    let channels = find_bytes(&exr, b"channels\0chlist\0") + 16;
+    assert_eq!(LittleEndian::read_i32(&exr[channels..]), 19);
+    // a single `Y` channel of 32-bit floats (pixel type 2)
+    assert_eq!(&exr[channels + 4..channels + 6], b"Y\0");
+    assert_eq!(LittleEndian::read_i32(&exr[channels + 6..]), 2);
+    let compression = find_bytes(&exr, b"compression\0compression\0") + 24;
+    assert_eq!(LittleEndian::read_i32(&exr[compression..]), 1);
+    assert_eq!(exr[compression + 4], 0);
+    let data_window = find_bytes(&exr, b"dataWindow\0box2i\0") + 17 + 4;
+    let window: Vec<i32> = (0..4)
+        .map(|i| LittleEndian::read_i32(&exr[data_window + i * 4..]))
+        .collect();
+    assert_eq!(window, vec![0, 0, 2, 1]);
+    let line_order = find_bytes(&exr, b"lineOrder\0lineOrder\0") + 20;
+    assert_eq!(LittleEndian::read_i32(&exr[line_order..]), 1);
+    assert_eq!(exr[line_order + 4], 0);
+
+    // the header ends with a null byte after the last attribute, followed by an offset table with one entry per
+    // scanline.  Each scanline chunk contains its y coordinate, its size, and its samples.
+    let header_end = find_bytes(&exr, b"screenWindowWidth\0float\0") + 24 + 4 + 4 + 1;
+    let chunk_size = 4 + 4 + 3 * 4;
+    assert_eq!(exr.len(), header_end + 2 * 8 + 2 * chunk_size);
+    for y in 0..2 {
+        let offset = LittleEndian::read_u64(&exr[header_end + y * 8..]) as usize;
+        assert_eq!(offset, header_end + 2 * 8 + y * chunk_size);
+        assert_eq!(LittleEndian::read_i32(&exr[offset..]), y as i32);
+        assert_eq!(LittleEndian::read_i32(&exr[offset + 4..]), 3 * 4);
+        assert_eq!(
+            read_f32s_le(&exr[offset + 8..offset + chunk_size]),
+            &expected_f32[y * 3..(y + 1) * 3]
+        );
+    }
+
+    // 16-bit PNGs can't store arbitrary values, so [-1, 1] is mapped onto the full range of samples when no remapping
+    // is selected
+    let png16 = encode_heightmap(HeightmapFormat::Png16, ValueRemap::None);
+    let mut decoder = png::Decoder::new(&png16[..]);
+    // samples are stripped to 8 bits by default
+    decoder.set(png::Transformations::IDENTITY);
+    let (info, mut reader) = decoder.read_info().unwrap();
+    assert_eq!((info.width, info.height), (3, 2));
+    assert_eq!(info.color_type, png::ColorType::Grayscale);
+    assert_eq!(info.bit_depth, png::BitDepth::Sixteen);
+    let mut data = vec![0; info.buffer_size()];
+    reader.next_frame(&mut data).unwrap();
+    let samples: Vec<u16> = data.chunks(2).map(BigEndian::read_u16).collect();
+    assert_eq!(samples, vec![0, 16384, 32768, 40959, 49151, 65535]);
+}
+
+#[test]
+fn looped_values_are_continuous_across_periods() {
+    const PERIOD: f64 = 90.;
+    // Sampling exactly at the end of the period would wrap around to its start for crossfading, so the output just
+    // before the end is compared to the start instead.
+    const END: f64 = PERIOD - 1e-6;
+    let trees = vec![
+        (create_initial_tree(), LoopMode::Circle),
+        (build_super_simplex_tree(), LoopMode::Crossfade),
+    ];
+
+    for (mut tree, mode) in trees {
+        assert_eq!(LoopMode::best_for(&tree), mode);
+        let conf = LoopConf {
+            mode,
+            period: PERIOD,
+        };
+        let start = render_looped_values(&tree, 8, 8, 0., &conf).unwrap();
+        let end = render_looped_values(&tree, 8, 8, END, &conf).unwrap();
+        let middle = render_looped_values(&tree, 8, 8, PERIOD / 2., &conf).unwrap();
+
+        for i in 0..start.len() {
+            assert!(
+                (start[i] - end[i]).abs() < 1e-6,
+                "{:?}: {} != {}",
+                mode,
+                start[i],
+                end[i]
+            );
+        }
+        assert_ne!(start, middle);
+
+        // the mode is selected once when the loop period is set rather than for every sample
+        tree.global_conf.loop_period = Some(PERIOD);
+        tree.update_normalization();
+        assert_eq!(tree.global_conf.loop_mode, Some(mode));
+        for &(x, y) in &[(0., 0.), (13., 7.)] {
+            let (start, end) = (tree.get([x, y, 0.]), tree.get([x, y, END]));
+            assert!((start - end).abs() < 1e-6, "{} != {}", start, end);
+        }
+    }
+}
+
+#[test]
+fn loop_period_from_global_conf() {
+    for mut tree in vec![create_initial_tree(), build_super_simplex_tree()] {
+        tree.global_conf.loop_period = Some(60.);
+        tree.update_normalization();
+        for &(x, y) in &[(0., 0.), (13., 7.), (250., 31.)] {
+            let (start, end) = (tree.get([x, y, 15.]), tree.get([x, y, 75.]));
+            assert!((start - end).abs() < 1e-9, "{} != {}", start, end);
+        }
+    }
+}
+
+#[test]
+fn animation_encoding() {
+    let tree = create_initial_tree();
+    let conf = AnimationConf {
+        start_time: 0.,
+        end_time: 30.,
+        frame_count: 3,
+        fps: 30.,
+        seamless_loop: true,
+    };
+    assert_eq!(conf.frame_times(), vec![0., 10., 20.]);
+
+    let gif = encode_animation(
+        &tree,
+        ColorFunction::Oceanic,
+        16,
+        16,
+        AnimationFormat::Gif,
+        &conf,
+    )
+    .unwrap();
+    assert_eq!(&gif[..6], b"GIF89a");
+
+    let apng = encode_animation(
+        &tree,
+        ColorFunction::Oceanic,
+        16,
+        16,
+        AnimationFormat::Apng,
+        &conf,
+    )
+    .unwrap();
+    let count_chunks = |chunk_type: &[u8]| {
+        apng.windows(4)
+            .filter(|window| *window == chunk_type)
+            .count()
+    };
+    assert_eq!(count_chunks(b"acTL"), 1);
+    assert_eq!(count_chunks(b"fcTL"), 3);
+    assert_eq!(count_chunks(b"fdAT"), 2);
+}
+
+#[test]
+fn evaluation_in_2d_and_4d() {
+    let tree = create_initial_tree();
+    let values = render_values_2d(&tree, 8, 8);
+    assert!(values.iter().all(|val| val.is_finite()));
+    assert!(values.iter().any(|&val| val != values[0]));
+
+    // replacing W with the output of a HONF only affects 4D evaluation
+    let mut def = create_initial_tree_definition();
+    if let CompositionTreeNodeDefinition::Composed {
+        ref mut transformations,
+        ..
+    } = def.root_node
+    {
+        transformations.push(InputTransformationDefinition::HigherOrderNoiseModule {
+            node_def: CompositionTreeNodeDefinition::Leaf {
+                module_type: NoiseModuleType::Constant,
+                module_conf: vec![NoiseModuleConf::Constant { constant: 0.75 }],
+                transformations: Vec::new(),
+            },
+            replaced_dim: Dim::W,
+        });
+    }
+    let replaced: CompositionTree = def.into();
+
+    let coord3 = [10., 20., 30.];
+    assert_eq!(tree.get(coord3), replaced.get(coord3));
+    let coord4 = [10., 20., 30., 40.];
+    assert_ne!(tree.get(coord4), replaced.get(coord4));
+    assert_eq!(replaced.get(coord4), replaced.get([10., 20., 30., 0.]));
+}
+
+#[test]
+fn value_stats_and_auto_normalization() {
+    let values: Vec<f64> = (0..101).map(|i| i as f64 / 100.).collect();
+    let stats = compute_stats(&values, 4, &[50., 90.]);
+    assert_eq!((stats.min, stats.max, stats.sample_count), (0., 1., 101));
+    assert!((stats.mean - 0.5).abs() < 1e-9);
+    assert!((stats.percentiles[1].value - 0.9).abs() < 1e-9);
+    assert_eq!(stats.histogram.counts, vec![25, 25, 25, 26]);
+
+    let mut tree = build_super_simplex_tree();
+    let raw = tree.analyze(&AnalysisConf::default()).unwrap();
+    assert!(raw.min > -1. && raw.max < 1.);
+
+    tree.global_conf.auto_normalize = true;
+    tree.update_normalization();
+    assert!(tree.global_conf.normalization_bounds.is_some());
+    let normalized = compute_stats(&render_values_2d(&tree, 64, 64), 1, &[]);
+    assert!(normalized.min >= -1. && normalized.max <= 1.);
+    assert!(normalized.max - normalized.min > raw.max - raw.min);
+}
+
+#[test]
+fn subtree_preview_and_solo() {
+    let def = CompositionTreeDefinition {
+        global_conf: MasterConf::default(),
+        root_node: CompositionTreeNodeDefinition::Composed {
+            scheme: CompositionScheme::Average,
+            children: vec![
+                CompositionTreeNodeDefinition::Leaf {
+                    module_type: NoiseModuleType::Constant,
+                    module_conf: vec![NoiseModuleConf::Constant { constant: 0.5 }],
+                    transformations: Vec::new(),
+                },
+                CompositionTreeNodeDefinition::Leaf {
+                    module_type: NoiseModuleType::SuperSimplex,
+                    module_conf: vec![NoiseModuleConf::Seedable {
+                        seed: "tileable".into(),
+                    }],
+                    transformations: Vec::new(),
+                },
+            ],
+            transformations: Vec::new(),
+        },
+    };
+    let mut tree: CompositionTree = def.into();
+    let standalone = build_super_simplex_tree();
+
+    // previews cover the same area as the full canvas
+    let preview = render_node_preview(&tree, &[1], ColorFunction::Oceanic, 8, 4, 5.).unwrap();
+    let mut expected_values = Vec::new();
+    for y in 0..4 {
+        for x in 0..4 {
+            expected_values.push(standalone.get([x as f64 * 2., y as f64 * 2., 5.]));
+        }
+    }
+    assert_eq!(
+        preview,
+        colorize_values(&expected_values, ColorFunction::Oceanic)
+    );
+    assert!(render_node_preview(&tree, &[2], ColorFunction::Oceanic, 8, 4, 5.).is_err());
+
+    let coord = [3., 4., 5.];
+    let combined = tree.get(coord);
+    tree.solo(Some(&[0])).unwrap();
+    assert_eq!(tree.get(coord), 0.5);
+    tree.solo(Some(&[1])).unwrap();
+    assert_eq!(tree.get(coord), standalone.get(coord));
+    assert!(tree.solo(Some(&[0, 0])).is_err());
+    tree.solo(None).unwrap();
+    assert_eq!(tree.get(coord), combined);
+}
+
+#[test]
+fn pixel_probe_traces_every_node() {
+    let def = CompositionTreeDefinition {
+        global_conf: MasterConf::default(),
+        root_node: CompositionTreeNodeDefinition::Composed {
+            scheme: CompositionScheme::Average,
+            children: vec![
+                CompositionTreeNodeDefinition::Leaf {
+                    module_type: NoiseModuleType::Constant,
+                    module_conf: vec![NoiseModuleConf::Constant { constant: 0.5 }],
+                    transformations: Vec::new(),
+                },
+                CompositionTreeNodeDefinition::Leaf {
+                    module_type: NoiseModuleType::SuperSimplex,
+                    module_conf: Vec::new(),
+                    transformations: vec![InputTransformationDefinition::ScaleAll(2.)],
+                },
+            ],
+            transformations: Vec::new(),
+        },
+    };
+    let mut tree: CompositionTree = def.into();
+
+    let probe = tree.probe(3., 4., 5.);
+    assert_eq!(probe.value, tree.get([3., 4., 5.]));
+    assert_eq!(probe.samples.len(), 1);
+    let root = &probe.samples[0].root;
+    assert_eq!((root.kind, root.children.len()), ("composed", 2));
+    assert_eq!(root.output, probe.value);
+    assert_eq!(root.children[0].output, 0.5);
+    assert_eq!(root.children[1].path, vec![1]);
+    assert_eq!(root.children[1].input, root.transformed);
+    let scaled: Vec<f64> = root.transformed.iter().map(|val| val * 2.).collect();
+    assert_eq!(root.children[1].transformed, scaled);
+
+    // crossfaded looping blends two samples
+    tree.global_conf.loop_period = Some(10.);
+    let probe = tree.probe(3., 4., 5.);
+    assert_eq!(probe.samples.len(), 2);
+    assert_eq!(probe.value, tree.get([3., 4., 5.]));
+}
+
+#[test]
+fn undo_and_redo_edits() {
+    let def = create_initial_tree_definition();
+    let mut log = EditLog::new(def.root_node.clone());
+    let transformation_count = def.root_node.transformations().len();
+    let mut tree: CompositionTree = def.into();
+    let coord = [10., 20., 30.];
+    let original = tree.get(coord);
+
+    let constant = CompositionTreeNodeDefinition::Leaf {
+        module_type: NoiseModuleType::Constant,
+        module_conf: vec![NoiseModuleConf::Constant { constant: 0.75 }],
+        transformations: Vec::new(),
+    };
+    log.apply(
+        &mut tree,
+        TreeEdit::ReplaceNode {
+            parent: Vec::new(),
+            index: 0,
+            node: constant,
+        },
+    )
+    .unwrap();
+    let replaced = tree.get(coord);
+    assert_ne!(replaced, original);
+
+    log.apply(
+        &mut tree,
+        TreeEdit::AddInputTransformation {
+            node: Vec::new(),
+            index: 0,
+            transformation: InputTransformationDefinition::ScaleAll(3.),
+        },
+    )
+    .unwrap();
+    let scaled = tree.get(coord);
+    assert_ne!(scaled, replaced);
+    assert!(log
+        .apply(
+            &mut tree,
+            TreeEdit::DeleteNode {
+                parent: vec![0],
+                index: 0
+            }
+        )
+        .is_err());
+
+    // undoing returns the edit that was applied to reverse the change
+    match log.undo(&mut tree).unwrap() {
+        Some(TreeEdit::DeleteInputTransformation { index: 0, .. }) => (),
+        other => panic!("Unexpected undo edit: {:?}", other),
+    }
+    assert_eq!(tree.get(coord), replaced);
+    log.undo(&mut tree).unwrap();
+    assert_eq!(tree.get(coord), original);
+    assert!(log.undo(&mut tree).unwrap().is_none());
+
+    log.redo(&mut tree).unwrap();
+    log.redo(&mut tree).unwrap();
+    assert_eq!(tree.get(coord), scaled);
+    assert!(!log.can_redo());
+    assert_eq!(
+        log.definition().transformations().len(),
+        transformation_count + 1
+    );
+}
+
+#[test]
+fn batched_edits_are_atomic() {
+    let def = create_initial_tree_definition();
+    let mut log = EditLog::new(def.root_node.clone());
+    let child_count = match def.root_node {
+        CompositionTreeNodeDefinition::Composed { ref children, .. } => children.len(),
+        _ => panic!("The initial tree should have a composed root node!"),
+    };
+    let mut tree: CompositionTree = def.into();
+    let coord = [10., 20., 30.];
+    let original = tree.get(coord);
+
+    let constant = CompositionTreeNodeDefinition::Leaf {
+        module_type: NoiseModuleType::Constant,
+        module_conf: vec![NoiseModuleConf::Constant { constant: 0.75 }],
+        transformations: Vec::new(),
+    };
+    let replace = TreeEdit::ReplaceNode {
+        parent: Vec::new(),
+        index: 0,
+        node: constant.clone(),
+    };
+
+    // the second edit fails, so the first one must not be applied either
+    let failing = TreeEdit::Batch {
+        edits: vec![
+            replace.clone(),
+            TreeEdit::DeleteNode {
+                parent: Vec::new(),
+                index: child_count,
+            },
+        ],
+    };
+    assert!(log.apply(&mut tree, failing).is_err());
+    assert_eq!(tree.get(coord), original);
+    assert!(!log.can_undo());
+
+    let batch = TreeEdit::Batch {
+        edits: vec![
+            replace,
+            TreeEdit::AddNode {
+                parent: Vec::new(),
+                index: child_count,
+                node: constant,
+            },
+            TreeEdit::DeleteNode {
+                parent: Vec::new(),
+                index: child_count,
+            },
+        ],
+    };
+    assert_eq!(batch.target(), Vec::<i32>::new());
+    log.apply(&mut tree, batch).unwrap();
+    let edited = tree.get(coord);
+    assert_ne!(edited, original);
+
+    // the whole batch is undone in one step
+    log.undo(&mut tree).unwrap();
+    assert_eq!(tree.get(coord), original);
+    assert!(!log.can_undo());
+    log.redo(&mut tree).unwrap();
+    assert_eq!(tree.get(coord), edited);
+}
+
+/// Builds a chain of `depth` multifractal modules with the supplied number of octaves, each of which replaces the Z
+/// coordinate of its parent through a higher order noise function.
+fn nested_honf_node(depth: usize, octaves: u32) -> CompositionTreeNodeDefinition {
+    let transformations = if depth > 1 {
+        vec![InputTransformationDefinition::HigherOrderNoiseModule {
+            node_def: nested_honf_node(depth - 1, octaves),
+            replaced_dim: Dim::Z,
+        }]
+    } else {
+        Vec::new()
+    };
+
+    CompositionTreeNodeDefinition::Leaf {
+        module_type: NoiseModuleType::RidgedMulti,
+        module_conf: vec![NoiseModuleConf::MultiFractal {
+            octaves,
+            frequency: 1.,
+            lacunarity: 2.,
+            persistence: 0.5,
+        }],
+        transformations,
+    }
+}
+
+#[test]
+fn resource_limits() {
+    let limits = ResourceLimits::default();
+    let initial_metrics = DefinitionMetrics::measure(&create_initial_tree_definition().root_node);
+    assert_eq!(initial_metrics.depth, 2);
+    assert_eq!(initial_metrics.node_count, 3);
+    assert_eq!(initial_metrics.max_octaves, 6);
+    assert!((initial_metrics.cost - 12.3).abs() < 1e-9);
+    assert!(limits.check(&initial_metrics).is_ok());
+
+    // octaves past the maximum that modules support don't add to the cost
+    let metrics = DefinitionMetrics::measure(&nested_honf_node(20, 350));
+    assert_eq!(metrics.depth, 20);
+    assert_eq!(metrics.node_count, 20);
+    assert_eq!(metrics.max_octaves, 350);
+    assert!((metrics.cost - 20. * 32.1).abs() < 1e-9);
+    assert!(limits.check(&metrics).is_err());
+
+    // each of the limits is enforced on its own
+    assert!(limits.check_definition(&nested_honf_node(12, 8)).is_ok());
+    assert!(limits.check_definition(&nested_honf_node(17, 1)).is_err());
+    assert!(limits.check_definition(&nested_honf_node(1, 16)).is_ok());
+    assert!(limits.check_definition(&nested_honf_node(1, 17)).is_err());
+    assert!(limits.check_definition(&nested_honf_node(16, 16)).is_err());
+    let wide = CompositionTreeNodeDefinition::Composed {
+        scheme: CompositionScheme::Average,
+        children: (0..300).map(|_| nested_honf_node(1, 1)).collect(),
+        transformations: Vec::new(),
+    };
+    assert!(limits.check_definition(&wide).is_err());
+
+    // definitions that exceed the limits are rejected when they're built
+    let def = CompositionTreeDefinition {
+        global_conf: MasterConf::default(),
+        root_node: nested_honf_node(20, 350),
+    };
+    let ir = serde_json::to_string(&build_ir_from_def(&def)).unwrap();
+    match try_build_tree_from_def(&ir) {
+        Err(err) => assert_eq!(err.stage, BuildStage::Limits),
+        Ok(_) => panic!("The definition should have exceeded the resource limits"),
+    }
+}
+
+/// Builds a tree definition that contains every kind of node, input transformation, and setting.
+fn build_kitchen_sink_definition() -> CompositionTreeDefinition {
+    CompositionTreeDefinition {
+        global_conf: MasterConf {
+            relief_mode: Some(ReliefMode::Hillshade {
+                strength: 4.,
+                azimuth: 90.,
+                altitude: 30.,
+            }),
+            loop_period: Some(120.),
+            ..MasterConf::default()
+        },
+        root_node: CompositionTreeNodeDefinition::Composed {
+            scheme: CompositionScheme::WeightedAverage(vec![1., 2.]),
+            children: vec![
+                nested_honf_node(2, 4),
+                CompositionTreeNodeDefinition::Leaf {
+                    module_type: NoiseModuleType::Worley,
+                    module_conf: vec![
+                        NoiseModuleConf::Seedable {
+                            seed: "schema".into(),
+                        },
+                        NoiseModuleConf::Worley {
+                            range_function: InteropRangeFunction::Chebyshev,
+                            range_function_enabled: true,
+                            worley_frequency: 2.,
+                            displacement: 0.5,
+                        },
+                    ],
+                    transformations: Vec::new(),
+                },
+                CompositionTreeNodeDefinition::Leaf {
+                    module_type: NoiseModuleType::Constant,
+                    module_conf: vec![NoiseModuleConf::Constant { constant: 0.25 }],
+                    transformations: Vec::new(),
+                },
+            ],
+            transformations: vec![
+                InputTransformationDefinition::ZoomScale {
+                    speed: 1.,
+                    zoom: 2.,
+                },
+                InputTransformationDefinition::ScaleAll(0.5),
+            ],
+        },
+    }
+}
+
+/// Checks that the type of the node and the keys and enumerated values of its settings are listed in the IR schema.
+fn assert_ir_node_in_schema(definitions: &Value, node: &IrNode) {
+    let node_types = definitions["node"]["properties"]["type"]["enum"]
+        .as_array()
+        .unwrap();
+    assert!(node_types
+        .iter()
+        .any(|node_type| *node_type == node._type.as_str()));
+
+    let settings_definition = match node._type.as_str() {
+        "root" | "noiseModule" => "noiseModuleSetting",
+        "globalConf" => "globalConfSetting",
+        "inputTransformation" => "inputTransformationSetting",
+        "compositionScheme" => "compositionSchemeSetting",
+        _ => {
+            assert!(node.settings.is_empty());
+            ""
+        }
+    };
+    for setting in &node.settings {
+        let setting_schema = definitions[settings_definition]["oneOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|schema| schema["properties"]["key"]["const"] == setting.key.as_str())
+            .unwrap_or_else(|| {
+                panic!(
+                    "Setting {} of {} node isn't in the schema",
+                    setting.key, node._type
+                )
+            });
+        if let Some(values) = setting_schema["properties"]["value"]["enum"].as_array() {
+            assert!(
+                values.contains(&serde_json::to_value(&setting.value).unwrap()),
+                "Value {} of setting {} isn't in the schema",
+                setting.value,
+                setting.key
+            );
+        }
+    }
+
+    for child in &node.children {
+        assert_ir_node_in_schema(definitions, child);
+    }
+}
+
+#[test]
+fn json_schemas() {
+    let def_schema = definition_schema();
+    let definitions = &def_schema["definitions"];
+    assert_eq!(
+        definitions["NoiseModuleType"]["enum"]
+            .as_array()
+            .unwrap()
+            .len(),
+        NoiseModuleType::ALL.len()
+    );
+    assert_eq!(
+        definitions["ColorFunction"]["enum"]
+            .as_array()
+            .unwrap()
+            .len(),
+        ColorFunction::ALL.len()
+    );
+    assert!(definitions["CompositionScheme"].is_object());
+    assert!(definitions["InputTransformationDefinition"].is_object());
+
+    // a tree containing every kind of node and setting produces IR that matches the schema
+    let mut def = build_kitchen_sink_definition();
+    let schema = ir_schema();
+    for &color_fn in ColorFunction::ALL.iter() {
+        def.global_conf.color_function = color_fn;
+        assert_ir_node_in_schema(&schema["definitions"], &build_ir_from_def(&def));
+    }
+}
+
+/// Converts every setting of the IR tree into the legacy form, in which all values are encoded as strings.
+fn to_legacy_ir(node: &IrNode) -> IrNode {
+    let settings = node
+        .settings
+        .iter()
+        .map(|setting| {
+            let value = match setting.value {
+                IrValue::String(ref val) => val.clone(),
+                IrValue::Number(val) => val.to_string(),
+                IrValue::Bool(val) => val.to_string(),
+                ref val => val.to_string(),
+            };
+            IrSetting::new(&setting.key, value)
+        })
+        .collect();
+
+    IrNode::new(
+        &node._type,
+        settings,
+        node.children.iter().map(to_legacy_ir).collect(),
+    )
+}
+
+#[test]
+fn typed_and_legacy_ir_settings() {
+    let mut def = build_kitchen_sink_definition();
+    def.global_conf.color_function = ColorFunction::Cosmos;
+    let ir = build_ir_from_def(&def);
+    let ir_json = serde_json::to_value(&ir).unwrap();
+    assert_eq!(ir_json["children"][0]["settings"][0]["value"], json!(0.008));
+    assert!(ir_json
+        .to_string()
+        .contains(r#"{"key":"weights","value":[1.0,2.0]}"#));
+
+    // the typed and legacy forms of the IR build into identical trees
+    let (typed_color_fn, typed_def) = build_def_from_ir(ir.clone()).unwrap();
+    let (legacy_color_fn, legacy_def) = build_def_from_ir(to_legacy_ir(&ir)).unwrap();
+    assert_eq!(typed_color_fn, ColorFunction::Cosmos);
+    assert_eq!(legacy_color_fn, ColorFunction::Cosmos);
+    assert_eq!(
+        serde_json::to_value(&typed_def.global_conf).unwrap(),
+        serde_json::to_value(&legacy_def.global_conf).unwrap()
+    );
+    assert_eq!(
+        typed_def.global_conf.relief_mode,
+        def.global_conf.relief_mode
+    );
+    let (typed_tree, legacy_tree): (CompositionTree, CompositionTree) =
+        (typed_def.into(), legacy_def.into());
+    for &point in &[[0., 0., 0.], [1.5, -2.5, 3.], [40., 12., 0.25]] {
+        assert_eq!(typed_tree.get(point), legacy_tree.get(point));
+    }
+
+    // higher order noise functions can be supplied through a setting, either as a node or a JSON-encoded node
+    let inner: IrNode = (&nested_honf_node(1, 3)).into();
+    let honf = |node_value: IrValue| {
+        let transformation = IrNode::new(
+            "inputTransformation",
+            vec![
+                IrSetting::new("inputTransformationType", "honf"),
+                IrSetting::new("replacedDim", "y"),
+                IrSetting::new("noiseModule", node_value),
+            ],
+            Vec::new(),
+        );
+        let def: InputTransformationDefinition = transformation.try_into().unwrap();
+        serde_json::to_value(&def).unwrap()
+    };
+    let expected = serde_json::to_value(&InputTransformationDefinition::HigherOrderNoiseModule {
+        node_def: nested_honf_node(1, 3),
+        replaced_dim: Dim::Y,
+    })
+    .unwrap();
+    assert_eq!(honf(inner.clone().into()), expected);
+    assert_eq!(
+        honf(serde_json::to_string(&inner).unwrap().into()),
+        expected
+    );
+
+    // values of the wrong type are rejected
+    let settings = vec![
+        IrSetting::new("octaves", true),
+        IrSetting::new("frequency", 1.),
+        IrSetting::new("lacunarity", 2.),
+        IrSetting::new("persistence", 0.5),
+    ];
+    let err = build_noise_module_settings(settings).unwrap_err();
+    assert!(err.contains("octaves"), err);
+    let settings = vec![IrSetting::new("moduleType", 3u32)];
+    let node = IrNode::new("noiseModule", settings, Vec::new());
+    assert!(CompositionTreeNodeDefinition::try_from(node).is_err());
+}
+
+#[test]
+fn frame_budget_adapts_resolution() {
+    let mut budget = FrameBudget::new(Some(10.));
+
+    // 0.01ms per point only leaves enough time for 1/4 of the resolution
+    assert_eq!(budget.record(0.64, 8, 64, 64), 4);
+    assert_eq!(budget.stats().effective_width, 8);
+    assert_eq!(budget.stats().average_evaluation_time, 0.01);
+
+    // the resolution is raised as frames get cheaper to render
+    let mut scale = 4;
+    for _ in 0..32 {
+        scale = budget.record(0.001 * (64 * 64 / (scale * scale)) as f64, scale, 64, 64);
+    }
+    assert_eq!(scale, 1);
+    assert_eq!(budget.stats().effective_width, 64);
+
+    // and lowered again once they get more expensive
+    for _ in 0..32 {
+        scale = budget.record(0.1 * (64 * 64 / (scale * scale)) as f64, scale, 64, 64);
+    }
+    assert_eq!(scale, 8);
+    assert!((budget.stats().average_evaluation_time - 0.1).abs() < 1e-3);
+
+    // without a budget, frames are always refined to full resolution
+    budget.set_target_frame_time(None);
+    assert_eq!(budget.record(1000., 8, 64, 64), 1);
+}
@@ -0,0 +1,413 @@
+//! Property-based tests for converting between the IR, tree definitions, and built composition trees.  Definitions
+//! are parsed from untrusted user input on the backend, so these check that every valid definition survives the
+//! round trip through the IR and that arbitrary IR trees are rejected with errors rather than panics.
+
+use noise::NoiseFn;
+use proptest::collection::vec;
+use proptest::option;
+use proptest::prelude::*;
+use serde_json;
+
+use color_schemes::ColorFunction;
+use composition::CompositionScheme;
+use conf::{InteropRangeFunction, NoiseModuleConf};
+use definition::{
+    CompositionTreeDefinition, CompositionTreeNodeDefinition, InputTransformationDefinition,
+    NoiseModuleType,
+};
+use ir::{build_ir_from_def, IrNode, IrSetting, IrValue};
+use limits::ResourceLimits;
+use relief::ReliefMode;
+use util::{build_def_from_ir, build_tree_from_def, Dim};
+use {CompositionTree, MasterConf};
+
+/// Values with up to three decimal places like those entered into the frontend.  `serde_json` doesn't guarantee that
+/// floats with more significant digits than that survive being serialized and parsed again.
+fn arb_f64() -> BoxedStrategy<f64> {
+    (-1_000_000..1_000_000i32)
+        .prop_map(|val| val as f64 / 1000.)
+        .boxed()
+}
+
+fn arb_leaf_module_type() -> BoxedStrategy<NoiseModuleType> {
+    prop_oneof![
+        Just(NoiseModuleType::Fbm),
+        Just(NoiseModuleType::Worley),
+        Just(NoiseModuleType::OpenSimplex),
+        Just(NoiseModuleType::Billow),
+        Just(NoiseModuleType::HybridMulti),
+        Just(NoiseModuleType::SuperSimplex),
+        Just(NoiseModuleType::Value),
+        Just(NoiseModuleType::RidgedMulti),
+        Just(NoiseModuleType::BasicMulti),
+        Just(NoiseModuleType::Constant),
+    ]
+    .boxed()
+}
+
+fn arb_range_function() -> BoxedStrategy<InteropRangeFunction> {
+    prop_oneof![
+        Just(InteropRangeFunction::Euclidean),
+        Just(InteropRangeFunction::EuclideanSquared),
+        Just(InteropRangeFunction::Manhattan),
+        Just(InteropRangeFunction::Chebyshev),
+        Just(InteropRangeFunction::Quadratic),
+    ]
+    .boxed()
+}
+
+/// The settings of a leaf are grouped by the kind of configuration that they belong to, so a leaf has at most one
+/// configuration of each kind.  `MasterConf` isn't included since it's part of the `globalConf` node instead.
+fn arb_module_confs() -> BoxedStrategy<Vec<NoiseModuleConf>> {
+    let multifractal = (0..40u32, arb_f64(), arb_f64(), arb_f64()).prop_map(
+        |(octaves, frequency, lacunarity, persistence)| NoiseModuleConf::MultiFractal {
+            octaves,
+            frequency,
+            lacunarity,
+            persistence,
+        },
+    );
+    let seedable = "\\PC{0,16}".prop_map(|seed| NoiseModuleConf::Seedable { seed });
+    let worley = (arb_range_function(), any::<bool>(), arb_f64(), arb_f64()).prop_map(
+        |(range_function, range_function_enabled, worley_frequency, displacement)| {
+            NoiseModuleConf::Worley {
+                range_function,
+                range_function_enabled,
+                worley_frequency,
+                displacement,
+            }
+        },
+    );
+    let constant = arb_f64().prop_map(|constant| NoiseModuleConf::Constant { constant });
+    let ridged_multi =
+        arb_f64().prop_map(|attenuation| NoiseModuleConf::RidgedMulti { attenuation });
+
+    (
+        option::of(multifractal),
+        option::of(seedable),
+        option::of(worley),
+        option::of(constant),
+        option::of(ridged_multi),
+    )
+        .prop_map(|(a, b, c, d, e)| vec![a, b, c, d, e].into_iter().flatten().collect())
+        .boxed()
+}
+
+fn arb_dim() -> BoxedStrategy<Dim> {
+    prop_oneof![Just(Dim::X), Just(Dim::Y), Just(Dim::Z), Just(Dim::W)].boxed()
+}
+
+fn arb_transformations(
+    node: BoxedStrategy<CompositionTreeNodeDefinition>,
+) -> BoxedStrategy<Vec<InputTransformationDefinition>> {
+    let transformation = prop_oneof![
+        (arb_f64(), arb_f64())
+            .prop_map(|(speed, zoom)| InputTransformationDefinition::ZoomScale { speed, zoom }),
+        arb_f64().prop_map(InputTransformationDefinition::ScaleAll),
+        (node, arb_dim()).prop_map(|(node_def, replaced_dim)| {
+            InputTransformationDefinition::HigherOrderNoiseModule {
+                node_def,
+                replaced_dim,
+            }
+        }),
+    ];
+
+    vec(transformation, 0..3).boxed()
+}
+
+fn arb_leaf(
+    transformations: BoxedStrategy<Vec<InputTransformationDefinition>>,
+) -> BoxedStrategy<CompositionTreeNodeDefinition> {
+    (arb_leaf_module_type(), arb_module_confs(), transformations)
+        .prop_map(|(module_type, module_conf, transformations)| {
+            CompositionTreeNodeDefinition::Leaf {
+                module_type,
+                module_conf,
+                transformations,
+            }
+        })
+        .boxed()
+}
+
+fn arb_scheme() -> BoxedStrategy<CompositionScheme> {
+    prop_oneof![
+        Just(CompositionScheme::Average),
+        vec(arb_f64(), 0..4).prop_map(CompositionScheme::WeightedAverage),
+    ]
+    .boxed()
+}
+
+/// Generates valid node definitions, including composed nodes and higher order noise modules nested a few levels deep
+pub fn arb_node_def() -> BoxedStrategy<CompositionTreeNodeDefinition> {
+    arb_leaf(Just(Vec::new()).boxed())
+        .prop_recursive(4, 24, 4, |inner| {
+            prop_oneof![
+                arb_leaf(arb_transformations(inner.clone())),
+                (
+                    arb_scheme(),
+                    vec(inner.clone(), 0..4),
+                    arb_transformations(inner)
+                )
+                    .prop_map(|(scheme, children, transformations)| {
+                        CompositionTreeNodeDefinition::Composed {
+                            scheme,
+                            children,
+                            transformations,
+                        }
+                    }),
+            ]
+        })
+        .boxed()
+}
+
+fn arb_relief_mode() -> BoxedStrategy<ReliefMode> {
+    prop_oneof![
+        arb_f64().prop_map(|strength| ReliefMode::NormalMap { strength }),
+        (arb_f64(), arb_f64(), arb_f64()).prop_map(|(strength, azimuth, altitude)| {
+            ReliefMode::Hillshade {
+                strength,
+                azimuth,
+                altitude,
+            }
+        }),
+        arb_f64().prop_map(|strength| ReliefMode::Slope { strength }),
+    ]
+    .boxed()
+}
+
+fn arb_global_conf() -> BoxedStrategy<MasterConf> {
+    (
+        (arb_f64(), arb_f64(), arb_f64(), arb_f64(), arb_f64()),
+        option::of((1..1_000_000i32).prop_map(|val| val as f64 / 1000.)),
+        any::<bool>(),
+        option::of(arb_relief_mode()),
+        0..ColorFunction::ALL.len(),
+    )
+        .prop_map(
+            |(
+                (speed, zoom, x_offset, y_offset, z_offset),
+                loop_period,
+                auto_normalize,
+                relief_mode,
+                color_fn_ix,
+            )| MasterConf {
+                speed,
+                zoom,
+                x_offset,
+                y_offset,
+                z_offset,
+                loop_period,
+                auto_normalize,
+                relief_mode,
+                color_function: ColorFunction::ALL[color_fn_ix],
+                ..MasterConf::default()
+            },
+        )
+        .boxed()
+}
+
+pub fn arb_tree_def() -> BoxedStrategy<CompositionTreeDefinition> {
+    (arb_global_conf(), arb_node_def())
+        .prop_map(|(global_conf, root_node)| CompositionTreeDefinition {
+            global_conf,
+            root_node,
+        })
+        .boxed()
+}
+
+/// Generates IR trees built out of the node types and setting keys used by the frontend, but with the nodes and
+/// settings combined arbitrarily.  Most of these aren't valid definitions.
+fn arb_ir_node() -> BoxedStrategy<IrNode> {
+    let node_type = prop_oneof![
+        Just("root"),
+        Just("globalConf"),
+        Just("noiseModule"),
+        Just("compositionScheme"),
+        Just("inputTransformations"),
+        Just("inputTransformation"),
+        Just("unknown"),
+    ];
+    let key = prop_oneof![
+        Just("moduleType"),
+        Just("compositionScheme"),
+        Just("weights"),
+        Just("inputTransformationType"),
+        Just("replacedDim"),
+        Just("octaves"),
+        Just("seed"),
+        Just("rangeFunction"),
+        Just("constant"),
+        Just("zoom"),
+        Just("colorFunction"),
+        Just("reliefMode"),
+    ];
+    // values are generated both in their typed form and in the legacy form where everything is a string
+    let number = (-10_000i32..10_000).prop_map(|n| IrValue::Number(n as f64 / 10.));
+    let value = prop_oneof![
+        Just(IrValue::from("Composed")),
+        Just(IrValue::from("Fbm")),
+        Just(IrValue::from("weightedAverage")),
+        Just(IrValue::from("honf")),
+        Just(IrValue::from("[1,2]")),
+        Just(IrValue::from("tieDye")),
+        Just(IrValue::from("hillshade")),
+        Just(IrValue::from("z")),
+        "-?[0-9]{1,4}(\\.[0-9]{1,3})?".prop_map(IrValue::String),
+        "\\PC{0,8}".prop_map(IrValue::String),
+        any::<bool>().prop_map(IrValue::Bool),
+        number.clone(),
+        vec(number, 0..4).prop_map(IrValue::Array),
+    ];
+    let settings = vec(
+        (key, value).prop_map(|(key, value)| IrSetting::new(key, value)),
+        0..5,
+    );
+    let leaf = (node_type.clone(), settings.clone())
+        .prop_map(|(_type, settings)| IrNode::new(_type, settings, Vec::new()));
+
+    leaf.prop_recursive(4, 24, 4, move |inner| {
+        // nodes can also be nested in the values of settings, such as for higher order noise functions
+        let node_setting = option::of(
+            inner
+                .clone()
+                .prop_map(|node| IrSetting::new("noiseModule", node)),
+        );
+        (
+            node_type.clone(),
+            settings.clone(),
+            node_setting,
+            vec(inner, 0..4),
+        )
+            .prop_map(|(_type, mut settings, node_setting, children)| {
+                settings.extend(node_setting);
+                IrNode::new(_type, settings, children)
+            })
+    })
+    .boxed()
+}
+
+/// Returns a copy of the node definition with the configurations of every leaf sorted.  Settings are grouped by kind
+/// while being converted from the IR, so the order of the configurations isn't preserved.
+fn normalize(def: &CompositionTreeNodeDefinition) -> CompositionTreeNodeDefinition {
+    let normalize_transformations = |transformations: &[InputTransformationDefinition]| {
+        transformations
+            .iter()
+            .map(|transformation| match transformation {
+                &InputTransformationDefinition::HigherOrderNoiseModule {
+                    ref node_def,
+                    replaced_dim,
+                } => InputTransformationDefinition::HigherOrderNoiseModule {
+                    node_def: normalize(node_def),
+                    replaced_dim,
+                },
+                other => other.clone(),
+            })
+            .collect()
+    };
+
+    match def {
+        &CompositionTreeNodeDefinition::Leaf {
+            ref module_type,
+            ref module_conf,
+            ref transformations,
+        } => {
+            let mut module_conf = module_conf.clone();
+            module_conf.sort_by_key(|conf| serde_json::to_string(conf).unwrap());
+
+            CompositionTreeNodeDefinition::Leaf {
+                module_type: module_type.clone(),
+                module_conf,
+                transformations: normalize_transformations(transformations),
+            }
+        }
+        &CompositionTreeNodeDefinition::Composed {
+            ref scheme,
+            ref children,
+            ref transformations,
+        } => CompositionTreeNodeDefinition::Composed {
+            scheme: scheme.clone(),
+            children: children.iter().map(normalize).collect(),
+            transformations: normalize_transformations(transformations),
+        },
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn definitions_round_trip_through_ir(def in arb_tree_def()) {
+        let color_fn = def.global_conf.color_function;
+        let ir = build_ir_from_def(&def);
+        let (parsed_color_fn, parsed) = build_def_from_ir(ir).map_err(|err| err.message).unwrap();
+
+        prop_assert_eq!(parsed_color_fn, color_fn);
+        prop_assert_eq!(serde_json::to_value(&parsed.global_conf).unwrap(), serde_json::to_value(&def.global_conf).unwrap());
+        prop_assert_eq!(
+            serde_json::to_value(&normalize(&parsed.root_node)).unwrap(),
+            serde_json::to_value(&normalize(&def.root_node)).unwrap()
+        );
+    }
+
+    #[test]
+    fn definitions_round_trip_through_json(def in arb_tree_def()) {
+        let serialized = serde_json::to_string(&def).unwrap();
+        let parsed: CompositionTreeDefinition = serde_json::from_str(&serialized).unwrap();
+
+        prop_assert_eq!(serde_json::to_value(&parsed).unwrap(), serde_json::to_value(&def).unwrap());
+    }
+
+    #[test]
+    fn ir_strings_build_into_trees(def in arb_tree_def()) {
+        let color_fn = def.global_conf.color_function;
+        let ir_string = serde_json::to_string(&build_ir_from_def(&def)).unwrap();
+        let reparsed: IrNode = serde_json::from_str(&ir_string).unwrap();
+        prop_assert_eq!(serde_json::to_string(&reparsed).unwrap(), ir_string.clone());
+
+        // only definitions within the resource limits are built
+        let limits_check = ResourceLimits::default().check_definition(&def.root_node);
+        match build_tree_from_def(&ir_string) {
+            Ok((built_color_fn, tree)) => {
+                prop_assert!(limits_check.is_ok());
+                prop_assert_eq!(built_color_fn, color_fn);
+                tree.get([1., 2., 3.]);
+            }
+            Err(err) => prop_assert_eq!(Err(err), limits_check),
+        }
+    }
+
+    #[test]
+    fn arbitrary_ir_never_panics(node in arb_ir_node()) {
+        let def = serde_json::to_string(&node).unwrap();
+        if let Ok((_, tree)) = build_tree_from_def(&def) {
+            tree.get([1., 2., 3.]);
+        }
+    }
+}
+
+#[test]
+fn composed_leaf_module_type_builds() {
+    let def = CompositionTreeDefinition {
+        global_conf: MasterConf::default(),
+        root_node: CompositionTreeNodeDefinition::Leaf {
+            module_type: NoiseModuleType::Composed,
+            module_conf: Vec::new(),
+            transformations: Vec::new(),
+        },
+    };
+    let tree: CompositionTree = def.into();
+
+    assert_eq!(tree.get([1., 2., 3.]), 0.);
+}
+
+#[test]
+fn weighted_average_composition() {
+    let scheme = CompositionScheme::WeightedAverage(vec![3., 1.]);
+    assert_eq!(scheme.combine(vec![1., -1.].into_iter()), 0.5);
+    // children without a weight are given a weight of 1
+    assert_eq!(scheme.combine(vec![1., -1., -1.].into_iter()), 0.2);
+    assert_eq!(
+        CompositionScheme::WeightedAverage(vec![0.]).combine(vec![1.].into_iter()),
+        0.
+    );
+}
@@ -1,14 +1,16 @@
 //! Defines a trait that serves as a wrapper around noise modules and transform their inputs in some way.
 
-use noise::{NoiseFn, Point3};
+use noise::NoiseFn;
 
+use dims::NoisePoint;
 use util::Dim;
 use CompositionTreeNode;
 
 pub enum InputTransformation {
-    /// Transforms the input coordinate by scaling the X and Y dims by `zoom` and the Z dim by `speed`.
+    /// Transforms the input coordinate by scaling the X and Y dims by `zoom` and the Z and W dims by `speed`.
     ZoomScale { speed: f64, zoom: f64 },
     /// Replaces the `replacement_dim` of the input with the result of passing it through the provided noise function.
+    /// Has no effect if the input doesn't have that dimension, such as when replacing W during 3D evaluation.
     HigherOrderNoiseModule {
         node: CompositionTreeNode,
         replaced_dim: Dim,
@@ -18,36 +20,37 @@ pub enum InputTransformation {
 }
 
 impl InputTransformation {
-    pub fn transform(&self, coord: Point3<f64>) -> Point3<f64> {
+    pub fn transform<P: NoisePoint>(&self, coord: P) -> P
+    where
+        CompositionTreeNode: NoiseFn<P>,
+    {
         match self {
-            &InputTransformation::ZoomScale { speed, zoom } => {
-                [coord[0] * zoom, coord[1] * zoom, coord[2] * speed]
-            }
+            &InputTransformation::ZoomScale { speed, zoom } => coord.zoom_scale(zoom, speed),
             &InputTransformation::HigherOrderNoiseModule {
                 ref node,
                 replaced_dim,
             } => {
                 let val = node.get(coord);
-
-                match replaced_dim {
-                    Dim::X => [val, coord[1], coord[2]],
-                    Dim::Y => [coord[0], val, coord[2]],
-                    Dim::Z => [coord[0], coord[1], val],
-                }
-            }
-            &InputTransformation::ScaleAll(scale) => {
-                [coord[0] * scale, coord[1] * scale, coord[2] * scale]
+                coord.replace_dim(replaced_dim, val)
             }
+            &InputTransformation::ScaleAll(scale) => coord.scale_all(scale),
         }
     }
 }
 
-/// Applies a list of transformations to the given input coordinate, returning the transformed result.
-pub fn apply_transformations(
-    transformations: &[InputTransformation],
-    coord: Point3<f64>,
-) -> Point3<f64> {
+/// Applies a list of transformations to the given input coordinate, returning the transformed result.  Transformations
+/// can move the coordinate arbitrarily far away from the point that the tree was evaluated at, so the result is
+/// clamped to `dims::MAX_COORD` like that point is.
+pub fn apply_transformations<P: NoisePoint>(transformations: &[InputTransformation], coord: P) -> P
+where
+    CompositionTreeNode: NoiseFn<P>,
+{
+    if transformations.is_empty() {
+        return coord;
+    }
+
     transformations
         .iter()
         .fold(coord, |acc, transformation| transformation.transform(acc))
+        .clamp_coords()
 }
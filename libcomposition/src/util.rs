@@ -2,21 +2,32 @@
 
 use std::collections::HashMap;
 use std::convert::{TryFrom, TryInto};
+use std::fmt::{self, Display, Formatter};
 use std::str::FromStr;
 
 use itertools::Itertools;
 use serde_json;
 
-use super::{CompositionTree, CompositionTreeNode, CompositionTreeNodeDefinition, MasterConf};
+use super::{
+    CompositionTree, CompositionTreeDefinition, CompositionTreeNodeDefinition, MasterConf,
+};
 use color_schemes::ColorFunction;
 use conf::{map_setting_to_type, NoiseModuleConf, SettingType};
-use ir::{IrNode, IrSetting};
+use ir::{FromIrValue, IrNode, IrSetting, IrValue};
+use limits::ResourceLimits;
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, JsonSchema)]
 pub enum Dim {
     X,
     Y,
     Z,
+    /// Only present when the tree is evaluated in 4D, such as when tiling or looping
+    W,
+}
+
+impl Dim {
+    /// Every dimension, including `W`
+    pub const ALL: [Dim; 4] = [Dim::X, Dim::Y, Dim::Z, Dim::W];
 }
 
 impl FromStr for Dim {
@@ -27,11 +38,25 @@ impl FromStr for Dim {
             "X" | "x" => Ok(Dim::X),
             "Y" | "y" => Ok(Dim::Y),
             "Z" | "z" => Ok(Dim::Z),
+            "W" | "w" => Ok(Dim::W),
             _ => Err(format!("Can't convert supplied string to `Dim`: {}", s)),
         }
     }
 }
 
+impl Display for Dim {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let key = match self {
+            &Dim::X => "x",
+            &Dim::Y => "y",
+            &Dim::Z => "z",
+            &Dim::W => "w",
+        };
+
+        write!(f, "{}", key)
+    }
+}
+
 /// Attempts to locate a child node among the children of a node and convert it into an internal definition.
 pub fn build_child<T>(children: &[IrNode], child_type: &str) -> Result<T, T::Error>
 where
@@ -47,26 +72,26 @@ where
         )
 }
 
-/// Searches through a slice of `IrSetting`s provided to a node and attempts to find the setting with the supplied name.
-pub fn find_setting_by_name(name: &str, settings: &[IrSetting]) -> Result<String, String> {
-    Ok(settings
+/// Searches through a slice of `IrSetting`s provided to a node and attempts to find the value of the setting with the
+/// supplied name.
+pub fn find_setting_by_name<'a>(
+    name: &str,
+    settings: &'a [IrSetting],
+) -> Result<&'a IrValue, String> {
+    settings
         .iter()
         .find(|&&IrSetting { ref key, .. }| key == name)
-        .ok_or(String::from(
-            "No `moduleType` setting provided to node of type `noiseModule`!",
-        ))?.value
-        .clone())
+        .map(|setting| &setting.value)
+        .ok_or_else(|| format!("No `{}` setting provided to node!", name))
 }
 
-/// Attempts to find the setting with the supplied key in the settings slice and parse its value into a `T`.
+/// Attempts to find the setting with the supplied key in the settings slice and convert its value into a `T`.
 pub fn convert_setting<T>(key: &str, settings: &[IrSetting]) -> Result<T, String>
 where
-    T: FromStr,
+    T: FromIrValue,
 {
-    let raw_val = find_setting_by_name(key, &settings)?;
-    raw_val
-        .parse()
-        .map_err(|_| format!("Unable to convert value from string: {}", raw_val))
+    T::from_ir_value(find_setting_by_name(key, settings)?)
+        .map_err(|err| format!("Invalid value for setting `{}`: {}", key, err))
 }
 
 fn build_noise_module_conf(
@@ -137,12 +162,46 @@ pub fn build_noise_module_settings(
         })
 }
 
-/// Given a definition string, produces an entirely new composition tree from scratch.
-pub fn build_tree_from_def(def: &str) -> Result<(ColorFunction, CompositionTree), String> {
-    // attempt to parse the provided IR definition into an `IrNode`
-    let ir_root_node_def: IrNode = serde_json::from_str::<IrNode>(def)
-        .map_err(|_| "Error while parsing the provided definition string!".to_string())?;
+/// The step of building a composition tree out of a definition string during which an error occurred.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BuildStage {
+    /// The definition string isn't valid JSON or doesn't have the shape of an `IrNode`
+    Parse,
+    /// The `globalConf` node is missing or contains invalid settings
+    GlobalConf,
+    /// The `colorFunction` setting is missing or unknown
+    ColorFunction,
+    /// The IR tree couldn't be converted into a `CompositionTreeNodeDefinition`
+    Tree,
+    /// The tree definition exceeds the resource limits that it's built with
+    Limits,
+}
 
+/// An error produced while building a composition tree from a definition string, tagged with the stage that failed
+/// so that callers can report it in a structured manner.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BuildError {
+    pub stage: BuildStage,
+    pub message: String,
+}
+
+impl BuildError {
+    fn new(stage: BuildStage, message: String) -> Self {
+        BuildError { stage, message }
+    }
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Converts a parsed IR tree into a `CompositionTreeDefinition`, also returning the color function selected in its
+/// `globalConf` node.
+pub fn build_def_from_ir(
+    ir_root_node_def: IrNode,
+) -> Result<(ColorFunction, CompositionTreeDefinition), BuildError> {
     // find the global conf node in the IR tree and build it into a `MasterConf`.
     // also pull off the color scheme string and buid it into a `ColorScheme`.
     let (global_conf, color_fn): (MasterConf, ColorFunction) = {
@@ -150,36 +209,96 @@ pub fn build_tree_from_def(def: &str) -> Result<(ColorFunction, CompositionTree)
             .children
             .iter()
             .find(|node| node._type.as_str() == "globalConf")
-            .ok_or(String::from(
-                "Supplied definition string doesn't contain a `globalConf` node!",
+            .ok_or(BuildError::new(
+                BuildStage::GlobalConf,
+                String::from("Supplied definition string doesn't contain a `globalConf` node!"),
             ))?;
         let global_conf = ir_global_conf.clone().try_into().map_err(|err| {
-            format!(
-                "Unable to convert IR global conf into `GlobalConf`: {}",
-                err
+            BuildError::new(
+                BuildStage::GlobalConf,
+                format!(
+                    "Unable to convert IR global conf into `GlobalConf`: {}",
+                    err
+                ),
             )
         })?;
-        let color_fn_string = find_setting_by_name("colorFunction", &ir_global_conf.settings)
+        let color_fn_value = find_setting_by_name("colorFunction", &ir_global_conf.settings)
             .map_err(|_| {
-                String::from("No `colorFunction` setting included in provided `globalConf` node!")
+                BuildError::new(
+                    BuildStage::ColorFunction,
+                    String::from("No `colorFunction` setting included in provided `globalConf` node!"),
+                )
             })?;
-        let color_fn = ColorFunction::from_str(&color_fn_string)?;
+        let color_fn = ColorFunction::from_ir_value(color_fn_value)
+            .map_err(|err| BuildError::new(BuildStage::ColorFunction, err))?;
 
         (global_conf, color_fn)
     };
 
     // and then convert that into a `CompositionTreeNodeDefinition`
-    let root_node_def: CompositionTreeNodeDefinition = ir_root_node_def.try_into()?;
+    let root_node: CompositionTreeNodeDefinition = ir_root_node_def
+        .try_into()
+        .map_err(|err| BuildError::new(BuildStage::Tree, err))?;
 
-    // build the definition into a proper `CompositionTreeNode`.
-    let root_node: CompositionTreeNode = root_node_def.into();
-
-    // create the full `CompositionTree` from the root node and the global configuration
     Ok((
         color_fn,
-        CompositionTree {
-            root_node,
+        CompositionTreeDefinition {
             global_conf,
+            root_node,
         },
     ))
 }
+
+/// Parses a definition string and converts it into a `CompositionTreeDefinition` without building it, also returning
+/// the color function selected in its `globalConf` node.
+pub fn build_def_from_str(
+    def: &str,
+) -> Result<(ColorFunction, CompositionTreeDefinition), BuildError> {
+    // attempt to parse the provided IR definition into an `IrNode`
+    let ir_root_node_def: IrNode = serde_json::from_str::<IrNode>(def).map_err(|err| {
+        BuildError::new(
+            BuildStage::Parse,
+            format!(
+                "Error while parsing the provided definition string: {}",
+                err
+            ),
+        )
+    })?;
+
+    build_def_from_ir(ir_root_node_def)
+}
+
+/// Checks that a tree definition is within the supplied resource limits, returning an error for the `Limits` stage
+/// describing the limit that it exceeds if it isn't.
+pub fn check_def_limits(
+    tree_def: &CompositionTreeDefinition,
+    limits: &ResourceLimits,
+) -> Result<(), BuildError> {
+    limits
+        .check_definition(&tree_def.root_node)
+        .map_err(|err| BuildError::new(BuildStage::Limits, err))
+}
+
+/// Same as `try_build_tree_from_def`, but rejects definitions that exceed the supplied resource limits rather than
+/// the default ones.
+pub fn try_build_tree_from_def_with_limits(
+    def: &str,
+    limits: &ResourceLimits,
+) -> Result<(ColorFunction, CompositionTree), BuildError> {
+    let (color_fn, tree_def) = build_def_from_str(def)?;
+    check_def_limits(&tree_def, limits)?;
+
+    // build the definition into a full `CompositionTree`
+    Ok((color_fn, tree_def.into()))
+}
+
+/// Same as `build_tree_from_def`, but returns a `BuildError` describing which step of the build failed.
+pub fn try_build_tree_from_def(def: &str) -> Result<(ColorFunction, CompositionTree), BuildError> {
+    try_build_tree_from_def_with_limits(def, &ResourceLimits::default())
+}
+
+/// Given a definition string, produces an entirely new composition tree from scratch.  Definitions that exceed the
+/// default `ResourceLimits` are rejected since they may come from untrusted users.
+pub fn build_tree_from_def(def: &str) -> Result<(ColorFunction, CompositionTree), String> {
+    try_build_tree_from_def(def).map_err(|err| err.message)
+}
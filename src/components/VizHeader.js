@@ -34,8 +34,8 @@ const MappedMenuItem = connect(mapState, { push })(({
       resume();
       push(path);
       cleanupRuntime();
-      initializeFromScratch(JSON.stringify(initialTree));
       init(lastCanvasSize);
+      initializeFromScratch(JSON.stringify(initialTree));
     } }
   />
 ));
@@ -0,0 +1,40 @@
+//! Displays how long the engine takes to render each frame and the resolution that it renders them at, which drops
+//! below full resolution when the composition is too expensive to render within the frame time budget.
+
+import React from 'react';
+
+import { getFrameStats } from 'src/interop';
+import { getEnginePointer } from 'src/selectors/enginePointer';
+
+const POLL_INTERVAL_MS = 500;
+
+export default class FrameStats extends React.Component {
+  state = { stats: null };
+
+  componentDidMount() {
+    this.interval = setInterval(() => {
+      if(getEnginePointer() !== 0) {
+        this.setState({ stats: getFrameStats() });
+      }
+    }, POLL_INTERVAL_MS);
+  }
+
+  componentWillUnmount() {
+    clearInterval(this.interval);
+  }
+
+  render() {
+    const { stats } = this.state;
+    if(!stats || stats.render_scale === 0) {
+      return null;
+    }
+
+    return (
+      <div style={{ color: '#999', fontSize: 12, textAlign: 'center' }}>
+        { `${stats.average_frame_time.toFixed(1)} ms/frame, ` }
+        { `rendering at ${stats.effective_width}x${stats.effective_height}` }
+        { stats.min_scale > 1 && ` (1/${stats.min_scale} resolution to stay within the frame budget)` }
+      </div>
+    );
+  }
+}
@@ -4,11 +4,13 @@ import React from 'react';
 import { connect } from 'react-redux';
 
 import { setStageContainerSize } from 'src/actions/stage';
+import FrameStats from './FrameStats';
 import VizCanvas from './VizCanvas';
 
 const Vizualization = ({enginePointer, setStageContainerSize}) => (
   <div style={{marginBottom: 20, height: '100%', width: '100%'}}>
     <VizCanvas />
+    <FrameStats />
   </div>
 );
 
@@ -21,7 +21,13 @@ export default {
       settings: [
         createSetting('speed', '0.008'),
         createSetting('zoom', '0.015'),
+        createSetting('loopPeriod', '0'),
         createSetting('colorFunction', 'tieDye'),
+        createSetting('autoNormalize', 'false'),
+        createSetting('reliefMode', 'none'),
+        createSetting('reliefStrength', '10'),
+        createSetting('sunAzimuth', '315'),
+        createSetting('sunAltitude', '45'),
       ],
       children: [],
     }, {
@@ -39,8 +39,10 @@ export const settingDefinitions = {
     default: '6',
     min: 0.0,
     trueMin: 0.0,
-    max: 80.0, // the limit that the user is capped at for sliders
-    trueMax: 350.0, // the limit that the user is capped at when the slider is unlocked
+    max: 16.0, // the limit that the user is capped at for sliders
+    // the limit that the user is capped at when the slider is unlocked.  The backend rejects compositions that have
+    // more than 16 octaves.
+    trueMax: 16.0,
     hint: (
       <span>
         {'Total number of frequency octaves to generate the noise with.  '}
@@ -232,13 +234,30 @@ export const settingDefinitions = {
       { key: 'x', title: 'X' },
       { key: 'y', title: 'Y' },
       { key: 'z', title: 'Z' },
+      { key: 'w', title: 'W' },
     ],
+    hint: 'The W dimension is only used when the output is looping or tiled; replacing it has no effect otherwise.',
   },
   scaleFactor: {
     title: 'Scale Factor',
     default: '1',
     hint: 'All input coordinates are multipled by this number before being passed to the inner noise function.',
   },
+  loopPeriod: {
+    title: 'Loop Period',
+    default: '0',
+    min: 0.0,
+    trueMin: 0.0,
+    max: 10e4,
+    trueMax: Infinity,
+    hint: 'If greater than zero, the animation repeats seamlessly after this many ticks by traveling along a circle through the noise rather than a straight line.  Trees containing modules without 4D support (such as Super Simplex) crossfade instead.',
+  },
+  autoNormalize: {
+    title: 'Auto Normalize',
+    bool: true,
+    default: 'false',
+    hint: 'Measures the range of values produced by the composition and stretches it to fill the entire range of the color map.',
+  },
   colorFunction: {
     title: 'Color Map Function',
     description: 'The function that is used to map the output from the noise functions to the color displayed for that pixel on the canvas.',
@@ -255,7 +274,46 @@ export const settingDefinitions = {
       { key: 'vaporwave', title: 'Vaporwave' },
       { key: 'algaeFloat', title: 'Algae Float' },
     ],
-  }
+  },
+  reliefMode: {
+    title: 'Relief Mode',
+    description: 'Treats the output of the noise functions as a heightfield and visualizes its relief instead of using the color map function.',
+    default: 'none',
+    enum: true,
+    enumValues: [
+      { key: 'none', title: 'None', description: 'Use the color map function' },
+      { key: 'normalMap', title: 'Normal Map', description: 'Tangent-space normal map (OpenGL convention)' },
+      { key: 'hillshade', title: 'Hillshade', description: 'Shaded as if lit by the sun' },
+      { key: 'slope', title: 'Slope', description: 'Steeper areas are brighter' },
+    ],
+  },
+  reliefStrength: {
+    title: 'Relief Strength',
+    default: '10',
+    min: 0.0,
+    trueMin: 0.0,
+    max: 100.0,
+    trueMax: Infinity,
+    hint: 'Multiplier applied to the differences between neighboring values when computing the relief.',
+  },
+  sunAzimuth: {
+    title: 'Sun Azimuth',
+    default: '315',
+    min: 0.0,
+    trueMin: -Infinity,
+    max: 360.0,
+    trueMax: Infinity,
+    hint: 'Direction of the sun for hillshading in degrees clockwise from the top of the canvas.',
+  },
+  sunAltitude: {
+    title: 'Sun Altitude',
+    default: '45',
+    min: 0.0,
+    trueMin: 0.0,
+    max: 90.0,
+    trueMax: 90.0,
+    hint: 'Angle of the sun above the horizon for hillshading in degrees.',
+  },
 };
 
 const mapSettingState = ({ compositionTree: { entities: { settings } } }) => ({ settings });
@@ -12,14 +12,14 @@ import {
 } from 'src/interop';
 import { denormalizeNode } from 'src/helpers/compositionTree/normalization';
 import { getLeafAttr, getNodeParent } from 'src/selectors/compositionTree';
-import { getTreePointer } from 'src/selectors/enginePointer';
+import { getEnginePointer } from 'src/selectors/enginePointer';
 import { NULL_UUID } from 'src/data/misc';
 import { mapIdsToEntites } from 'src/helpers/compositionTree/util';
 
 /**
  * Finds the path to a node in the composition tree and returns it as an array of integers.
  */
-const getNodeCoords = (entities, nodeId) => {
+export const getNodeCoords = (entities, nodeId) => {
   let coordBuf = [];
   let curId = nodeId;
 
@@ -88,7 +88,7 @@ export const commitChanges = (entities, { new: newNodes, updated: updatedNodes,
     if(nodeType === 'noiseModule') {
       replaceNode(R.init(coords), R.last(coords), def);
     } else if(nodeType === 'globalConf') {
-      setGlobalConf(getTreePointer(), def);
+      setGlobalConf(getEnginePointer(), def);
     } else if(nodeType === 'inputTransformation') {
       // coords are [..., parentNode, inputTransformations, inputTransformation]
       const spl = R.splitAt(-3, coords);
@@ -0,0 +1,38 @@
+/**
+ * Functions for previewing the output of individual nodes of the composition tree, identified by their IDs.
+ */
+
+import { getNodeCoords } from 'src/helpers/compositionTree/commit';
+import { renderNodePreview, setSoloNode, clearSoloNode } from 'src/interop';
+
+/**
+ * Renders a `size` x `size` thumbnail of the output of the node with the supplied ID.  Returns the RGBA pixel data or
+ * `null` if the node couldn't be found or rendered.
+ */
+export const renderNodePreviewById = (entities, nodeId, size) => {
+  const coords = getNodeCoords(entities, nodeId);
+  if(coords === false) {
+    return null;
+  }
+
+  return renderNodePreview(coords, size);
+};
+
+/**
+ * Renders the node with the supplied ID in place of the entire composition tree.  Since the engine stores the soloed
+ * node by its position in the tree, this has to be called again after the tree's structure changes.  Passing `null`
+ * renders the entire tree again.
+ */
+export const soloNodeById = (entities, nodeId) => {
+  if(nodeId === null) {
+    clearSoloNode();
+    return 0;
+  }
+
+  const coords = getNodeCoords(entities, nodeId);
+  if(coords === false) {
+    return 1;
+  }
+
+  return setSoloNode(coords);
+};
@@ -2,11 +2,11 @@
  * Functions for interacting with the Asm.JS/Emscripten backend
  */
 
-/* global Module lengthBytesUTF8 stringToUTF8 */
+/* global Module lengthBytesUTF8 stringToUTF8 UTF8ToString */
 
 import store from 'src/reducers';
-import { getEnginePointer, getTreePointer } from 'src/selectors/enginePointer';
-import { setEnginePointer, setTreePointer } from 'src/reducers/enginePointerReducer';
+import { getEnginePointer } from 'src/selectors/enginePointer';
+import { setEnginePointer } from 'src/reducers/enginePointerReducer';
 
 export const RANGE_FUNCTIONS = {
   euclidean: 0,
@@ -26,11 +26,6 @@ const log = (...args) => {
  */
 Module.registerEnginePointer = pointer => store.dispatch(log(setEnginePointer(pointer)));
 
-/**
- * Adds a hook into the `Module` that can be called from the Rust side to register a pointer to the backend's composition tree.
- */
-Module.registerTreePointer = pointer => store.dispatch(log(setTreePointer(pointer)));
-
 /**
  * Initializes the noise engine backend, returning a pointer to the noise engine configuration object passed along with
  * configuration
@@ -45,6 +40,30 @@ export const init = canvasSize => {
   Module.ccall('init', null, ['number'], [canvasSize]);
 };
 
+const createInstanceInner = Module.cwrap('create_instance', 'number', ['number', 'number']);
+
+// ID 0 is reserved for the main canvas
+Module.canvases = {};
+let nextCanvasId = 1;
+// maps the pointers of the instances created by `createInstance` to the IDs of their canvases
+const instanceCanvasIds = {};
+
+/**
+ * Creates an additional engine instance that renders to the supplied canvas independently of the main engine, for
+ * example for live previews.  All instances are animated by the same scheduler.  Returns a pointer to the instance
+ * that can be passed to the other functions in place of the main engine's pointer; it must be freed by passing it to
+ * `cleanupRuntime` once it's no longer needed.
+ */
+export const createInstance = canvas => {
+  const canvasId = nextCanvasId;
+  nextCanvasId += 1;
+  Module.canvases[canvasId] = canvas;
+
+  const enginePointer = createInstanceInner(canvasId, canvas.width);
+  instanceCanvasIds[enginePointer] = canvasId;
+  return enginePointer;
+};
+
 /**
  * Replaces the currently active global configuration for the composition tree with the provided configuration in
  * IR format.
@@ -72,10 +91,16 @@ const setGlobalConfInner = Module.cwrap('set_global_conf', 'number', ['number',
  */
 export const setCanvasSize = Module.cwrap('set_canvas_size', null, ['number', 'number']);
 
-export const pause = Module.cwrap('pause_engine', null, []);
-export const resume = Module.cwrap('resume_engine', null, []);
+/**
+ * Pauses or resumes the engine instance at the given pointer.
+ */
+export const pauseInstance = Module.cwrap('pause_engine', null, ['number']);
+export const resumeInstance = Module.cwrap('resume_engine', null, ['number']);
 
-// tree_pointer, depth, coords, index, node_definition
+export const pause = () => pauseInstance(getEnginePointer());
+export const resume = () => resumeInstance(getEnginePointer());
+
+// engine_pointer, depth, coords, index, node_definition
 const addNodeInner = Module.cwrap('add_node', 'number', [
   'number',
   'number',
@@ -98,7 +123,7 @@ export const addNode = (nodeCoords, index, defString) => {
   Module.HEAP32.set(new Int32Array(nodeCoords), coordBufPtr / 4);
 
   // actually call the backend's node add function and record the result
-  const status = addNodeInner(getTreePointer(), nodeCoords.length, coordBufPtr, index, defBufPtr);
+  const status = addNodeInner(getEnginePointer(), nodeCoords.length, coordBufPtr, index, defBufPtr);
 
   Module._free(defBufPtr);
   Module._free(coordBufPtr);
@@ -106,7 +131,7 @@ export const addNode = (nodeCoords, index, defString) => {
   return status;
 };
 
-// tree_pointer, depth, coords, index
+// engine_pointer, depth, coords, index
 const deleteNodeInner = Module.cwrap('delete_node', 'number', [
   'number',
   'number',
@@ -118,7 +143,7 @@ export const deleteNode = (nodeCoords, index) => {
   const coordBufPtr = Module._malloc(nodeCoords.length * 4);
   Module.HEAP32.set(new Int32Array(nodeCoords), coordBufPtr / 4);
 
-  const status = deleteNodeInner(getTreePointer(), nodeCoords.length, coordBufPtr, index);
+  const status = deleteNodeInner(getEnginePointer(), nodeCoords.length, coordBufPtr, index);
 
   Module._free(coordBufPtr);
 
@@ -144,7 +169,7 @@ export const replaceNode = (nodeCoords, index, defString) => {
 
   // actually call the backend's node add function and record the result
   const status = replaceNodeInner(
-    getTreePointer(),
+    getEnginePointer(),
     nodeCoords.length,
     coordBufPtr,
     index,
@@ -157,7 +182,7 @@ export const replaceNode = (nodeCoords, index, defString) => {
   return status;
 };
 
-// (tree_pointer, tree_depth, coords, node_index, transformation_definition)
+// (engine_pointer, tree_depth, coords, node_index, transformation_definition)
 const addInputTransformationInner = Module.cwrap('add_input_transformation', 'number', [
   'number',
   'number',
@@ -176,7 +201,7 @@ export const addInputTransformation = (parentNodeCoords, index, defString) => {
 
   // call the backend function and try to add the input transformation
   const status = addInputTransformationInner(
-    getTreePointer(),
+    getEnginePointer(),
     parentNodeCoords.length,
     coordBufPtr,
     index,
@@ -189,7 +214,7 @@ export const addInputTransformation = (parentNodeCoords, index, defString) => {
   return status;
 };
 
-// tree_pointer, depth, coords, node_index, transformation_index
+// engine_pointer, depth, coords, node_index, transformation_index
 export const deleteInputTransformationInner = Module.cwrap(
   'delete_input_transformation',
   'number',
@@ -201,7 +226,7 @@ export const deleteInputTransformation = (parentNodeCoords, treeIndex, transform
   Module.HEAP32.set(new Int32Array(parentNodeCoords), coordBufPtr / 4);
 
   const status = deleteInputTransformationInner(
-    getTreePointer(),
+    getEnginePointer(),
     parentNodeCoords.length,
     coordBufPtr,
     treeIndex,
@@ -213,7 +238,7 @@ export const deleteInputTransformation = (parentNodeCoords, treeIndex, transform
   return status;
 };
 
-// (tree_pointer, tree_depth, coords, node_index, transformation_index, transformation_definition)
+// (engine_pointer, tree_depth, coords, node_index, transformation_index, transformation_definition)
 const replaceInputTransformationInner = Module.cwrap('replace_input_transformation', 'number', [
   'number',
   'number',
@@ -240,7 +265,7 @@ export const replaceInputTransformation = (
 
   // actually call the backend's node add function and record the result
   const status = replaceInputTransformationInner(
-    getTreePointer(),
+    getEnginePointer(),
     parentNodeCoords.length,
     coordBufPtr,
     treeIndex,
@@ -259,7 +284,7 @@ const initializeFromScratchInner = Module.cwrap('initialize_from_scratch', 'numb
   'number',
 ]);
 
-export const initializeFromScratch = defString => {
+export const initializeFromScratch = (defString, enginePointer = getEnginePointer()) => {
   console.log('re-initing the backend...');
   const bufferSize = lengthBytesUTF8(defString) + 1;
   // allocate space on the heap for both the definition string as well as the coordinates array
@@ -267,20 +292,203 @@ export const initializeFromScratch = defString => {
   stringToUTF8(defString, defBufPtr, 10000000);
 
   // call the backend function
-  const status = initializeFromScratchInner(getTreePointer(), defBufPtr);
+  const status = initializeFromScratchInner(enginePointer, defBufPtr);
 
   Module._free(defBufPtr);
 
   return status;
 };
 
-export const cleanupRuntimeInner = Module.cwrap('cleanup_runtime', null, ['number', 'number']);
+// engine_pointer, depth, coords, size, buf
+const renderNodePreviewInner = Module.cwrap('render_node_preview_into', 'number', [
+  'number',
+  'number',
+  'number',
+  'number',
+  'number',
+]);
+
+/**
+ * Renders the subtree at the supplied coordinates into a `size` x `size` image using the same viewport, point in time,
+ * and color function as the main canvas.  Returns the RGBA pixel data as a `Uint8ClampedArray` that can be used to
+ * construct an `ImageData`, or `null` if there was an error.
+ */
+export const renderNodePreview = (nodeCoords, size) => {
+  const coordBufPtr = Module._malloc(nodeCoords.length * 4);
+  Module.HEAP32.set(new Int32Array(nodeCoords), coordBufPtr / 4);
+  const pixelBufPtr = Module._malloc(size * size * 4);
+
+  const status = renderNodePreviewInner(getEnginePointer(), nodeCoords.length, coordBufPtr, size, pixelBufPtr);
+  // copy the pixel data out of Emscripten memory before freeing it
+  const pixels = status === 0
+    ? new Uint8ClampedArray(Module.HEAPU8.slice(pixelBufPtr, pixelBufPtr + size * size * 4))
+    : null;
+
+  Module._free(coordBufPtr);
+  Module._free(pixelBufPtr);
+
+  return pixels;
+};
+
+// engine_pointer, depth, coords
+const setSoloNodeInner = Module.cwrap('set_solo_node', 'number', ['number', 'number', 'number']);
+
+/**
+ * Temporarily renders the subtree at the supplied coordinates in place of the entire composition tree.
+ */
+export const setSoloNode = nodeCoords => {
+  const coordBufPtr = Module._malloc(nodeCoords.length * 4);
+  Module.HEAP32.set(new Int32Array(nodeCoords), coordBufPtr / 4);
+
+  const status = setSoloNodeInner(getEnginePointer(), nodeCoords.length, coordBufPtr);
+
+  Module._free(coordBufPtr);
+
+  return status;
+};
+
+const clearSoloNodeInner = Module.cwrap('clear_solo_node', null, ['number']);
+
+/**
+ * Goes back to rendering the entire composition tree after a node was soloed.
+ */
+export const clearSoloNode = () => clearSoloNodeInner(getEnginePointer());
+
+const analyzeCompositionInner = Module.cwrap('analyze_composition', 'number', ['number', 'number']);
+const freeString = Module.cwrap('free_string', null, ['number']);
+
+/**
+ * Samples the active composition tree and returns statistics about its output values (min, max, mean, standard
+ * deviation, percentiles, and a histogram).  `conf` can contain any of the fields of `AnalysisConf`; missing fields use
+ * their defaults.  Returns `null` if there was an error.
+ */
+export const analyzeComposition = (conf = {}) => {
+  const confString = JSON.stringify(conf);
+  const bufferSize = lengthBytesUTF8(confString) + 1;
+  const confBufPtr = Module._malloc(bufferSize);
+  stringToUTF8(confString, confBufPtr, bufferSize);
+
+  const statsPtr = analyzeCompositionInner(getEnginePointer(), confBufPtr);
+  Module._free(confBufPtr);
+  if(statsPtr === 0) {
+    return null;
+  }
+
+  const stats = JSON.parse(UTF8ToString(statsPtr));
+  freeString(statsPtr);
+  return stats;
+};
+
+const probePixelInner = Module.cwrap('probe_pixel', 'number', ['number', 'number', 'number']);
+
+/**
+ * Traces the evaluation of the composition tree at the supplied canvas pixel, returning the final value along with the
+ * transformed coordinate and output of every node in the tree.  Returns `null` if there was an error.
+ */
+export const probePixel = (x, y) => {
+  const probePtr = probePixelInner(getEnginePointer(), x, y);
+  if(probePtr === 0) {
+    return null;
+  }
+
+  const probe = JSON.parse(UTF8ToString(probePtr));
+  freeString(probePtr);
+  return probe;
+};
+
+const frameStatsInner = Module.cwrap('frame_stats', 'number', ['number']);
+
+/**
+ * Returns timing statistics about the frames rendered by the engine (the last and average frame time in milliseconds,
+ * the frame time budget, and the resolution that frames are currently rendered at).  Returns `null` if there was an
+ * error.
+ */
+export const getFrameStats = (enginePointer = getEnginePointer()) => {
+  const statsPtr = frameStatsInner(enginePointer);
+  if(statsPtr === 0) {
+    return null;
+  }
+
+  const stats = JSON.parse(UTF8ToString(statsPtr));
+  freeString(statsPtr);
+  return stats;
+};
+
+const setTargetFrameTimeInner = Module.cwrap('set_target_frame_time', null, ['number', 'number']);
+
+/**
+ * Sets the time budget for rendering a single frame in milliseconds.  Frames that take longer to render are rendered at
+ * a lower resolution.  A budget of 0 disables it, always refining frames to full resolution.
+ */
+export const setTargetFrameTime = (targetFrameTime, enginePointer = getEnginePointer()) =>
+  setTargetFrameTimeInner(enginePointer, targetFrameTime);
+
+const applyEditsInner = Module.cwrap('apply_edits', 'number', ['number', 'number']);
+
+/**
+ * Applies a list of edits (serialized `TreeEdit`s) to the composition tree all at once.  If any of the edits fails,
+ * none of them are applied.  The edits are undone and redone together as a single step.  Returns 0 if successful and 1
+ * if there was an error.
+ */
+export const applyEdits = edits => {
+  const editsString = JSON.stringify(edits);
+  const bufferSize = lengthBytesUTF8(editsString) + 1;
+  const editsBufPtr = Module._malloc(bufferSize);
+  stringToUTF8(editsString, editsBufPtr, bufferSize);
+
+  const status = applyEditsInner(getEnginePointer(), editsBufPtr);
+  Module._free(editsBufPtr);
+  return status;
+};
+
+const undoEditInner = Module.cwrap('undo_edit', 'number', ['number']);
+const redoEditInner = Module.cwrap('redo_edit', 'number', ['number']);
+
+const stepHistory = innerFn => {
+  const editPtr = innerFn(getEnginePointer());
+  if(editPtr === 0) {
+    return null;
+  }
+
+  const edit = JSON.parse(UTF8ToString(editPtr));
+  freeString(editPtr);
+  return edit;
+};
+
+/**
+ * Reverses the most recent edit made to the composition tree.  Returns the edit that was applied to the tree in order
+ * to do so (a serialized `TreeEdit`) or `null` if there was nothing to undo.
+ */
+export const undoEdit = () => stepHistory(undoEditInner);
+
+/**
+ * Re-applies the most recently undone edit.  Returns the edit that was applied to the tree (a serialized `TreeEdit`) or
+ * `null` if there was nothing to redo.
+ */
+export const redoEdit = () => stepHistory(redoEditInner);
+
+const canUndoInner = Module.cwrap('can_undo', 'number', ['number']);
+const canRedoInner = Module.cwrap('can_redo', 'number', ['number']);
+
+export const canUndo = () => canUndoInner(getEnginePointer()) === 1;
+export const canRedo = () => canRedoInner(getEnginePointer()) === 1;
+
+export const cleanupRuntimeInner = Module.cwrap('cleanup_runtime', null, ['number']);
 
 /**
- * Deallocates the previously created composition tree and engine.
+ * Stops the engine and deallocates it along with its composition tree.  A new engine must be created with `init` before
+ * any of the other functions can be used again.  If the pointer of an instance created with `createInstance` is
+ * supplied, only that instance is stopped.
  */
-export const cleanupRuntime = () => {
-  cleanupRuntimeInner(getEnginePointer(), getTreePointer());
+export const cleanupRuntime = (enginePointer = getEnginePointer()) => {
+  cleanupRuntimeInner(enginePointer);
+
+  if(instanceCanvasIds[enginePointer] !== undefined) {
+    delete Module.canvases[instanceCanvasIds[enginePointer]];
+    delete instanceCanvasIds[enginePointer];
+  } else {
+    store.dispatch(setEnginePointer(0));
+  }
 };
 
 // export const render_single_frame = Module.cwrap('render_single_frame', null, []);